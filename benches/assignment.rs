@@ -0,0 +1,99 @@
+//! Label assignment of kg-entities on generated entity files with different
+//! distributions of ambiguous labels. Label assignment is part of the
+//! binary, so every iteration runs it on the file, including parsing and
+//! writing the outputs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const NUM_ENTITIES: usize = 20_000;
+
+/// Number of entities sharing a label.
+enum Ambiguity {
+    /// every label is unique
+    Unique,
+    /// every label is shared by this many entities
+    Groups(usize),
+    /// few labels are shared by many entities, most labels by few
+    Skewed,
+}
+
+fn label_id(ambiguity: &Ambiguity, i: usize) -> usize {
+    match ambiguity {
+        Ambiguity::Unique => i,
+        Ambiguity::Groups(size) => i / size,
+        Ambiguity::Skewed => {
+            // squaring a pseudo random number in [0, 1) favors small ids
+            let x = (i.wrapping_mul(2_654_435_761) % NUM_ENTITIES) as f64 / NUM_ENTITIES as f64;
+            (x * x * NUM_ENTITIES as f64) as usize
+        }
+    }
+}
+
+fn write_entities(name: &str, ambiguity: &Ambiguity) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("assignment");
+    fs::create_dir_all(&dir).unwrap();
+    let mut content = "?ent\t?ent_name\t?ent_description\t?links\t?types\t?aliases\n".to_string();
+    for i in 0..NUM_ENTITIES {
+        let label = label_id(ambiguity, i);
+        // half of the entities share their description with another one
+        content.push_str(&format!(
+            "<http://www.wikidata.org/entity/Q{}>\t\"label {label}\"@en\t\
+             \"description {}\"@en\t{}\t\t\"alias {}; label {}\"\n",
+            i + 1,
+            i / 2,
+            NUM_ENTITIES - i,
+            i % 1000,
+            label + 1
+        ));
+    }
+    let file = dir.join(format!("{name}.tsv"));
+    fs::write(&file, content).unwrap();
+    file
+}
+
+fn kg_entities(file: &Path, output: &Path) {
+    let status = Command::new(env!("CARGO_BIN_EXE_kg-entities"))
+        .arg("--file")
+        .arg(file)
+        .arg("--output")
+        .arg(output)
+        .args([
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+            "--disambiguate",
+            "all",
+            "--ignore-types",
+            "--force",
+        ])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+fn label_assignment(c: &mut Criterion) {
+    let mut group = c.benchmark_group("label_assignment");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(NUM_ENTITIES as u64));
+    for (name, ambiguity) in [
+        ("unique", Ambiguity::Unique),
+        ("pairs", Ambiguity::Groups(2)),
+        ("groups-of-50", Ambiguity::Groups(50)),
+        ("skewed", Ambiguity::Skewed),
+    ] {
+        let file = write_entities(name, &ambiguity);
+        let output = file.with_extension("out");
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| kg_entities(&file, &output))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, label_assignment);
+criterion_main!(benches);
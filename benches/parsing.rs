@@ -0,0 +1,110 @@
+//! Parsing of synthetic entity and property lines of every knowledge graph.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sparql_data_preparation::{KnowledgeGraph, KnowledgeGraphProcessor};
+
+const NUM_LINES: usize = 10_000;
+
+fn entity_lines(kg: &KnowledgeGraph) -> Vec<String> {
+    (0..NUM_LINES)
+        .map(|i| {
+            let (ent, typ) = match kg {
+                KnowledgeGraph::Wikidata => (
+                    format!("<http://www.wikidata.org/entity/Q{i}>"),
+                    format!("<http://www.wikidata.org/entity/Q{}>", i % 100),
+                ),
+                KnowledgeGraph::Freebase => (
+                    format!("<http://rdf.freebase.com/ns/m.0{i:x}>"),
+                    format!("<http://rdf.freebase.com/ns/m.0{:x}>", i % 100),
+                ),
+                KnowledgeGraph::DBPedia => (
+                    format!("<http://dbpedia.org/resource/Entity_{i}>"),
+                    format!("<http://dbpedia.org/ontology/Type{}>", i % 100),
+                ),
+            };
+            format!(
+                "{ent}\t\"entity {i}\"@en\t\"description of entity {i}\"@en\t{}\t{typ}\t\
+                 \"alias {i}; other alias {i}; ent {i}\"",
+                NUM_LINES - i
+            )
+        })
+        .collect()
+}
+
+fn property_lines(kg: &KnowledgeGraph) -> Vec<String> {
+    (0..NUM_LINES)
+        .map(|i| {
+            let (prop, inv) = match kg {
+                KnowledgeGraph::Wikidata => (
+                    format!("<http://www.wikidata.org/entity/P{i}>"),
+                    format!("<http://www.wikidata.org/entity/P{}>", i + 1),
+                ),
+                KnowledgeGraph::Freebase => (
+                    format!("<http://rdf.freebase.com/ns/domain.type.property_{i}>"),
+                    format!(
+                        "<http://rdf.freebase.com/ns/domain.type.property_{}>",
+                        i + 1
+                    ),
+                ),
+                KnowledgeGraph::DBPedia => (
+                    format!("<http://dbpedia.org/ontology/property{i}>"),
+                    format!("<http://dbpedia.org/property/property{}>", i + 1),
+                ),
+            };
+            format!(
+                "{prop}\t\"property {i}\"@en\t{}\t\"alias {i}; other alias {i}\"\t{inv}",
+                NUM_LINES - i
+            )
+        })
+        .collect()
+}
+
+const KNOWLEDGE_GRAPHS: [(&str, KnowledgeGraph); 3] = [
+    ("wikidata", KnowledgeGraph::Wikidata),
+    ("freebase", KnowledgeGraph::Freebase),
+    ("dbpedia", KnowledgeGraph::DBPedia),
+];
+
+fn parse_entity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_entity");
+    group.throughput(Throughput::Elements(NUM_LINES as u64));
+    for (name, kg) in KNOWLEDGE_GRAPHS {
+        let lines = entity_lines(&kg);
+        let processor = KnowledgeGraphProcessor::new(kg).unwrap();
+        for ignore_types in [false, true] {
+            let id = if ignore_types {
+                format!("{name}/ignore-types")
+            } else {
+                name.to_string()
+            };
+            group.bench_function(BenchmarkId::from_parameter(id), |b| {
+                b.iter(|| {
+                    for line in &lines {
+                        black_box(processor.parse_entity(line, ignore_types).unwrap());
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn parse_property(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_property");
+    group.throughput(Throughput::Elements(NUM_LINES as u64));
+    for (name, kg) in KNOWLEDGE_GRAPHS {
+        let lines = property_lines(&kg);
+        let processor = KnowledgeGraphProcessor::new(kg).unwrap();
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                for line in &lines {
+                    black_box(processor.parse_property(line).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse_entity, parse_property);
+criterion_main!(benches);
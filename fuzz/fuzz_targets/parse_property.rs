@@ -0,0 +1,27 @@
+#![no_main]
+
+use std::sync::OnceLock;
+
+use libfuzzer_sys::fuzz_target;
+use sparql_data_preparation::{KnowledgeGraph, KnowledgeGraphProcessor};
+
+fn processors() -> &'static [KnowledgeGraphProcessor] {
+    static PROCESSORS: OnceLock<Vec<KnowledgeGraphProcessor>> = OnceLock::new();
+    PROCESSORS.get_or_init(|| {
+        [
+            KnowledgeGraph::Wikidata,
+            KnowledgeGraph::Freebase,
+            KnowledgeGraph::DBPedia,
+        ]
+        .into_iter()
+        .map(|kg| KnowledgeGraphProcessor::new(kg).unwrap())
+        .collect()
+    })
+}
+
+// parsing may fail on malformed lines, but must never panic
+fuzz_target!(|line: &str| {
+    for kg in processors() {
+        let _ = kg.parse_property(line);
+    }
+});
@@ -0,0 +1,179 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::bail;
+
+use crate::cli::{BuildArgs, IoArgs};
+use crate::output::MANIFEST_FILE;
+use crate::{line_iter, KnowledgeGraph};
+
+/// Whether --knowledge-base lists several knowledge graphs, separated by
+/// commas, to build in one invocation.
+pub fn is_batch(knowledge_base: &str) -> bool {
+    knowledge_base.contains(',')
+}
+
+/// Options of every knowledge graph in a batch config, which has the
+/// knowledge graph, the option name (without --), and its value per line,
+/// separated by tabs, e.g. wikidata, file, and wikidata-entities.tsv. Empty
+/// lines and lines starting with # are skipped.
+pub fn read_batch_config(
+    file: impl AsRef<Path>,
+    knowledge_graphs: &[&str],
+) -> anyhow::Result<Vec<Vec<(String, String)>>> {
+    let file = file.as_ref();
+    let mut options = vec![vec![]; knowledge_graphs.len()];
+    for (i, line) in line_iter(file)?.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.split('\t').collect();
+        let [kg, name, value] = fields[..] else {
+            bail!(
+                "invalid line {} in batch config {}: {line}",
+                i + 1,
+                file.display()
+            );
+        };
+        // knowledge graphs of the config that are not built are skipped
+        if let Some(idx) = knowledge_graphs.iter().position(|&k| k == kg.trim()) {
+            options[idx].push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    for (kg, options) in knowledge_graphs.iter().zip(&options) {
+        if !options.iter().any(|(name, _)| name == "file") {
+            bail!("batch config {} has no input file for {kg}", file.display());
+        }
+    }
+    Ok(options)
+}
+
+/// Removes the given options and their values from command line arguments.
+/// Options are expected as `-o value`, `-ovalue`, `--option value`, or
+/// `--option=value`.
+fn remove_options(args: Vec<OsString>, options: &[(&str, &str)]) -> Vec<OsString> {
+    let mut kept = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let s = arg.to_string_lossy();
+        if options.iter().any(|&(short, long)| s == short || s == long) {
+            args.next();
+        } else if !options.iter().any(|&(short, long)| {
+            s.starts_with(&format!("{long}=")) || (s.starts_with(short) && !s.starts_with("--"))
+        }) {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+/// Builds every knowledge graph of a comma separated --knowledge-base by
+/// running the current binary once per knowledge graph, all of them
+/// concurrently. --file is the batch config with the input files (see
+/// read_batch_config), every build is written to a subdirectory of --output
+/// named after its knowledge graph, and a manifest of all their files is
+/// written to --output. Returns the statistics printed by the builds, in the
+/// order of the knowledge graphs.
+pub fn run_batch(io: &IoArgs, build: &BuildArgs) -> anyhow::Result<String> {
+    let knowledge_graphs: Vec<_> = build.knowledge_base.split(',').map(str::trim).collect();
+    for (i, kg) in knowledge_graphs.iter().enumerate() {
+        KnowledgeGraph::try_from(*kg)?;
+        if knowledge_graphs[..i].contains(kg) {
+            bail!("knowledge graph {kg} is given more than once");
+        }
+    }
+    let options = read_batch_config(&io.file, &knowledge_graphs)?;
+    let manifest = io.output.join(MANIFEST_FILE);
+    if !build.dry_run {
+        if manifest.exists() && !build.force && !build.skip_unchanged {
+            bail!(
+                "output directory {} already contains a build, pass --force to overwrite it",
+                io.output.display()
+            );
+        }
+        fs::create_dir_all(&io.output)?;
+    }
+
+    let shared = remove_options(
+        env::args_os().skip(1).collect(),
+        &[
+            ("-f", "--file"),
+            ("-k", "--knowledge-base"),
+            ("-o", "--output"),
+        ],
+    );
+    let exe = env::current_exe()?;
+    let mut children = vec![];
+    for (kg, options) in knowledge_graphs.iter().zip(&options) {
+        let mut command = Command::new(&exe);
+        command
+            .args(&shared)
+            .arg("--knowledge-base")
+            .arg(kg)
+            .arg("--output")
+            .arg(io.output.join(kg));
+        for (name, value) in options {
+            command.arg(format!("--{name}")).arg(value);
+        }
+        children.push(command.stdout(Stdio::piped()).spawn()?);
+    }
+    let mut failed = vec![];
+    let mut stats = String::new();
+    for (kg, child) in knowledge_graphs.iter().zip(children) {
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            failed.push(*kg);
+        }
+        stats.push_str(&String::from_utf8_lossy(&output.stdout));
+    }
+    if !failed.is_empty() {
+        bail!("build failed for {}", failed.join(", "));
+    }
+
+    if !build.dry_run {
+        let mut lines = vec![];
+        for kg in &knowledge_graphs {
+            let sub_manifest = fs::read_to_string(io.output.join(kg).join(MANIFEST_FILE))?;
+            lines.extend(sub_manifest.lines().map(|line| format!("{kg}/{line}\n")));
+        }
+        lines.sort();
+        fs::write(manifest, lines.concat())?;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_are_removed_in_all_forms() {
+        let args = [
+            "entities",
+            "-kwikidata,dbpedia",
+            "--progress",
+            "-o",
+            "out",
+            "--file=batch.tsv",
+            "--min-count",
+            "2",
+            "-ffoo",
+            "--knowledge-base",
+            "wikidata",
+            "--output=out",
+        ];
+        let kept = remove_options(
+            args.iter().map(OsString::from).collect(),
+            &[
+                ("-f", "--file"),
+                ("-k", "--knowledge-base"),
+                ("-o", "--output"),
+            ],
+        );
+        assert_eq!(kept, ["entities", "--progress", "--min-count", "2"]);
+    }
+}
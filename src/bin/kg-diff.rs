@@ -0,0 +1,111 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+use clap::Parser;
+use itertools::Itertools;
+use sparql_data_preparation::stats::{Stats, StatsFormat};
+use sparql_data_preparation::{read_dump_date, record_iter, record_writer, FileFormat};
+
+/// Compares two index directories built by kg-entities or kg-properties.
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(long)]
+    old: PathBuf,
+
+    #[clap(long)]
+    new: PathBuf,
+
+    /// report the entities whose preferred surface form changed between the
+    /// dump dates of the two directories
+    #[clap(long)]
+    temporal: bool,
+
+    /// output file for the changed entities of --temporal
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    #[clap(long, default_value = "tsv")]
+    format: String,
+
+    #[clap(long, default_value = "text")]
+    stats_format: String,
+}
+
+/// Loads the preferred (first) surface form of every id in an index.
+fn load_index(dir: &Path, format: FileFormat) -> anyhow::Result<HashMap<String, String>> {
+    let mut index = HashMap::new();
+    for record in record_iter(dir.join(format.file_name("index")), format)? {
+        let record = record?;
+        let (Some(id), Some(label)) = (record.get(0), record.get(1)) else {
+            continue;
+        };
+        // qualifier rows of property indices repeat ids, keep the first one
+        index
+            .entry(id.to_string())
+            .or_insert_with(|| label.to_string());
+    }
+    Ok(index)
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let format = FileFormat::try_from(args.format.as_str())?;
+    let stats_format = StatsFormat::try_from(args.stats_format.as_str())?;
+
+    let (mut old_dir, mut new_dir) = (&args.old, &args.new);
+    let (mut old_date, mut new_date) = (read_dump_date(old_dir)?, read_dump_date(new_dir)?);
+    if args.temporal {
+        let (Some(old), Some(new)) = (&old_date, &new_date) else {
+            bail!("--temporal needs dump dates for both directories, build them with --dump-date");
+        };
+        // compare in chronological order
+        if old > new {
+            std::mem::swap(&mut old_dir, &mut new_dir);
+            std::mem::swap(&mut old_date, &mut new_date);
+        }
+    }
+
+    let old = load_index(old_dir, format)?;
+    let new = load_index(new_dir, format)?;
+    let old_ids: HashSet<_> = old.keys().collect();
+    let new_ids: HashSet<_> = new.keys().collect();
+
+    let changed: Vec<_> = old_ids
+        .intersection(&new_ids)
+        .filter(|&&id| old[id] != new[id])
+        .sorted()
+        .map(|&id| (id, &old[id], &new[id]))
+        .collect();
+
+    let mut stats = Stats::new(match (&old_date, &new_date) {
+        (Some(old), Some(new)) => format!("index diff {old} -> {new}"),
+        _ => "index diff".to_string(),
+    });
+    stats.count("old_ids", old_ids.len());
+    stats.count("new_ids", new_ids.len());
+    stats.count("added_ids", new_ids.difference(&old_ids).count());
+    stats.count("removed_ids", old_ids.difference(&new_ids).count());
+    stats.count("changed_surface_forms", changed.len());
+
+    if args.temporal {
+        if let Some(output) = &args.output {
+            let old_date = old_date.unwrap_or_default();
+            let new_date = new_date.unwrap_or_default();
+            let mut output = record_writer(output, format)?;
+            for (id, old_label, new_label) in &changed {
+                output.write_record([id, &old_date, old_label, &new_date, new_label])?;
+            }
+            output.flush()?;
+        } else {
+            for (id, old_label, new_label) in &changed {
+                println!("{id}: {old_label} -> {new_label}");
+            }
+        }
+    }
+
+    print!("{}", stats.render(stats_format));
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use clap::Parser;
+use sparql_data_preparation::cli::IoArgs;
+use sparql_data_preparation::stats::Stats;
+use sparql_data_preparation::{
+    line_iter, progress_bar, record_iter, record_writer, KnowledgeGraph, KnowledgeGraphProcessor,
+};
+
+/// Joins an embedding file (one id and its vector per line, e.g. exported
+/// from PyTorch BigGraph) with an index built by kg-entities or
+/// kg-properties, and writes the vectors in the order of the index rows.
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(flatten)]
+    io: IoArgs,
+
+    /// embedding file with an id followed by the vector components per line,
+    /// separated by tabs or spaces
+    #[clap(short, long)]
+    embeddings: PathBuf,
+
+    /// match entity ids of this knowledge graph (wikidata, freebase, or
+    /// dbpedia) by their local id, so iris and short ids (Q42) both match
+    #[clap(short, long)]
+    knowledge_base: Option<String>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let input_format = args.io.input_format()?;
+    let output_format = args.io.output_format()?;
+    let stats_format = args.io.stats_format()?;
+    let kg = args
+        .knowledge_base
+        .as_deref()
+        .map(|kg| KnowledgeGraphProcessor::new(KnowledgeGraph::try_from(kg)?))
+        .transpose()?;
+    let key = |id: &str| -> String {
+        let id = id.trim();
+        kg.as_ref()
+            .and_then(|kg| kg.parse_entity_id(id))
+            .unwrap_or(id.trim_start_matches('<').trim_end_matches('>'))
+            .to_string()
+    };
+
+    let mut index_ids = vec![];
+    for record in record_iter(&args.io.file, input_format)? {
+        let record = record?;
+        let Some(id) = record.get(0) else {
+            continue;
+        };
+        index_ids.push(id.to_string());
+    }
+    let mut rows: HashMap<String, Vec<usize>> = HashMap::new();
+    for (row, id) in index_ids.iter().enumerate() {
+        rows.entry(key(id)).or_default().push(row);
+    }
+
+    let mut vectors: Vec<Option<Vec<String>>> = vec![None; index_ids.len()];
+    let mut dim = None;
+    let mut num_embeddings = 0;
+    let mut num_unused = 0;
+    let pbar = progress_bar("reading embeddings", u64::MAX, !args.io.progress);
+    for (i, line) in pbar.wrap_iter(line_iter(&args.embeddings)?).enumerate() {
+        let line = line?;
+        let mut fields = line.split_whitespace();
+        let Some(id) = fields.next() else {
+            continue;
+        };
+        let vector: Vec<_> = fields.map(str::to_string).collect();
+        // word2vec text files start with the number of vectors and their
+        // dimension
+        if i == 0 && vector.len() == 1 && id.parse::<usize>().is_ok() {
+            continue;
+        }
+        if let Some(invalid) = vector.iter().find(|v| v.parse::<f32>().is_err()) {
+            bail!(
+                "invalid vector component {invalid} of {id} in line {}",
+                i + 1
+            );
+        }
+        match dim {
+            None => dim = Some(vector.len()),
+            Some(dim) if dim != vector.len() => bail!(
+                "vector of {id} in line {} has dimension {}, expected {dim}",
+                i + 1,
+                vector.len()
+            ),
+            _ => (),
+        }
+        num_embeddings += 1;
+        let Some(id_rows) = rows.get(&key(id)) else {
+            num_unused += 1;
+            continue;
+        };
+        for &row in id_rows {
+            vectors[row] = Some(vector.clone());
+        }
+    }
+    pbar.finish_and_clear();
+    let Some(dim) = dim else {
+        bail!("{} contains no vectors", args.embeddings.display());
+    };
+
+    // ids without a vector get a zero vector, so row i of the output always
+    // belongs to row i of the index
+    let zeros = vec!["0".to_string(); dim];
+    let mut output = record_writer(&args.io.output, output_format)?;
+    let mut num_missing = 0;
+    for (id, vector) in index_ids.iter().zip(&vectors) {
+        let vector = vector.as_ref().unwrap_or_else(|| {
+            num_missing += 1;
+            &zeros
+        });
+        output.write_record([id].into_iter().chain(vector))?;
+    }
+    output.flush()?;
+
+    let num_matched_ids = rows
+        .values()
+        .filter(|id_rows| vectors[id_rows[0]].is_some())
+        .count();
+    let mut stats = Stats::new("embedding join");
+    stats.count("index_rows", index_ids.len());
+    stats.count("embeddings", num_embeddings);
+    stats.count("dimension", dim);
+    stats.count("matched_ids", num_matched_ids);
+    stats.count("missing_vectors", num_missing);
+    stats.percent(
+        "vector_coverage",
+        index_ids.len() - num_missing,
+        index_ids.len(),
+    );
+    stats.count("unused_embeddings", num_unused);
+    print!("{}", stats.render(stats_format));
+
+    Ok(())
+}
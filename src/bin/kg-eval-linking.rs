@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use anyhow::bail;
+use clap::Parser;
+use sparql_data_preparation::candidates::CandidateGenerator;
+use sparql_data_preparation::cli::IoArgs;
+use sparql_data_preparation::stats::Stats;
+use sparql_data_preparation::{
+    progress_bar, record_iter, record_writer, KnowledgeGraph, KnowledgeGraphProcessor,
+};
+
+/// Evaluates the candidates an index built by kg-entities gives for the
+/// mentions of an entity linking dataset (e.g. AIDA or Mewsli, with one
+/// mention and its gold entity per line), and reports the recall of the
+/// gold entities among the top k candidates.
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(flatten)]
+    io: IoArgs,
+
+    /// index built by kg-entities
+    #[clap(short, long)]
+    index: PathBuf,
+
+    /// match entity ids of this knowledge graph (wikidata, freebase, or
+    /// dbpedia) by their local id, so iris and short ids (Q42) both match
+    #[clap(short, long)]
+    knowledge_base: Option<String>,
+
+    /// number of candidates to report the recall for, can be given several
+    /// times
+    #[clap(short, long, default_values_t = [1, 5, 10])]
+    top: Vec<usize>,
+
+    /// maximum edit distance of fuzzy matches, 0 disables fuzzy matching
+    #[clap(long, default_value_t = 1)]
+    max_edit_distance: u32,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let input_format = args.io.input_format()?;
+    let output_format = args.io.output_format()?;
+    let stats_format = args.io.stats_format()?;
+    let Some(&max_top) = args.top.iter().max() else {
+        bail!("--top needs at least one number of candidates");
+    };
+    if max_top == 0 {
+        bail!("--top must be positive");
+    }
+    let kg = args
+        .knowledge_base
+        .as_deref()
+        .map(|kg| KnowledgeGraphProcessor::new(KnowledgeGraph::try_from(kg)?))
+        .transpose()?;
+    let key = |id: &str| -> String {
+        let id = id.trim();
+        kg.as_ref()
+            .and_then(|kg| kg.parse_entity_id(id))
+            .unwrap_or(id.trim_start_matches('<').trim_end_matches('>'))
+            .to_string()
+    };
+
+    let generator = CandidateGenerator::from_index(&args.index, input_format)?
+        .with_max_edit_distance(args.max_edit_distance)
+        .with_max_candidates(max_top);
+
+    let pbar = progress_bar("linking mentions", u64::MAX, !args.io.progress);
+    let mut output = record_writer(&args.io.output, output_format)?;
+    let mut num_mentions = 0;
+    let mut num_nil = 0;
+    let mut num_without_candidates = 0;
+    // number of mentions with their gold entity at every rank
+    let mut num_at_rank = vec![0; max_top];
+    for record in pbar.wrap_iter(record_iter(&args.io.file, input_format)?) {
+        let record = record?;
+        let (Some(mention), Some(gold)) = (record.get(0), record.get(1)) else {
+            continue;
+        };
+        // mentions of entities that are not in the knowledge graph, e.g.
+        // --NME-- in AIDA
+        if gold.trim().is_empty() || gold == "--NME--" {
+            num_nil += 1;
+            continue;
+        }
+        num_mentions += 1;
+        let candidates = generator.candidates(mention);
+        if candidates.is_empty() {
+            num_without_candidates += 1;
+        }
+        let gold_key = key(gold);
+        let rank = candidates.iter().position(|c| key(&c.id) == gold_key);
+        if let Some(rank) = rank {
+            num_at_rank[rank] += 1;
+        }
+        let top = candidates.first().map_or("", |c| c.id.as_str());
+        output.write_record([
+            mention,
+            gold,
+            &rank.map_or(String::new(), |rank| (rank + 1).to_string()),
+            top,
+        ])?;
+    }
+    output.flush()?;
+    pbar.finish_and_clear();
+
+    let mut stats = Stats::new("entity linking evaluation");
+    stats.count("mentions", num_mentions);
+    stats.count("nil_mentions", num_nil);
+    stats.count("mentions_without_candidates", num_without_candidates);
+    let mut top = args.top.clone();
+    top.sort();
+    top.dedup();
+    for k in top {
+        let num_found = num_at_rank[..k].iter().sum();
+        stats.percent(&format!("recall_at_{k}"), num_found, num_mentions);
+    }
+    print!("{}", stats.render(stats_format));
+
+    Ok(())
+}
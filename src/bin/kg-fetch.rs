@@ -0,0 +1,17 @@
+use clap::Parser;
+use sparql_data_preparation::fetch::{run, FetchArgs};
+
+/// Runs the queries for the input files of the other binaries against a
+/// SPARQL endpoint.
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(flatten)]
+    fetch: FetchArgs,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse().fetch;
+    let stats_format = args.stats_format()?;
+    print!("{}", run(args)?.render(stats_format));
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use clap::Parser;
+use itertools::Itertools;
+use rayon::prelude::*;
+use sparql_data_preparation::cli::IoArgs;
+use sparql_data_preparation::stats::Stats;
+use sparql_data_preparation::{line_iter, progress_bar, record_iter, record_writer};
+
+/// Counts how often the surface forms of an index built by kg-entities or
+/// kg-properties occur in a text corpus, and writes the index with the
+/// frequencies of every row's surface forms as an extra last column.
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(flatten)]
+    io: IoArgs,
+
+    /// text file with the corpus, can be given several times
+    #[clap(short, long, required = true)]
+    corpus: Vec<PathBuf>,
+
+    /// match surface forms case sensitively
+    #[clap(long)]
+    case_sensitive: bool,
+
+    /// keep parenthesized suffixes like "(city in France)" when matching,
+    /// which are usually added to tell entities apart and are rare in text
+    #[clap(long)]
+    keep_info: bool,
+}
+
+/// Number of corpus lines counted at once.
+const CHUNK_SIZE: usize = 100_000;
+
+/// Splits text into words at everything but letters and digits.
+fn tokenize(s: &str, case_sensitive: bool) -> Vec<String> {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            if case_sensitive {
+                word.to_string()
+            } else {
+                word.to_lowercase()
+            }
+        })
+        .collect()
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let input_format = args.io.input_format()?;
+    let output_format = args.io.output_format()?;
+    let stats_format = args.io.stats_format()?;
+
+    let records: Vec<_> =
+        record_iter(&args.io.file, input_format)?.collect::<anyhow::Result<_>>()?;
+    // surface forms are matched by their words, so punctuation and spacing
+    // do not matter
+    let mut forms: HashMap<String, usize> = HashMap::new();
+    let mut row_forms = vec![];
+    let mut max_words = 0;
+    for record in &records {
+        let ids: Vec<_> = record
+            .iter()
+            .skip(1)
+            .map(|form| {
+                let form = if args.keep_info {
+                    form
+                } else {
+                    form.split(" (").next().unwrap_or(form)
+                };
+                let words = tokenize(form, args.case_sensitive);
+                max_words = max_words.max(words.len());
+                let num_forms = forms.len();
+                *forms.entry(words.join(" ")).or_insert(num_forms)
+            })
+            .collect();
+        row_forms.push(ids);
+    }
+
+    let pbar = progress_bar("counting surface forms", u64::MAX, !args.io.progress);
+    let mut frequencies = vec![0; forms.len()];
+    let mut num_lines = 0;
+    for corpus in &args.corpus {
+        // counted in chunks, so large corpora are never loaded at once
+        for chunk in &pbar.wrap_iter(line_iter(corpus)?).chunks(CHUNK_SIZE) {
+            let lines: Vec<_> = chunk.collect::<anyhow::Result<_>>()?;
+            num_lines += lines.len();
+            let counts = lines
+                .par_iter()
+                .fold(HashMap::new, |mut counts: HashMap<usize, usize>, line| {
+                    let words = tokenize(line, args.case_sensitive);
+                    for start in 0..words.len() {
+                        let end = words.len().min(start + max_words);
+                        for n in start + 1..=end {
+                            if let Some(&id) = forms.get(&words[start..n].join(" ")) {
+                                *counts.entry(id).or_default() += 1;
+                            }
+                        }
+                    }
+                    counts
+                })
+                .reduce(HashMap::new, |mut a, b| {
+                    for (id, count) in b {
+                        *a.entry(id).or_default() += count;
+                    }
+                    a
+                });
+            for (id, count) in counts {
+                frequencies[id] += count;
+            }
+        }
+    }
+    pbar.finish_and_clear();
+
+    let mut output = record_writer(&args.io.output, output_format)?;
+    let mut num_unseen_rows = 0;
+    for (record, ids) in records.iter().zip(&row_forms) {
+        if ids.iter().all(|&id| frequencies[id] == 0) {
+            num_unseen_rows += 1;
+        }
+        let column = ids.iter().map(|&id| frequencies[id]).join("; ");
+        output.write_record(record.iter().chain([column.as_str()]))?;
+    }
+    output.flush()?;
+
+    let num_seen_forms = frequencies.iter().filter(|&&freq| freq > 0).count();
+    let mut stats = Stats::new("surface form frequency");
+    stats.count("index_rows", records.len());
+    stats.count("surface_forms", forms.len());
+    stats.count("corpus_lines", num_lines);
+    stats.count("seen_surface_forms", num_seen_forms);
+    stats.percent("surface_form_coverage", num_seen_forms, forms.len());
+    stats.count("unseen_rows", num_unseen_rows);
+    print!("{}", stats.render(stats_format));
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use clap::{Parser, Subcommand};
+use sparql_data_preparation::batch::{is_batch, run_batch};
+use sparql_data_preparation::entities::{self, EntitiesArgs};
+use sparql_data_preparation::mapping::{self, MappingArgs};
+use sparql_data_preparation::properties::{self, PropertiesArgs};
+use sparql_data_preparation::redirects::{self, RedirectsArgs};
+use sparql_data_preparation::summary::{self, SummaryArgs};
+
+/// Builds and inspects natural language indices of knowledge graphs.
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// build an entity index (same as kg-entities)
+    Entities(Box<EntitiesArgs>),
+    /// build an entity index from the merged entity files of shards (same
+    /// as kg-merge-shards)
+    MergeShards(Box<EntitiesArgs>),
+    /// update an entity index with the delta of a newer dump (same as
+    /// kg-update)
+    Update(Box<EntitiesArgs>),
+    /// build a property index (same as kg-properties)
+    Properties(Box<PropertiesArgs>),
+    /// normalize a redirect file for --redirects of entity builds
+    Redirects(RedirectsArgs),
+    /// print statistics of an existing index
+    Stats(SummaryArgs),
+    /// map the entities of a Freebase index to those of a Wikidata index
+    /// with the published mapping between mids and qids
+    Mapping(MappingArgs),
+}
+
+fn main() -> anyhow::Result<()> {
+    let command = Args::parse().command;
+    // builds of several knowledge graphs run this binary once per graph
+    let build = match &command {
+        Command::Entities(args) | Command::MergeShards(args) | Command::Update(args) => {
+            Some((&args.io, &args.build))
+        }
+        Command::Properties(args) => Some((&args.io, &args.build)),
+        _ => None,
+    };
+    if let Some((io, build)) = build.filter(|(_, build)| is_batch(&build.knowledge_base)) {
+        print!("{}", run_batch(io, build)?);
+        return Ok(());
+    }
+    let (stats_format, stats) = match command {
+        Command::Entities(args) => (args.io.stats_format()?, entities::run(*args)?),
+        Command::MergeShards(args) => (args.io.stats_format()?, entities::merge_shards(*args)?),
+        Command::Update(args) => (args.io.stats_format()?, entities::update(*args)?),
+        Command::Properties(args) => (args.io.stats_format()?, properties::run(*args)?),
+        Command::Redirects(args) => (args.io.stats_format()?, Some(redirects::run(args)?)),
+        Command::Stats(args) => (args.stats_format()?, Some(summary::run(args)?)),
+        Command::Mapping(args) => (args.stats_format()?, Some(mapping::run(args)?)),
+    };
+    // None if --skip-unchanged found the index up to date
+    if let Some(stats) = stats {
+        print!("{}", stats.render(stats_format));
+    }
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use clap::Parser;
+use sparql_data_preparation::batch::{is_batch, run_batch};
+use sparql_data_preparation::entities::{merge_shards, EntitiesArgs};
+
+/// Builds one entity index from the merged entity files of the shards of a
+/// dump (written with kg-entities --save-merged), same as kg-entities
+/// --from-merged with the other shards given with --shard.
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(flatten)]
+    entities: EntitiesArgs,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse().entities;
+    if is_batch(&args.build.knowledge_base) {
+        print!("{}", run_batch(&args.io, &args.build)?);
+        return Ok(());
+    }
+    let stats_format = args.io.stats_format()?;
+    if let Some(stats) = merge_shards(args)? {
+        print!("{}", stats.render(stats_format));
+    }
+    Ok(())
+}
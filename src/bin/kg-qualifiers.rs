@@ -0,0 +1,73 @@
+use clap::Parser;
+use regex::Regex;
+use sparql_data_preparation::cli::IoArgs;
+use sparql_data_preparation::stats::Stats;
+use sparql_data_preparation::{
+    progress_bar, record_iter, record_writer, wikidata_qualifier_groups, KnowledgeGraph,
+    KnowledgeGraphProcessor,
+};
+
+/// Creates a standalone index of the Wikidata qualifier variants
+/// (statement, qualifier, value, ...) from a property index built
+/// by kg-properties without --include-wikidata-qualifiers.
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(flatten)]
+    io: IoArgs,
+
+    #[clap(short, long)]
+    short_properties: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let kg = KnowledgeGraphProcessor::new(KnowledgeGraph::Wikidata)?;
+    let input_format = args.io.input_format()?;
+    let output_format = args.io.output_format()?;
+    let stats_format = args.io.stats_format()?;
+    // property ids in the index are either short (wdt:P31) or full iris
+    let index_prop_pattern =
+        Regex::new(r"^<?(?:wdt:|http://www\.wikidata\.org/prop/direct/)(P\d+)>?$")?;
+
+    let records: Vec<_> =
+        record_iter(&args.io.file, input_format)?.collect::<anyhow::Result<_>>()?;
+
+    let mut output = record_writer(&args.io.output, output_format)?;
+    let mut num_props = 0;
+    let mut num_qualifiers = 0;
+    let pbar = progress_bar(
+        "creating qualifier index",
+        records.len() as u64,
+        !args.io.progress,
+    );
+    for record in &records {
+        pbar.inc(1);
+        let Some(prop) = record
+            .get(0)
+            .and_then(|p| index_prop_pattern.captures(p.trim()))
+        else {
+            continue;
+        };
+        let prop = format!(
+            "http://www.wikidata.org/entity/{}",
+            prop.get(1).unwrap().as_str()
+        );
+        num_props += 1;
+        for (short, long, lbls) in wikidata_qualifier_groups(record.iter().skip(1)) {
+            let pfx = if args.short_properties { &short } else { &long };
+            let id = kg.format_property(&prop, args.short_properties, Some(pfx))?;
+            output.write_record([short, id].into_iter().chain(lbls))?;
+            num_qualifiers += 1;
+        }
+    }
+    pbar.finish_and_clear();
+    output.flush()?;
+
+    let mut stats = Stats::new("wikidata qualifiers");
+    stats.count("records", records.len());
+    stats.count("properties", num_props);
+    stats.count("qualifiers", num_qualifiers);
+    print!("{}", stats.render(stats_format));
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use clap::Parser;
+use sparql_data_preparation::batch::{is_batch, run_batch};
+use sparql_data_preparation::entities::{update, EntitiesArgs};
+
+/// Updates an entity index with the delta entity file of a newer dump
+/// (--delta), applied to the merged entities of the previous build (--file,
+/// written with kg-entities --save-merged), same as kg-entities
+/// --from-merged --delta.
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(flatten)]
+    entities: EntitiesArgs,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse().entities;
+    if is_batch(&args.build.knowledge_base) {
+        print!("{}", run_batch(&args.io, &args.build)?);
+        return Ok(());
+    }
+    let stats_format = args.io.stats_format()?;
+    if let Some(stats) = update(args)? {
+        print!("{}", stats.render(stats_format));
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::open_input;
+
+/// Magic bytes at the start of a decompressed entity cache.
+const CACHE_MAGIC: &[u8; 8] = b"KGCACHE2";
+
+/// Compression level of entity caches, a trade-off that favors writing
+/// quickly over the last bytes.
+const CACHE_LEVEL: i32 = 3;
+
+/// A merged entity record, as written with kg-entities --save-merged: the
+/// parsed entity with its redirects, count, and types
+/// resolved to labels. Records with only the redirect labels of an entity
+/// of another shard have no label. Written records borrow from the build,
+/// read ones own their strings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MergedEntity<'a> {
+    pub id: Cow<'a, str>,
+    #[serde(default)]
+    pub label: Option<Cow<'a, str>>,
+    #[serde(default)]
+    pub description: Cow<'a, str>,
+    #[serde(default)]
+    pub count: usize,
+    #[serde(default)]
+    pub types: Vec<Cow<'a, str>>,
+    #[serde(default)]
+    pub aliases: Vec<Cow<'a, str>>,
+    #[serde(default)]
+    pub redirects: Option<Vec<Cow<'a, str>>>,
+    #[serde(default)]
+    pub redirect_labels: Option<Vec<Cow<'a, str>>>,
+    #[serde(default)]
+    pub class: bool,
+}
+
+/// Writes merged entity records one at a time as a zstd-compressed bincode
+/// file, the format of kg-entities --save-merged. The decompressed file
+/// starts with `KGCACHE2`, followed by the number of records and the
+/// records.
+pub struct EntityCacheWriter {
+    writer: zstd::Encoder<'static, BufWriter<File>>,
+    num_left: usize,
+}
+
+impl EntityCacheWriter {
+    /// Creates the cache for the given number of records.
+    pub fn new(file: impl AsRef<Path>, num_records: usize) -> anyhow::Result<Self> {
+        let mut writer = zstd::Encoder::new(BufWriter::new(File::create(file)?), CACHE_LEVEL)?;
+        writer.write_all(CACHE_MAGIC)?;
+        bincode::serialize_into(&mut writer, &(num_records as u64))?;
+        Ok(Self {
+            writer,
+            num_left: num_records,
+        })
+    }
+
+    pub fn write(&mut self, record: &MergedEntity) -> anyhow::Result<()> {
+        if self.num_left == 0 {
+            bail!("more records than announced for the entity cache");
+        }
+        self.num_left -= 1;
+        bincode::serialize_into(&mut self.writer, record)?;
+        Ok(())
+    }
+
+    /// Completes the cache, which must have all of its records.
+    pub fn finish(self) -> anyhow::Result<()> {
+        if self.num_left > 0 {
+            bail!("{} records of the entity cache are missing", self.num_left);
+        }
+        self.writer.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads the merged entity records of a cache written with
+/// [`EntityCacheWriter`].
+pub fn read_entity_cache(file: impl AsRef<Path>) -> anyhow::Result<Vec<MergedEntity<'static>>> {
+    let file = file.as_ref();
+    let mut reader = open_input(file)?;
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != CACHE_MAGIC {
+        bail!("{} is no entity cache", file.display());
+    }
+    let num_records: u64 = bincode::deserialize_from(&mut reader)?;
+    let mut records = Vec::with_capacity(num_records as usize);
+    for _ in 0..num_records {
+        records.push(bincode::deserialize_from(&mut reader)?);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entity_cache_round_trip() {
+        let label = String::from("Paris");
+        let records = [
+            MergedEntity {
+                id: Cow::Borrowed("Q90"),
+                label: Some(Cow::Borrowed(&label)),
+                description: Cow::Borrowed("capital of France"),
+                count: 400,
+                types: vec![Cow::Borrowed("city")],
+                aliases: vec![Cow::Borrowed("City of Light")],
+                redirects: Some(vec![Cow::Borrowed("Q7")]),
+                redirect_labels: None,
+                class: false,
+            },
+            MergedEntity {
+                id: Cow::Borrowed("Q64"),
+                redirect_labels: Some(vec![Cow::Borrowed("Berlin, Germany")]),
+                ..Default::default()
+            },
+        ];
+        let file = std::env::temp_dir().join(format!("entity-cache-{}.bin", std::process::id()));
+        let mut writer = EntityCacheWriter::new(&file, records.len()).unwrap();
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        let read = read_entity_cache(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(read, records);
+
+        // the number of records is written first and must match
+        let mut writer = EntityCacheWriter::new(&file, 2).unwrap();
+        writer.write(&records[0]).unwrap();
+        assert!(writer.finish().is_err());
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use deunicode::deunicode;
+use fst::automaton::Levenshtein;
+use fst::{IntoStreamer, Map, Streamer};
+
+use crate::{
+    edit_distance, format_surface_form, normalize_label, parse_surface_form, record_iter,
+    FileFormat, SurfaceFormEscaping,
+};
+
+/// Edit distance beyond the fuzzy matches within which
+/// [`CandidateGenerator::near_misses`] looks for surface forms.
+pub const NEAR_MISS_DISTANCE: u32 = 2;
+
+/// How a mention matched a surface form, from the most to the least
+/// reliable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchKind {
+    /// the mention is the surface form
+    Exact,
+    /// equal after lowercasing, removing punctuation, and removing
+    /// parenthesized suffixes like "(city in France)"
+    CaseFolded,
+    /// equal after additionally transliterating to ASCII, e.g. "Zürich" and
+    /// "Zurich"
+    AsciiFolded,
+    /// ASCII folded forms within the maximum edit distance
+    Fuzzy(u32),
+}
+
+impl MatchKind {
+    /// Score of the match between 0 and 1.
+    pub fn score(&self) -> f64 {
+        match self {
+            MatchKind::Exact => 1.0,
+            MatchKind::CaseFolded => 0.9,
+            MatchKind::AsciiFolded => 0.8,
+            MatchKind::Fuzzy(distance) => 0.7 / (1 + distance) as f64,
+        }
+    }
+}
+
+/// An entity a mention may refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// id of the entity, as in the index
+    pub id: String,
+    /// row of the entity in the index, counted from 0
+    pub row: usize,
+    /// surface form of the entity that matched
+    pub form: String,
+    /// how the mention matched the form
+    pub kind: MatchKind,
+    /// product of the match score and the prior of the entity
+    pub score: f64,
+}
+
+/// Why a mention has no candidates, to debug the recall of an index.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NearMisses {
+    /// the mention case folded and transliterated to ASCII, as it was
+    /// looked up
+    pub folded: String,
+    /// whether the mention was not looked up fuzzily, since it is too short
+    /// or fuzzy matching is disabled
+    pub fuzzy_skipped: bool,
+    /// surface forms sharing the longest prefix with the folded mention,
+    /// most popular first
+    pub prefix: Vec<String>,
+    /// surface forms within a larger edit distance than fuzzy matches, with
+    /// their distance, closest first
+    pub closest: Vec<(String, u32)>,
+}
+
+/// Generates candidate entities for mentions from an index built by
+/// kg-entities or kg-properties, whose rows have an id followed by the
+/// surface forms and are sorted by descending popularity.
+///
+/// A mention is looked up exactly, case folded, ASCII folded, and only if
+/// none of these match, fuzzily (see [`MatchKind`]). Every entity keeps its
+/// best match, and is scored by the match score times its prior
+/// 1 / (1 + log2(1 + row)), so popular entities come first among equally
+/// good matches.
+pub struct CandidateGenerator {
+    ids: Vec<String>,
+    forms: Vec<Vec<String>>,
+    exact: HashMap<String, Vec<(usize, usize)>>,
+    case_folded: HashMap<String, Vec<(usize, usize)>>,
+    ascii_folded: Vec<Vec<(usize, usize)>>,
+    ascii_keys: Map<Vec<u8>>,
+    max_edit_distance: u32,
+    max_candidates: usize,
+}
+
+/// Lowercases a label without punctuation.
+fn case_fold(label: &str) -> String {
+    normalize_label(label).to_lowercase()
+}
+
+/// Case folds a label and transliterates it to ASCII.
+fn ascii_fold(label: &str) -> String {
+    case_fold(&deunicode(label))
+}
+
+impl CandidateGenerator {
+    /// Creates a generator from rows of an id and its surface forms, most
+    /// popular first.
+    pub fn new(rows: impl IntoIterator<Item = (String, Vec<String>)>) -> anyhow::Result<Self> {
+        Self::with_escaping(rows, SurfaceFormEscaping::None)
+    }
+
+    /// Creates a generator from rows whose surface forms are escaped, see
+    /// [`parse_surface_form`]. The candidates have the unescaped forms.
+    pub fn with_escaping(
+        rows: impl IntoIterator<Item = (String, Vec<String>)>,
+        escaping: SurfaceFormEscaping,
+    ) -> anyhow::Result<Self> {
+        let mut ids = vec![];
+        let mut forms = vec![];
+        let mut exact: HashMap<_, Vec<_>> = HashMap::new();
+        let mut case_folded: HashMap<_, Vec<_>> = HashMap::new();
+        let mut ascii: HashMap<_, Vec<_>> = HashMap::new();
+        for (row, (id, row_forms)) in rows.into_iter().enumerate() {
+            let row_forms: Vec<_> = row_forms
+                .iter()
+                .map(|form| parse_surface_form(form, escaping))
+                .collect();
+            for (i, (label, _)) in row_forms.iter().enumerate() {
+                // the info is left out of the folded forms
+                case_folded
+                    .entry(case_fold(label))
+                    .or_default()
+                    .push((row, i));
+                ascii.entry(ascii_fold(label)).or_default().push((row, i));
+            }
+            let row_forms: Vec<_> = row_forms
+                .iter()
+                .map(|(label, info)| {
+                    format_surface_form(label, info.as_deref(), SurfaceFormEscaping::None)
+                })
+                .collect();
+            for (i, form) in row_forms.iter().enumerate() {
+                exact.entry(form.clone()).or_default().push((row, i));
+            }
+            ids.push(id);
+            forms.push(row_forms);
+        }
+        let mut ascii: Vec<_> = ascii.into_iter().collect();
+        ascii.sort();
+        let ascii_keys = Map::from_iter(
+            ascii
+                .iter()
+                .enumerate()
+                .map(|(i, (key, _))| (key.as_str(), i as u64)),
+        )?;
+        Ok(Self {
+            ids,
+            forms,
+            exact,
+            case_folded,
+            ascii_folded: ascii.into_iter().map(|(_, matches)| matches).collect(),
+            ascii_keys,
+            max_edit_distance: 1,
+            max_candidates: 10,
+        })
+    }
+
+    /// Creates a generator from an index file built by kg-entities or
+    /// kg-properties.
+    pub fn from_index(file: impl AsRef<Path>, format: FileFormat) -> anyhow::Result<Self> {
+        Self::from_escaped_index(file, format, SurfaceFormEscaping::None)
+    }
+
+    /// Creates a generator from an index file built with
+    /// --escape-surface-forms.
+    pub fn from_escaped_index(
+        file: impl AsRef<Path>,
+        format: FileFormat,
+        escaping: SurfaceFormEscaping,
+    ) -> anyhow::Result<Self> {
+        let mut rows = vec![];
+        for record in record_iter(file, format)? {
+            let record = record?;
+            let mut fields = record.iter().map(str::to_string);
+            let Some(id) = fields.next() else {
+                continue;
+            };
+            rows.push((id, fields.collect()));
+        }
+        Self::with_escaping(rows, escaping)
+    }
+
+    /// Sets the maximum edit distance of fuzzy matches (default 1), 0
+    /// disables fuzzy matching.
+    pub fn with_max_edit_distance(mut self, distance: u32) -> Self {
+        self.max_edit_distance = distance;
+        self
+    }
+
+    /// Sets the maximum number of candidates per mention (default 10).
+    pub fn with_max_candidates(mut self, num: usize) -> Self {
+        self.max_candidates = num;
+        self
+    }
+
+    /// Number of rows in the index.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Candidates for a mention, sorted by descending score. If there are
+    /// none, [`near_misses`](Self::near_misses) tells why.
+    pub fn candidates(&self, mention: &str) -> Vec<Candidate> {
+        let mut matches = vec![];
+        if let Some(exact) = self.exact.get(mention) {
+            matches.extend(exact.iter().map(|&m| (m, MatchKind::Exact)));
+        }
+        // a mention may come with a parenthesized suffix like the forms
+        let label = mention.split(" (").next().unwrap_or(mention);
+        if let Some(folded) = self.case_folded.get(&case_fold(label)) {
+            matches.extend(folded.iter().map(|&m| (m, MatchKind::CaseFolded)));
+        }
+        let ascii = ascii_fold(label);
+        if let Some(i) = self.ascii_keys.get(&ascii) {
+            let folded = &self.ascii_folded[i as usize];
+            matches.extend(folded.iter().map(|&m| (m, MatchKind::AsciiFolded)));
+        }
+        // fuzzy matches of short mentions are mostly noise
+        if matches.is_empty() && self.max_edit_distance > 0 && ascii.chars().count() > 3 {
+            matches.extend(self.fuzzy_matches(&ascii));
+        }
+
+        // every row keeps its best match, earlier forms win ties
+        matches.sort_by_key(|&((row, i), kind)| (row, kind, i));
+        let mut seen = HashSet::new();
+        let mut candidates: Vec<_> = matches
+            .into_iter()
+            .filter(|((row, _), _)| seen.insert(*row))
+            .map(|((row, i), kind)| Candidate {
+                id: self.ids[row].clone(),
+                row,
+                form: self.forms[row][i].clone(),
+                kind,
+                score: kind.score() / (1.0 + (1.0 + row as f64).log2()),
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.row.cmp(&b.row)));
+        candidates.truncate(self.max_candidates);
+        candidates
+    }
+
+    /// Diagnostics for a mention without candidates: the surface forms
+    /// closest to it by prefix and by edit distance (at most
+    /// [`NEAR_MISS_DISTANCE`] more than fuzzy matches), at most as many as
+    /// candidates each.
+    pub fn near_misses(&self, mention: &str) -> NearMisses {
+        let label = mention.split(" (").next().unwrap_or(mention);
+        let folded = ascii_fold(label);
+        let fuzzy_skipped = self.max_edit_distance == 0 || folded.chars().count() <= 3;
+
+        // the longest prefix of the mention that some forms start with
+        let mut prefix = vec![];
+        let mut end = folded.len();
+        while end > 0 && prefix.is_empty() {
+            let start = &folded[..end];
+            let mut stream = self.ascii_keys.range().ge(start).into_stream();
+            while let Some((key, i)) = stream.next() {
+                if !key.starts_with(start.as_bytes()) {
+                    break;
+                }
+                prefix.push(self.most_popular_form(i));
+            }
+            end = folded[..end].char_indices().last().map_or(0, |(i, _)| i);
+        }
+        prefix.sort();
+        let prefix = prefix
+            .into_iter()
+            .take(self.max_candidates)
+            .map(|(_, form)| form)
+            .collect();
+
+        let mut closest = vec![];
+        let distance = self.max_edit_distance + NEAR_MISS_DISTANCE;
+        if let Ok(automaton) = Levenshtein::new(&folded, distance) {
+            let mut stream = self.ascii_keys.search(automaton).into_stream();
+            while let Some((key, i)) = stream.next() {
+                let distance = edit_distance(&folded, &String::from_utf8_lossy(key)) as u32;
+                closest.push((distance, self.most_popular_form(i)));
+            }
+        }
+        closest.sort();
+        let closest = closest
+            .into_iter()
+            .take(self.max_candidates)
+            .map(|(distance, (_, form))| (form, distance))
+            .collect();
+        NearMisses {
+            folded,
+            fuzzy_skipped,
+            prefix,
+            closest,
+        }
+    }
+
+    /// Row and surface form of the most popular entity with an ASCII folded
+    /// form.
+    fn most_popular_form(&self, key: u64) -> (usize, String) {
+        let &(row, i) = self.ascii_folded[key as usize].iter().min().unwrap();
+        (row, self.forms[row][i].clone())
+    }
+
+    fn fuzzy_matches(&self, ascii: &str) -> Vec<((usize, usize), MatchKind)> {
+        // the automaton may become too large for long mentions
+        let Ok(automaton) = Levenshtein::new(ascii, self.max_edit_distance) else {
+            return vec![];
+        };
+        let mut matches = vec![];
+        let mut stream = self.ascii_keys.search(automaton).into_stream();
+        while let Some((key, i)) = stream.next() {
+            let distance = edit_distance(ascii, &String::from_utf8_lossy(key)) as u32;
+            let kind = MatchKind::Fuzzy(distance);
+            matches.extend(self.ascii_folded[i as usize].iter().map(|&m| (m, kind)));
+        }
+        matches
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+
+use crate::output::{input_fingerprint, OutputDir};
+use crate::stats::StatsFormat;
+use crate::{FileFormat, KnowledgeGraph, KnowledgeGraphProcessor};
+
+/// Input, output, and reporting arguments of all index binaries.
+#[derive(clap::Args, Debug, Clone)]
+#[clap(about = None, long_about = None)]
+pub struct IoArgs {
+    /// input file, or the batch config if --knowledge-base lists several
+    /// knowledge graphs
+    #[clap(short, long)]
+    pub file: PathBuf,
+
+    /// where to write the outputs
+    #[clap(short, long)]
+    pub output: PathBuf,
+
+    /// show progress bars
+    #[clap(short, long)]
+    pub progress: bool,
+
+    /// format of the input files (tsv or csv)
+    #[clap(long, default_value = "tsv")]
+    pub input_format: String,
+
+    /// format of the outputs (tsv, csv, or jsonl where supported)
+    #[clap(long, default_value = "tsv")]
+    pub output_format: String,
+
+    /// format of the statistics printed at the end (text or json)
+    #[clap(long, default_value = "text")]
+    pub stats_format: String,
+}
+
+impl IoArgs {
+    pub fn input_format(&self) -> anyhow::Result<FileFormat> {
+        FileFormat::try_from(self.input_format.as_str())
+    }
+
+    pub fn output_format(&self) -> anyhow::Result<FileFormat> {
+        FileFormat::try_from(self.output_format.as_str())
+    }
+
+    pub fn stats_format(&self) -> anyhow::Result<StatsFormat> {
+        StatsFormat::try_from(self.stats_format.as_str())
+    }
+
+    /// The arguments without those that do not change the outputs, for the
+    /// fingerprint of --skip-unchanged.
+    pub fn without_run_options(&self) -> Self {
+        Self {
+            progress: false,
+            ..self.clone()
+        }
+    }
+}
+
+/// Arguments of the binaries that build an index from a knowledge graph dump.
+#[derive(clap::Args, Debug, Clone)]
+#[clap(about = None, long_about = None)]
+pub struct BuildArgs {
+    /// knowledge graph of the input (wikidata, freebase, or dbpedia), or
+    /// several of them separated by commas to build them concurrently
+    #[clap(short, long)]
+    pub knowledge_base: String,
+
+    /// host of a dbpedia language chapter, e.g. de.dbpedia.org
+    #[clap(long)]
+    pub dbpedia_host: Option<String>,
+
+    /// leave out entities or properties whose count (e.g. sitelinks or
+    /// statements) is below this value
+    #[clap(long, default_value_t = 0)]
+    pub min_count: usize,
+
+    /// fail instead of warning if the header of the input file does not
+    /// have the expected columns
+    #[clap(long)]
+    pub strict: bool,
+
+    /// date (YYYY-MM-DD) of the dump, written to dump-date.txt in the output
+    /// directory
+    #[clap(long)]
+    pub dump_date: Option<String>,
+
+    /// check the inputs and estimate the size of the build from a sample of
+    /// the input file, without building the index
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// number of records parsed by --dry-run
+    #[clap(long, default_value_t = 1000)]
+    pub dry_run_sample: usize,
+
+    /// overwrite the output directory of a previous build
+    #[clap(long)]
+    pub force: bool,
+
+    /// skip the build if the output directory holds a complete build of a
+    /// previous --skip-unchanged run with the same arguments and unchanged
+    /// input files, and otherwise overwrite it like --force
+    #[clap(long)]
+    pub skip_unchanged: bool,
+
+    /// sample the memory usage of every stage and add it to the statistics
+    #[clap(long)]
+    pub memory_stats: bool,
+
+    /// measure the wall-clock time of every stage and add it to the
+    /// statistics
+    #[clap(long)]
+    pub timing_stats: bool,
+}
+
+impl BuildArgs {
+    /// Creates the processor for the knowledge graph, on the configured
+    /// dbpedia host if given.
+    pub fn processor(&self) -> anyhow::Result<KnowledgeGraphProcessor> {
+        let kg = KnowledgeGraph::try_from(self.knowledge_base.as_str())?;
+        match (kg, &self.dbpedia_host) {
+            (KnowledgeGraph::DBPedia, Some(host)) => {
+                KnowledgeGraphProcessor::with_dbpedia_host(host)
+            }
+            (_, Some(_)) => bail!("--dbpedia-host requires --knowledge-base dbpedia"),
+            (kg, None) => KnowledgeGraphProcessor::new(kg),
+        }
+    }
+
+    /// The arguments without those that do not change the outputs, for the
+    /// fingerprint of --skip-unchanged.
+    pub fn without_run_options(&self) -> Self {
+        Self {
+            force: false,
+            skip_unchanged: false,
+            ..self.clone()
+        }
+    }
+
+    /// Creates the output directory of the build. With --skip-unchanged it
+    /// returns None if the directory is up to date, and otherwise the
+    /// fingerprint of the parsed arguments (see
+    /// [`without_run_options`](Self::without_run_options)) and the inputs of
+    /// the build is written with its manifest.
+    pub fn output_dir(
+        &self,
+        dir: &Path,
+        args: &impl Debug,
+        inputs: &[&Path],
+    ) -> anyhow::Result<Option<OutputDir>> {
+        if !self.skip_unchanged {
+            return Ok(Some(OutputDir::create(dir, self.force)?));
+        }
+        let fingerprint = input_fingerprint(&format!("{args:?}"), inputs)?;
+        if OutputDir::is_up_to_date(dir, &fingerprint)? {
+            eprintln!("{} is up to date, skipping the build", dir.display());
+            return Ok(None);
+        }
+        let out = OutputDir::create(dir, true)?;
+        Ok(Some(out.with_fingerprint(fingerprint)))
+    }
+}
@@ -0,0 +1,3455 @@
+use rayon::prelude::*;
+use std::{
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::bail;
+use itertools::Itertools;
+use serde_json::json;
+
+use crate::cache::{read_entity_cache, EntityCacheWriter, MergedEntity};
+use crate::cli::{BuildArgs, IoArgs};
+use crate::hierarchy::TypeHierarchy;
+use crate::intern::{write_dictionary_index, StringInterner};
+use crate::literals::{LiteralDetector, LiteralKind, LiteralLabels};
+use crate::normalize::{Normalizer, UnicodeForm};
+use crate::output::OutputDir;
+use crate::progress::enable_memory_sampling;
+use crate::stats::{StageTimer, Stats};
+use crate::wikidata_dump::WikidataDumpReader;
+use crate::{
+    check_dump_date, english_number_variant, format_surface_form, line_iter, map_header_columns,
+    normalize_label, progress_bar, record_iter, record_writer, remove_non_english,
+    report_header_problems, sample_records, select_columns, title_qualifier, truncate_label,
+    within_edit_distance_one, write_dump_date, write_fst, Disambiguation, Ent, EntityId,
+    EntityIndexBuilder, EntityIndexOptions, EntityInfo, ExtraAliases, FileFormat, FormKind,
+    IdFormat, IdMap, KnowledgeGraph, KnowledgeGraphProcessor, LabelAliasPrecedence, OwnedEntity,
+    StreamingEntityReader, SurfaceFormEscaping, ENTITY_COLUMNS,
+};
+
+/// Number of entity lines parsed at once by one thread.
+const PARSE_CHUNK_SIZE: usize = 10_000;
+
+/// Number of type ids whose labels are fetched with one query.
+#[cfg(feature = "fetch")]
+const TYPE_LABEL_BATCH_SIZE: usize = 200;
+
+/// Arguments of an entity index build, see [`run`].
+#[derive(clap::Args, Debug, Clone)]
+#[clap(about = None, long_about = None)]
+pub struct EntitiesArgs {
+    #[clap(flatten)]
+    pub io: IoArgs,
+
+    #[clap(flatten)]
+    pub build: BuildArgs,
+
+    #[clap(short, long)]
+    redirects: Option<PathBuf>,
+
+    /// file with entity counts (id and count per line) overriding or
+    /// supplying the count column of the entity file
+    #[clap(long)]
+    counts: Option<PathBuf>,
+
+    /// file with type labels (id and label per line), used for types
+    /// that are not part of the entity file
+    #[clap(long)]
+    type_labels: Option<PathBuf>,
+
+    /// look up the labels of types that are neither in the entity file nor
+    /// in --type-labels at a SPARQL endpoint instead of leaving them out of
+    /// the infos (requires the fetch feature)
+    #[clap(long)]
+    fetch_type_labels: bool,
+
+    /// SPARQL endpoint for --fetch-type-labels, defaults to the QLever
+    /// endpoint of the knowledge graph
+    #[clap(long)]
+    type_label_endpoint: Option<String>,
+
+    /// maximum number of requests per second for --fetch-type-labels
+    #[clap(long, default_value = "5")]
+    type_label_requests_per_second: f64,
+
+    /// file caching the fetched type labels (id and label per line, like
+    /// --type-labels), read before fetching and updated afterwards
+    #[clap(long)]
+    type_label_cache: Option<PathBuf>,
+
+    /// file with extra aliases (id and alias per line) added to the
+    /// aliases of the entity file
+    #[clap(long)]
+    extra_aliases: Option<PathBuf>,
+
+    /// file with unit symbols (unit id and symbol per line, e.g. from
+    /// Wikidata P5061) added as aliases of the units
+    #[clap(long)]
+    unit_symbols: Option<PathBuf>,
+
+    /// file with external id statements (entity id, property, and value per
+    /// line), e.g. ISO codes or stock tickers, added as aliases for the
+    /// properties given with --external-id-property
+    #[clap(long)]
+    external_ids: Option<PathBuf>,
+
+    /// property (e.g. P297 for ISO 3166-1 alpha-2 codes) whose external ids
+    /// are added as aliases
+    #[clap(long)]
+    external_id_property: Vec<String>,
+
+    /// file with translated surface forms (entity id, surface form, and
+    /// language per line) added as aliases, e.g. to look up entities of an
+    /// English index in other languages; the aliases and their languages are
+    /// also written to translations.tsv
+    #[clap(long)]
+    translations: Option<PathBuf>,
+
+    /// file with pairs of entities said to be the same (two entity ids per
+    /// line, e.g. from Wikidata P460), whose labels are added as aliases of
+    /// each other with the lowest priority
+    #[clap(long)]
+    same_as: Option<PathBuf>,
+
+    /// file with subclass of statements (class id and superclass id per
+    /// line, e.g. from Wikidata P279), used with --class-types and
+    /// --specific-types
+    #[clap(long)]
+    subclass_of: Option<PathBuf>,
+
+    /// classes get their superclasses from --subclass-of as types instead of
+    /// their instance of types, which are mostly meta classes like "type of
+    /// state"
+    #[clap(long, requires = "subclass_of")]
+    class_types: bool,
+
+    /// types that are a superclass in --subclass-of of another type of the
+    /// entity are dropped, and types without label are replaced by their
+    /// nearest labeled superclass
+    #[clap(long, requires = "subclass_of")]
+    specific_types: bool,
+
+    /// file with entity ids to leave out of the index (one per line),
+    /// e.g. vandalized or test entities
+    #[clap(long)]
+    exclude_ids: Option<PathBuf>,
+
+    /// file with the entity ids to build the index for (one per line); types
+    /// outside of this set need --type-labels to be resolved
+    #[clap(long)]
+    include_ids: Option<PathBuf>,
+
+    /// file with type ids (one per line, e.g. Q5 for humans); only entities
+    /// with at least one of these types are indexed
+    #[clap(long)]
+    include_types: Option<PathBuf>,
+
+    /// file with type ids (one per line, e.g. Q4167410 for disambiguation
+    /// pages); entities with one of these types are left out
+    #[clap(long)]
+    exclude_types: Option<PathBuf>,
+
+    /// built-in type filter added to --exclude-types; no-scholarly leaves out
+    /// the scholarly articles, genes, and proteins of Wikidata, which make up
+    /// most of its entities but are rarely asked for
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// treat labels that differ only by punctuation or whitespace as the same
+    /// label, which goes to the most popular entity, with the other forms as
+    /// its aliases
+    #[clap(long)]
+    normalize_labels: bool,
+
+    /// unicode normalization form (nfc or nfkc) of labels and aliases, so
+    /// that visually identical ones, like "Café" with a composed and with a
+    /// combining accent, become the same surface form
+    #[clap(long)]
+    unicode_form: Option<String>,
+
+    /// remove zero-width and control characters from labels and aliases and
+    /// collapse their whitespace
+    #[clap(long)]
+    clean_labels: bool,
+
+    /// how tabs, line breaks, and parentheses in labels and infos are
+    /// written (none, sanitize, or escape), so that they cannot break rows
+    /// or be mistaken for the parentheses around an info
+    #[clap(long, default_value = "none")]
+    escape_surface_forms: String,
+
+    /// what happens to a surface form an entity gets both from its label and
+    /// from an alias or redirect label: the label holds it (label), or it
+    /// is also tagged with all of its sources in surface-form-sources.tsv
+    /// (merge)
+    #[clap(long, default_value = "label")]
+    label_alias_precedence: String,
+
+    /// also add the label with info to entities that get their label alone,
+    /// for generation models trained to always produce the disambiguated
+    /// form; in JSONL indices it is written as label_info
+    #[clap(long)]
+    label_info_variants: bool,
+
+    /// drop aliases within edit distance 1 of the label or a more frequent
+    /// alias of the same entity, e.g. typo variants
+    #[clap(long)]
+    collapse_near_duplicate_aliases: bool,
+
+    /// minimum length in characters of aliases that are collapsed, shorter
+    /// ones (NY, NJ) are too often distinct
+    #[clap(long, default_value_t = 5)]
+    collapse_min_length: usize,
+
+    /// drop aliases that are unlikely to be English (needs the
+    /// language-filter feature)
+    #[clap(long)]
+    english_aliases_only: bool,
+
+    /// labels and aliases that are only a number, year, or date (e.g.
+    /// "1984" or "11. September 2001") are better parsed as literals: keep
+    /// them, exclude them (with the entity for labels), or exclude them and
+    /// write them to literals.tsv (separate)
+    #[clap(long, default_value = "keep")]
+    literal_labels: String,
+
+    /// maximum number of characters of a label; longer labels, like the
+    /// titles of some scholarly articles, are cut after their last whole
+    /// word that fits
+    #[clap(long)]
+    max_label_length: Option<usize>,
+
+    /// maximum number of whitespace-separated tokens of a label, longer
+    /// labels are cut like with --max-label-length
+    #[clap(long)]
+    max_label_tokens: Option<usize>,
+
+    /// drop entities with a label longer than --max-label-length or
+    /// --max-label-tokens instead of cutting their label
+    #[clap(long)]
+    drop_long_labels: bool,
+
+    /// add the English plural or singular form of the labels and aliases of
+    /// classes (entities used as type of another entity) as aliases
+    #[clap(long)]
+    number_variants: bool,
+
+    /// add the title of DBpedia entities without its parenthetical qualifier
+    /// as alias, e.g. "Paris" for Paris_(mythology), with the qualifier as
+    /// info if the name alone is taken
+    #[clap(long)]
+    title_aliases: bool,
+
+    /// write aliases that could not be added to dropped-aliases.tsv
+    #[clap(long)]
+    dropped_aliases: bool,
+
+    /// file with aliases that are never indexed (one per line, compared
+    /// case-insensitively), e.g. pronouns or generic words
+    #[clap(long)]
+    alias_stoplist: Option<PathBuf>,
+
+    /// drop aliases with fewer characters
+    #[clap(long, default_value_t = 0)]
+    min_alias_length: usize,
+
+    /// write the aliases shared by several entities to alias-priors.tsv,
+    /// with every entity, its count, and its share of the counts of all
+    /// entities with the alias as prior, before the alias is dropped or
+    /// given to one of them
+    #[clap(long)]
+    alias_priors: bool,
+
+    /// write entities with the same label and (nearly) the same description
+    /// as a more popular entity to duplicates.tsv, to be confirmed for
+    /// --merge-duplicates
+    #[clap(long)]
+    duplicates: bool,
+
+    /// file with confirmed duplicates (duplicate id and the id of the entity
+    /// to merge it into per line, e.g. from duplicates.tsv); duplicates are
+    /// left out and their label and aliases become aliases of the other
+    /// entity, also along chains of duplicates
+    #[clap(long)]
+    merge_duplicates: Option<PathBuf>,
+
+    /// follow redirect chains (A to B to C) so that redirects, and with
+    /// --redirect-labels their labels, belong to the entity at the end of
+    /// the chain; redirects in cycles are dropped
+    #[clap(long)]
+    resolve_redirect_chains: bool,
+
+    /// use labels and aliases of redirecting entities as surface forms
+    /// of the redirect target instead of indexing them separately
+    #[clap(long)]
+    redirect_labels: bool,
+
+    /// write the parsed and merged entities (with redirects, counts, and type
+    /// labels applied) as a zstd-compressed bincode file, to be reused with
+    /// --from-merged
+    #[clap(long)]
+    save_merged: Option<PathBuf>,
+
+    /// read a merged entity file written with --save-merged instead of an
+    /// entity dump, to skip parsing when only the index options change
+    #[clap(long)]
+    from_merged: bool,
+
+    /// further merged entity files read after --file with --from-merged,
+    /// e.g. of the other shards of a dump; an entity in several files keeps
+    /// its first record
+    #[clap(long)]
+    shard: Vec<PathBuf>,
+
+    /// delta entity file of a newer dump applied to the merged entities
+    /// with --from-merged: an entity file with an additional change column
+    /// (added, changed, or deleted) per entity
+    #[clap(long)]
+    delta: Option<PathBuf>,
+
+    /// output directory of the build of the merged entities that --delta is
+    /// applied to; only the entities sharing a surface form with the
+    /// added, changed, or deleted ones are resolved again, the other rows
+    /// of its index are kept
+    #[clap(long)]
+    previous_index: Option<PathBuf>,
+
+    /// also write index.fst, a finite state transducer mapping every surface
+    /// form of the index to its row (starting at 0), for memory-mapped
+    /// prefix and fuzzy lookups
+    #[clap(long)]
+    fst: bool,
+
+    /// also write index.dict, the index with every distinct id, label, and
+    /// info stored once and referred to by number
+    #[clap(long)]
+    dictionary_index: bool,
+
+    /// parse the entity file while reading it and keep only the parsed
+    /// entities in memory instead of all of its lines, for dumps too large
+    /// to be loaded at once
+    #[clap(long)]
+    streaming: bool,
+
+    /// the input file is the official Wikidata JSON dump
+    /// (wikidata-YYYYMMDD-all.json.bz2) instead of an entity file; it is
+    /// always read like with --streaming
+    #[clap(long)]
+    wikidata_json: bool,
+
+    #[clap(short, long)]
+    ignore_types: bool,
+
+    /// how entities with the same label are disambiguated: drop them, keep
+    /// the most common one, add their info (info-suffix) or id (qid-suffix)
+    /// to the label, or add their info and keep the most common of the
+    /// entities with the same label and info (all)
+    #[clap(long, default_value = "info-suffix")]
+    disambiguate: String,
+
+    /// deprecated, same as --disambiguate all
+    #[clap(long, hide = true, conflicts_with = "disambiguate")]
+    keep_most_common_non_unique: bool,
+
+    /// only index labels and aliases that look like ids (e.g. Q1) together
+    /// with their type or description, so they cannot shadow id lookups
+    #[clap(long)]
+    disambiguate_id_labels: bool,
+
+    #[clap(short, long)]
+    check_for_popular_aliases: bool,
+
+    /// how many times more popular an entity with a matching alias must be
+    /// to take a label away from its entity
+    #[clap(long, default_value_t = 1.0)]
+    popular_alias_ratio: f64,
+
+    /// assign aliases shared by multiple entities to the most popular one
+    /// instead of dropping them
+    #[clap(long)]
+    resolve_alias_conflicts: bool,
+
+    /// how many times more popular the most popular entity must be than the
+    /// runner-up to get a contested alias
+    #[clap(long, default_value_t = 1.0)]
+    alias_conflict_ratio: f64,
+
+    #[clap(short, long)]
+    short_entities: bool,
+
+    #[clap(long)]
+    include_statement_prefixes: bool,
+
+    /// additional entity iri pattern with one capture group for the entity id
+    #[clap(long)]
+    entity_pattern: Vec<String>,
+
+    /// number of most common types to report the coverage for
+    #[clap(long, default_value_t = 10)]
+    type_coverage_top: usize,
+
+    /// output directory of a previous build to merge the entities of --file
+    /// into; its surface forms keep their entities
+    #[clap(long)]
+    augment: Option<PathBuf>,
+
+    /// Output ids as given by the knowledge graph (kg) or as consecutive
+    /// numbers (numeric), which are mapped back in id-map.tsv
+    #[clap(long, default_value = "kg")]
+    id_format: String,
+
+    /// id map of a previous build, whose numbers are kept for the same ids
+    /// (requires --id-format numeric)
+    #[clap(long)]
+    id_map: Option<PathBuf>,
+}
+
+impl EntitiesArgs {
+    /// The input files besides --file that must exist.
+    fn input_files(&self) -> impl Iterator<Item = &PathBuf> {
+        [
+            &self.redirects,
+            &self.counts,
+            &self.type_labels,
+            &self.extra_aliases,
+            &self.unit_symbols,
+            &self.external_ids,
+            &self.translations,
+            &self.same_as,
+            &self.subclass_of,
+            &self.alias_stoplist,
+            &self.merge_duplicates,
+            &self.exclude_ids,
+            &self.include_ids,
+            &self.include_types,
+            &self.exclude_types,
+            &self.id_map,
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// All inputs of the build, including the optional ones like the type
+    /// label cache and the directory of --augment.
+    fn inputs(&self) -> Vec<&Path> {
+        std::iter::once(&self.io.file)
+            .chain(self.input_files())
+            .chain(&self.shard)
+            .chain(&self.delta)
+            .chain(&self.augment)
+            .chain(&self.previous_index)
+            .chain(&self.type_label_cache)
+            .map(PathBuf::as_path)
+            .collect()
+    }
+}
+
+/// Builds the entity index and returns the statistics of the build, or
+/// None if --skip-unchanged found the index up to date. Batches of several
+/// knowledge graphs are built with [`run_batch`](crate::batch::run_batch).
+///
+/// The entities are read and filtered by id and type, their types resolved
+/// to labels, and their labels and aliases transformed, before the surface
+/// forms are assigned with an [`EntityIndexBuilder`] and the outputs are
+/// written.
+pub fn run(args: EntitiesArgs) -> anyhow::Result<Option<Stats>> {
+    if args.build.memory_stats {
+        enable_memory_sampling();
+    }
+    let mut kg = args.build.processor()?;
+    for pattern in &args.entity_pattern {
+        kg.add_entity_pattern(pattern)?;
+    }
+    let input_format = args.io.input_format()?;
+    let output_format = args.io.output_format()?;
+    let escaping = SurfaceFormEscaping::try_from(args.escape_surface_forms.as_str())?;
+    let disambiguation = if args.keep_most_common_non_unique {
+        Disambiguation::All
+    } else {
+        Disambiguation::try_from(args.disambiguate.as_str())?
+    };
+    let literal_labels = LiteralLabels::try_from(args.literal_labels.as_str())?;
+    let label_alias_precedence =
+        LabelAliasPrecedence::try_from(args.label_alias_precedence.as_str())?;
+    let id_format = IdFormat::try_from(args.id_format.as_str())?;
+    check_args(&args, &kg, output_format, escaping, id_format)?;
+    if args.build.dry_run {
+        return dry_run(&args, &kg, input_format).map(Some);
+    }
+    // load the previous id map before --force removes it
+    let mut ids = IdMap::new(id_format);
+    if let Some(file) = &args.id_map {
+        ids.load_previous(file)?;
+    }
+    // read before the output directory is prepared, which may be the same
+    let mut existing = match args.augment.as_ref().or(args.previous_index.as_ref()) {
+        Some(_) if output_format == FileFormat::Jsonl => {
+            bail!("--augment and --previous-index need a tsv or csv index, not jsonl")
+        }
+        Some(dir) => Some(ExistingIndex::load(dir, output_format)?),
+        None => None,
+    };
+    let fingerprint_args = EntitiesArgs {
+        io: args.io.without_run_options(),
+        build: args.build.without_run_options(),
+        ..args.clone()
+    };
+    let Some(out) = args
+        .build
+        .output_dir(&args.io.output, &fingerprint_args, &args.inputs())?
+    else {
+        return Ok(None);
+    };
+    let mut timer = StageTimer::new();
+    timer.start("loading");
+
+    let mut type_labels = if let Some(path) = &args.type_labels {
+        load_type_labels(&kg, path, input_format, args.io.progress)?
+    } else {
+        HashMap::new()
+    };
+    let (merged, delta) = if args.from_merged {
+        load_merged(&args, &kg, input_format, &type_labels)?
+    } else {
+        (vec![], DeltaChanges::default())
+    };
+    let inputs = Inputs::load(&args, &mut kg, input_format, &merged)?;
+    let filter = EntityFilter::load(&args, &kg, &inputs)?;
+    let mut read = if args.from_merged {
+        ReadEntities::default()
+    } else {
+        read_entities(&args, &kg, input_format, &inputs.counts, &filter)?
+    };
+    timer.start("parsing");
+    parse_records(&mut read, &args, &kg, &inputs.counts, &filter);
+    let mut collected = collect_entities(&read.entities, &merged, &inputs.redirects, &filter)?;
+    // without the redirect labels of entities of other shards
+    let num_records =
+        read.num_records + merged.iter().filter(|value| value.label.is_some()).count();
+
+    timer.start("type resolution");
+    let mut num_subclass_types = 0;
+    let mut num_ancestor_types = 0;
+    let mut num_generalized_types = 0;
+    let mut num_fetched_type_labels = 0;
+    let mut num_missing_type_labels = 0;
+    // types of merged entities are already resolved to labels
+    if !args.from_merged {
+        if args.class_types {
+            num_subclass_types = use_superclass_types(&mut collected, &inputs.type_hierarchy);
+        }
+        if args.fetch_type_labels {
+            (num_fetched_type_labels, num_missing_type_labels) =
+                fetch_missing_type_labels(&args, &kg, input_format, &collected, &mut type_labels)?;
+        }
+        (num_ancestor_types, num_generalized_types) = resolve_types(
+            &mut collected,
+            &type_labels,
+            &inputs.type_hierarchy,
+            args.specific_types,
+        );
+    }
+
+    if let Some(path) = &args.save_merged {
+        timer.start("saving merged");
+        save_merged(path, &collected)?;
+    }
+
+    timer.start("label assignment");
+    let Collected {
+        mut ent_infos,
+        type_filtered,
+        mut redirect_labels,
+        class_ids,
+        num_duplicates,
+        num_excluded,
+        num_not_included,
+    } = collected;
+    ent_infos.retain(|&ent, info| kg.apply_entity_hooks(ent, info));
+    let num_before_min_count = ent_infos.len();
+    ent_infos.retain(|_, info| info.count >= args.build.min_count);
+    let num_below_min_count = num_before_min_count - ent_infos.len();
+    // with a previous index, only the entities sharing a surface form with
+    // the delta are resolved again, the rows of the others are kept
+    let mut num_reused = 0;
+    if let Some(existing) = existing.as_mut().filter(|_| args.previous_index.is_some()) {
+        retain_touched_entities(&mut ent_infos, &redirect_labels, &delta);
+        let resolved: HashSet<_> = ent_infos
+            .keys()
+            .copied()
+            .chain(delta.entities.iter().map(String::as_str))
+            .map(|ent| kg.format_entity(ent, args.short_entities))
+            .collect();
+        existing.remove(&resolved);
+        num_reused = existing.rows.len();
+    }
+
+    let normalizer = label_normalizer(&args)?;
+    // labels and aliases that change, the entities borrow their strings
+    // from here
+    let normalized_forms = match &normalizer {
+        Some(normalizer) => normalized_forms(normalizer, &ent_infos, &redirect_labels),
+        None => HashMap::new(),
+    };
+    let num_empty_labels =
+        apply_normalized_forms(&normalized_forms, &mut ent_infos, &mut redirect_labels);
+    let limits_labels = args.max_label_length.is_some() || args.max_label_tokens.is_some();
+    let num_long_labels = if limits_labels {
+        truncate_labels(&mut ent_infos, &args)
+    } else {
+        0
+    };
+    let num_merged_duplicates =
+        merge_confirmed_duplicates(&mut ent_infos, &inputs.confirmed_duplicates);
+    let duplicates = if args.duplicates {
+        find_duplicates(&ent_infos)
+    } else {
+        vec![]
+    };
+    let mut num_non_english_aliases = 0;
+    if args.english_aliases_only {
+        for info in ent_infos.values_mut() {
+            num_non_english_aliases += remove_non_english(&mut info.aliases)?;
+        }
+    }
+    // aliases left out before the index is built, with the reason
+    let filtered_aliases = if args.alias_stoplist.is_some() || args.min_alias_length > 0 {
+        filter_aliases(
+            &mut ent_infos,
+            args.alias_stoplist.as_deref(),
+            args.min_alias_length,
+        )?
+    } else {
+        vec![]
+    };
+    let literals = if literal_labels != LiteralLabels::Keep {
+        remove_literals(&mut ent_infos)?
+    } else {
+        vec![]
+    };
+    let num_collapsed_aliases = if args.collapse_near_duplicate_aliases {
+        collapse_near_duplicate_aliases(&mut ent_infos, args.collapse_min_length)
+    } else {
+        0
+    };
+    let num_ents = ent_infos.len();
+    let number_variants = if args.number_variants {
+        number_variants(&ent_infos, &class_ids)
+    } else {
+        vec![]
+    };
+    let title_aliases = if args.title_aliases {
+        title_aliases(&ent_infos)
+    } else {
+        vec![]
+    };
+    let num_id_like_labels = ent_infos
+        .values()
+        .filter(|info| kg.looks_like_id(info.label))
+        .count();
+
+    let options = EntityIndexOptions {
+        check_for_popular_aliases: args.check_for_popular_aliases,
+        popular_alias_ratio: args.popular_alias_ratio,
+        disambiguation,
+        resolve_alias_conflicts: args.resolve_alias_conflicts,
+        alias_conflict_ratio: args.alias_conflict_ratio,
+        normalize_labels: args.normalize_labels,
+        escaping,
+        label_alias_precedence,
+        label_info_variants: args.label_info_variants,
+        progress_listener: None,
+    };
+    let mut builder = assign_labels(&args, &kg, options, ent_infos);
+
+    let mut stats = Stats::new(format!("{} entities", args.build.knowledge_base));
+    stats.count("records", num_records);
+    if args.wikidata_json {
+        stats.count("unlabeled_items", read.num_unlabeled);
+    }
+    stats.count("entities", num_ents);
+    stats.count("duplicate_entities", num_duplicates);
+    stats.count("excluded_entities", read.num_excluded + num_excluded);
+    if args.build.min_count > 0 {
+        stats.count("below_min_count", num_below_min_count);
+    }
+    if args.resolve_redirect_chains {
+        let (num_collapsed, num_cyclic) = inputs.redirect_chains;
+        stats.count("collapsed_redirect_chains", num_collapsed);
+        stats.count("cyclic_redirects", num_cyclic);
+    }
+    if normalizer.is_some() {
+        stats.count("normalized_forms", normalized_forms.len());
+        stats.count("empty_normalized_labels", num_empty_labels);
+    }
+    if limits_labels {
+        let name = if args.drop_long_labels {
+            "dropped_long_labels"
+        } else {
+            "truncated_labels"
+        };
+        stats.count(name, num_long_labels);
+    }
+    if args.delta.is_some() {
+        stats.count("added_entities", delta.num_added);
+        stats.count("changed_entities", delta.num_changed);
+        stats.count("deleted_entities", delta.num_deleted);
+    }
+    if args.unit_symbols.is_some() {
+        stats.count("unit_symbols", inputs.num_unit_symbols);
+    }
+    if args.external_ids.is_some() {
+        stats.count("external_ids", inputs.num_external_ids);
+    }
+    if args.translations.is_some() {
+        stats.count("translated_aliases", inputs.translations.len());
+    }
+    if args.class_types {
+        stats.count("subclass_types", num_subclass_types);
+    }
+    if args.specific_types {
+        stats.count("ancestor_types", num_ancestor_types);
+        stats.count("generalized_types", num_generalized_types);
+    }
+    if args.fetch_type_labels {
+        stats.count("fetched_type_labels", num_fetched_type_labels);
+        stats.count("missing_type_labels", num_missing_type_labels);
+    }
+    if args.merge_duplicates.is_some() {
+        stats.count("merged_duplicates", num_merged_duplicates);
+    }
+    if args.duplicates {
+        stats.count("description_duplicates", duplicates.len());
+    }
+    if filter.include_ids.is_some() {
+        stats.count(
+            "not_included_entities",
+            read.num_not_included + num_not_included,
+        );
+    }
+    if filter.filters_types {
+        stats.count("type_filtered_entities", type_filtered.len());
+    }
+    stats.count("unique_by_label", builder.num_label_unique);
+    stats.percent("label_coverage", builder.num_label_unique, num_ents);
+    stats.count("unique_by_label_and_info", builder.num_label_info_unique);
+    stats.percent(
+        "label_and_info_coverage",
+        builder.num_label_info_unique,
+        num_ents,
+    );
+    stats.count("entities_left", builder.num_entities_left);
+    stats.count("id_like_labels", num_id_like_labels);
+
+    timer.start("alias pass");
+    builder.add_aliases();
+
+    stats.count("aliases", builder.num_aliases);
+    stats.count("added_aliases", builder.num_added_aliases);
+    stats.percent(
+        "added_alias_ratio",
+        builder.num_added_aliases,
+        builder.num_aliases,
+    );
+    stats.count(
+        "dropped_aliases",
+        filtered_aliases.len() + builder.dropped_aliases.len(),
+    );
+    if args.alias_priors {
+        stats.count("shared_aliases", builder.shared_aliases.len());
+    }
+    if label_alias_precedence == LabelAliasPrecedence::Merge {
+        stats.count("merged_surface_forms", builder.merged_sources.len());
+    }
+    if args.english_aliases_only {
+        stats.count("non_english_aliases", num_non_english_aliases);
+    }
+    if literal_labels != LiteralLabels::Keep {
+        stats.count("literal_surface_forms", literals.len());
+    }
+    if args.collapse_near_duplicate_aliases {
+        stats.count("collapsed_aliases", num_collapsed_aliases);
+    }
+    if args.normalize_labels {
+        stats.count("variant_aliases", builder.num_variant_aliases);
+    }
+    if args.label_info_variants {
+        stats.count("label_info_variants", builder.num_label_info_variants);
+    }
+    if args.number_variants {
+        let num_added = add_number_variants(&mut builder, &number_variants);
+        stats.count("number_variant_aliases", num_added);
+    }
+    if args.title_aliases {
+        let num_added = add_title_aliases(&mut builder, &title_aliases);
+        stats.count("title_aliases", num_added);
+    }
+    if args.same_as.is_some() {
+        let num_added = add_same_as_labels(&mut builder, &inputs.same_as);
+        stats.count("same_as_aliases", num_added);
+    }
+    if args.redirect_labels {
+        let num_added = add_redirect_labels(&mut builder, redirect_labels);
+        stats.count("redirect_labels", num_added);
+    }
+    stats.count("index_size", builder.index().len());
+    let covered: HashSet<_> = builder.index().values().map(|ent| ent.as_str()).collect();
+    stats.percent("index_coverage", covered.len(), num_ents);
+    // coverage of the most common top-level (most popular) types
+    for (ent_type, total, num_covered) in type_coverage(&builder, &covered, args.type_coverage_top)
+    {
+        stats.count(&format!("entities_{ent_type}"), total);
+        stats.percent(&format!("coverage_{ent_type}"), num_covered, total);
+    }
+
+    timer.start("writing");
+    let ent_infos = builder.entities();
+    let rows = index_rows(&builder, &kg, args.short_entities);
+    let mut popularity: Vec<_> = ent_infos
+        .iter()
+        .map(|(&ent, info)| {
+            (
+                Reverse(info.count),
+                kg.format_entity(ent, args.short_entities),
+            )
+        })
+        .collect();
+    let rows = if let Some(existing) = existing {
+        let new_ids: HashSet<_> = popularity.iter().map(|(_, id)| id.clone()).collect();
+        popularity.extend(
+            existing
+                .counts
+                .iter()
+                .filter(|(id, _)| !new_ids.contains(id))
+                .map(|(id, count)| (Reverse(*count), id.clone())),
+        );
+        let num_existing = existing.rows.len();
+        let (mut rows, num_added, num_conflicts) = existing.augment(rows);
+        if args.previous_index.is_some() {
+            // the rows of the resolved entities move to their count
+            let counts: HashMap<_, _> = popularity
+                .iter()
+                .map(|(count, id)| (id.as_str(), *count))
+                .collect();
+            let count = |id: &str| counts.get(id).copied().unwrap_or(Reverse(0));
+            rows.sort_by(|(a, ..), (b, ..)| (count(a), a).cmp(&(count(b), b)));
+            stats.count("reused_entities", num_reused);
+            stats.count("resolved_entities", rows.len() - num_reused);
+        } else {
+            stats.count("existing_entities", num_existing);
+            stats.count("new_entities", rows.len() - num_existing);
+        }
+        stats.count("added_surface_forms", num_added);
+        stats.count("conflicting_surface_forms", num_conflicts);
+        rows
+    } else {
+        rows
+    };
+
+    if let Some(date) = &args.build.dump_date {
+        write_dump_date(&out, date)?;
+    }
+    // the other outputs are always written as tables, also for a jsonl index
+    let table_format = if output_format == FileFormat::Jsonl {
+        FileFormat::Tsv
+    } else {
+        output_format
+    };
+    let ctx = OutputContext {
+        args: &args,
+        kg: &kg,
+        out: &out,
+        table_format,
+        escaping,
+    };
+    // all entities by descending count, ties go to the smaller id
+    popularity.sort();
+    // numbered in the order of the index, so by descending count, then the
+    // entities without surface forms and the redirects
+    ids.number(
+        rows.iter()
+            .map(|(ent_id, ..)| ent_id.clone())
+            .chain(popularity.iter().map(|(_, ent)| ent.clone()))
+            .chain(
+                rows.iter()
+                    .filter_map(|(_, _, redirs)| redirs.as_ref())
+                    .flatten()
+                    .sorted()
+                    .cloned(),
+            ),
+    );
+    write_index(&ctx, output_format, rows, ent_infos, &mut ids, &mut stats)?;
+    if args.alias_priors {
+        write_alias_priors(&ctx, &builder, &mut ids)?;
+    }
+    if label_alias_precedence == LabelAliasPrecedence::Merge {
+        write_surface_form_sources(&ctx, &builder, &mut ids)?;
+    }
+    if args.dropped_aliases {
+        write_dropped_aliases(&ctx, &filtered_aliases, &builder, &mut ids)?;
+    }
+    if args.duplicates {
+        write_duplicates(&ctx, &duplicates, &mut ids)?;
+    }
+    if args.translations.is_some() {
+        write_translations(&ctx, &inputs.translations, &mut ids)?;
+    }
+    if literal_labels == LiteralLabels::Separate {
+        write_literals(&ctx, &literals, &mut ids)?;
+    }
+    write_popularity(&ctx, popularity, &mut ids)?;
+    if id_format == IdFormat::Numeric {
+        write_id_map(&ctx, &ids)?;
+        if args.id_map.is_some() {
+            let (reused, retired, new) = ids.previous_counts();
+            stats.count("reused_ids", reused);
+            stats.count("retired_ids", retired);
+            stats.count("new_ids", new);
+        }
+    }
+
+    out.write_manifest()?;
+    timer.finish();
+    if args.build.memory_stats {
+        stats.memory();
+        if let Some((num_strings, saved_bytes)) = read.interned {
+            stats.count("distinct_strings", num_strings);
+            stats.count("interning_saved_bytes", saved_bytes);
+        }
+    }
+    if args.build.timing_stats {
+        stats.timing(&timer);
+    }
+
+    Ok(Some(stats))
+}
+
+/// Builds one entity index from the merged entity files of several shards
+/// of a dump (--file and --shard, written with --save-merged), so label
+/// conflicts across the shards are resolved like in a build of the whole
+/// dump.
+pub fn merge_shards(mut args: EntitiesArgs) -> anyhow::Result<Option<Stats>> {
+    args.from_merged = true;
+    run(args)
+}
+
+/// Updates an entity index with the delta of a newer dump: the delta is
+/// applied to the merged entities of the previous build (--file, written
+/// with --save-merged), so only the labels are resolved again instead of
+/// parsing the whole dump. With --save-merged, the updated merged entities
+/// are written for the next update.
+pub fn update(mut args: EntitiesArgs) -> anyhow::Result<Option<Stats>> {
+    if args.delta.is_none() {
+        bail!("an update needs a delta entity file given with --delta");
+    }
+    args.from_merged = true;
+    run(args)
+}
+
+/// Checks the inputs and the header of the entity file, parses a sample of
+/// its records, and extrapolates the number of records, the memory needed
+/// to hold them, and the size of the index from it.
+fn dry_run(
+    args: &EntitiesArgs,
+    kg: &KnowledgeGraphProcessor,
+    input_format: FileFormat,
+) -> anyhow::Result<Stats> {
+    if args.from_merged || args.wikidata_json {
+        bail!("--dry-run needs an entity file, not a merged entity file or JSON dump");
+    }
+    for path in args.input_files() {
+        if !path.is_file() {
+            bail!("input file {} does not exist", path.display());
+        }
+    }
+
+    let sample = sample_records(&args.io.file, input_format, args.build.dry_run_sample)?;
+    let Some((header, records)) = sample.records.split_first() else {
+        bail!("{} is empty", args.io.file.display());
+    };
+    let header: Vec<_> = header.iter().collect();
+    let (columns, problems) = map_header_columns(&header, &ENTITY_COLUMNS);
+    report_header_problems(&args.io.file, &problems, args.build.strict)?;
+    if columns[0].is_none() || columns[1].is_none() {
+        bail!("entity file needs an id and a label column");
+    }
+
+    let mut num_parsed = 0;
+    let mut memory_bytes = 0;
+    let mut output_bytes = 0;
+    for record in records {
+        // records are kept in memory for the whole build
+        memory_bytes += record.as_byte_record().as_slice().len()
+            + size_of::<usize>() * (record.len() + 1)
+            + size_of::<csv::StringRecord>();
+        let mut fields = select_columns(record, &columns);
+        if args.counts.is_some() && fields.len() > 3 {
+            fields[3] = "0";
+        }
+        let Ok((ent, info)) = kg.parse_entity_fields(&fields, args.ignore_types) else {
+            continue;
+        };
+        num_parsed += 1;
+        // the entity info and its entries in the label maps
+        let num_strs = info.aliases.len() + info.types.lock().unwrap().len();
+        memory_bytes +=
+            size_of::<EntityInfo>() + size_of::<&str>() * num_strs + 4 * size_of::<(&str, Ent)>();
+        output_bytes += kg.format_entity(ent.as_str(), args.short_entities).len()
+            + std::iter::once(info.label)
+                .chain(info.aliases.iter().copied())
+                .map(|s| s.len() + 1)
+                .sum::<usize>()
+            + 1;
+    }
+    if !records.is_empty() && num_parsed == 0 {
+        bail!(
+            "none of the {} sampled records could be parsed, check --knowledge-base, \
+            --input-format, and --entity-pattern",
+            records.len()
+        );
+    }
+
+    let estimated_records = sample.estimated_records.saturating_sub(1) as usize;
+    let scale = |n: usize| {
+        (n as f64 * estimated_records as f64 / records.len().max(1) as f64).round() as usize
+    };
+    let mut stats = Stats::new(format!("{} entities (dry run)", args.build.knowledge_base));
+    stats.count("input_mib", (sample.file_bytes >> 20) as usize);
+    stats.count("sampled_records", records.len());
+    stats.count("parsed_records", num_parsed);
+    stats.percent("parse_rate", num_parsed, records.len());
+    stats.count("estimated_records", estimated_records);
+    stats.count("estimated_entities", scale(num_parsed));
+    stats.count("estimated_memory_mib", scale(memory_bytes) >> 20);
+    stats.count("estimated_index_mib", scale(output_bytes) >> 20);
+    Ok(stats)
+}
+
+/// Checks the combinations of arguments before anything is read.
+fn check_args(
+    args: &EntitiesArgs,
+    kg: &KnowledgeGraphProcessor,
+    output_format: FileFormat,
+    escaping: SurfaceFormEscaping,
+    id_format: IdFormat,
+) -> anyhow::Result<()> {
+    if escaping != SurfaceFormEscaping::None && output_format == FileFormat::Jsonl {
+        bail!("a jsonl index needs no escaping of surface forms");
+    }
+    if let Some(date) = &args.build.dump_date {
+        check_dump_date(date)?;
+    }
+    if args.wikidata_json && (kg.kg != KnowledgeGraph::Wikidata || args.from_merged) {
+        bail!("--wikidata-json needs --knowledge-base wikidata and no merged entity file");
+    }
+    let rate = args.type_label_requests_per_second;
+    if !(rate.is_finite() && rate > 0.0) {
+        bail!("--type-label-requests-per-second must be positive, got {rate}");
+    }
+    if args.title_aliases && kg.kg != KnowledgeGraph::DBPedia {
+        bail!("--title-aliases needs --knowledge-base dbpedia");
+    }
+    if args.id_map.is_some() && id_format != IdFormat::Numeric {
+        bail!("--id-map requires --id-format numeric");
+    }
+    if args.augment.is_some() && id_format != IdFormat::Kg {
+        bail!("--augment requires --id-format kg");
+    }
+    if args.previous_index.is_some() {
+        if args.delta.is_none() || args.augment.is_some() {
+            bail!("--previous-index needs --delta and no --augment");
+        }
+        if id_format != IdFormat::Kg {
+            bail!("--previous-index requires --id-format kg");
+        }
+    }
+    if args.from_merged
+        && (args.redirects.is_some()
+            || args.counts.is_some()
+            || (args.type_labels.is_some() && args.delta.is_none())
+            || args.fetch_type_labels
+            || args.subclass_of.is_some())
+    {
+        bail!(
+            "merged entity files already include redirects, counts, and resolved types, \
+            --type-labels is only used for the types of a delta"
+        );
+    }
+    let profile_types = match &args.profile {
+        Some(profile) => profile_excluded_types(profile, kg)?,
+        None => &[],
+    };
+    let filters_types =
+        args.include_types.is_some() || args.exclude_types.is_some() || !profile_types.is_empty();
+    if filters_types && (args.from_merged || args.ignore_types) {
+        bail!("--include-types and --exclude-types need the type ids of an entity dump");
+    }
+    if !args.shard.is_empty() && !args.from_merged {
+        bail!("--shard needs --from-merged");
+    }
+    if args.delta.is_some() && !args.from_merged {
+        bail!("--delta needs --from-merged");
+    }
+    if args.subclass_of.is_some() && !args.class_types && !args.specific_types {
+        bail!("--subclass-of needs --class-types or --specific-types");
+    }
+    Ok(())
+}
+
+/// Reads the merged entities of --file and --shard and applies the delta
+/// of --delta to them.
+fn load_merged(
+    args: &EntitiesArgs,
+    kg: &KnowledgeGraphProcessor,
+    input_format: FileFormat,
+    type_labels: &HashMap<EntityId, String>,
+) -> anyhow::Result<(Vec<MergedEntity<'static>>, DeltaChanges)> {
+    let mut merged = vec![];
+    for file in std::iter::once(&args.io.file).chain(&args.shard) {
+        merged.extend(read_entity_cache(file)?);
+    }
+    let delta = match &args.delta {
+        Some(path) => apply_delta(kg, path, input_format, args, type_labels, &mut merged)?,
+        None => DeltaChanges::default(),
+    };
+    Ok((merged, delta))
+}
+
+/// Inputs of an entity build besides the entities themselves.
+struct Inputs {
+    /// redirect sources per target
+    redirects: HashMap<String, Vec<String>>,
+    /// collapsed and cyclic chains with --resolve-redirect-chains
+    redirect_chains: (usize, usize),
+    counts: HashMap<String, usize>,
+    num_unit_symbols: usize,
+    num_external_ids: usize,
+    translations: Vec<(EntityId, String, String)>,
+    same_as: Vec<(String, String)>,
+    confirmed_duplicates: Vec<(String, String)>,
+    type_hierarchy: TypeHierarchy,
+}
+
+impl Inputs {
+    /// Loads the inputs given by the arguments. Extra aliases, unit symbols,
+    /// external ids, and translations are added to the knowledge graph as an
+    /// entity hook. Without --redirects, the redirects are those of the
+    /// merged entities.
+    fn load(
+        args: &EntitiesArgs,
+        kg: &mut KnowledgeGraphProcessor,
+        input_format: FileFormat,
+        merged: &[MergedEntity],
+    ) -> anyhow::Result<Self> {
+        let mut redirect_chains = (0, 0);
+        let redirects = if let Some(path) = &args.redirects {
+            let mut redirects = load_redirects(kg, path, input_format, args.io.progress)?;
+            if args.resolve_redirect_chains {
+                redirect_chains = resolve_redirect_chains(&mut redirects);
+            }
+            redirects
+        } else {
+            merged
+                .iter()
+                .filter_map(|value| {
+                    let redirs: Vec<_> = value
+                        .redirects
+                        .as_ref()?
+                        .iter()
+                        .map(|r| r.to_string())
+                        .collect();
+                    if redirs.is_empty() {
+                        return None;
+                    }
+                    Some((value.id.to_string(), redirs))
+                })
+                .collect()
+        };
+        let counts = if let Some(path) = &args.counts {
+            let pbar = progress_bar("loading entity counts", u64::MAX, !args.io.progress);
+            let mut counts = HashMap::new();
+            for record in pbar.wrap_iter(record_iter(path, input_format)?) {
+                let record = record?;
+                // skips the header and malformed lines
+                let (Some(ent), Some(Ok(count))) = (
+                    record.get(0).and_then(|ent| kg.parse_entity_id(ent)),
+                    record.get(1).map(|count| count.trim().parse::<usize>()),
+                ) else {
+                    continue;
+                };
+                counts.insert(ent.to_string(), count);
+            }
+            pbar.finish_and_clear();
+            counts
+        } else {
+            HashMap::new()
+        };
+        let mut extra_aliases = ExtraAliases::default();
+        if let Some(path) = &args.extra_aliases {
+            load_aliases(kg, path, input_format, &mut extra_aliases)?;
+        }
+        let num_unit_symbols = if let Some(path) = &args.unit_symbols {
+            load_aliases(kg, path, input_format, &mut extra_aliases)?
+        } else {
+            0
+        };
+        let num_external_ids = if let Some(path) = &args.external_ids {
+            load_external_ids(
+                kg,
+                path,
+                input_format,
+                &args.external_id_property,
+                &mut extra_aliases,
+            )?
+        } else {
+            0
+        };
+        let translations = if let Some(path) = &args.translations {
+            load_translations(kg, path, input_format, &mut extra_aliases)?
+        } else {
+            vec![]
+        };
+        if !extra_aliases.aliases.is_empty() {
+            kg.add_entity_hook(extra_aliases);
+        }
+        let same_as = if let Some(path) = &args.same_as {
+            load_id_pairs(kg, path, input_format)?
+        } else {
+            vec![]
+        };
+        let confirmed_duplicates = if let Some(path) = &args.merge_duplicates {
+            load_id_pairs(kg, path, input_format)?
+        } else {
+            vec![]
+        };
+        let type_hierarchy = if let Some(path) = &args.subclass_of {
+            load_subclass_of(kg, path, input_format)?
+        } else {
+            TypeHierarchy::new()
+        };
+        Ok(Self {
+            redirects,
+            redirect_chains,
+            counts,
+            num_unit_symbols,
+            num_external_ids,
+            translations,
+            same_as,
+            confirmed_duplicates,
+            type_hierarchy,
+        })
+    }
+}
+
+/// Decides by their ids and types which entities are part of the build.
+#[derive(Default)]
+struct EntityFilter<'a> {
+    /// the only entities of the build with --include-ids
+    include_ids: Option<HashSet<String>>,
+    exclude_ids: HashSet<String>,
+    include_types: Option<HashSet<String>>,
+    /// --exclude-types and the types left out by --profile
+    exclude_types: HashSet<String>,
+    filters_types: bool,
+    /// target of every redirect source with --redirect-labels
+    redirect_targets: HashMap<&'a str, &'a str>,
+    /// superclasses of classes, which are their types with --class-types
+    superclasses: Option<&'a TypeHierarchy>,
+}
+
+impl<'a> EntityFilter<'a> {
+    fn load(
+        args: &EntitiesArgs,
+        kg: &KnowledgeGraphProcessor,
+        inputs: &'a Inputs,
+    ) -> anyhow::Result<Self> {
+        let load =
+            |path: &Option<PathBuf>| path.as_deref().map(|path| load_ids(kg, path)).transpose();
+        let profile_types = match &args.profile {
+            Some(profile) => profile_excluded_types(profile, kg)?,
+            None => &[],
+        };
+        let mut exclude_types = load(&args.exclude_types)?.unwrap_or_default();
+        exclude_types.extend(profile_types.iter().map(|type_id| type_id.to_string()));
+        let redirect_targets = if args.redirect_labels {
+            inputs
+                .redirects
+                .iter()
+                .flat_map(|(target, sources)| {
+                    sources
+                        .iter()
+                        .map(move |source| (source.as_str(), target.as_str()))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            include_ids: load(&args.include_ids)?,
+            exclude_ids: load(&args.exclude_ids)?.unwrap_or_default(),
+            include_types: load(&args.include_types)?,
+            exclude_types,
+            filters_types: args.include_types.is_some()
+                || args.exclude_types.is_some()
+                || !profile_types.is_empty(),
+            redirect_targets,
+            superclasses: args.class_types.then_some(&inputs.type_hierarchy),
+        })
+    }
+
+    /// Whether an entity is part of the build with --include-ids. Redirect
+    /// sources of included entities are kept for their labels.
+    fn is_included(&self, ent: &str) -> bool {
+        self.include_ids.as_ref().is_none_or(|ids| {
+            ids.contains(ent)
+                || self
+                    .redirect_targets
+                    .get(ent)
+                    .is_some_and(|&target| ids.contains(target))
+        })
+    }
+
+    fn is_excluded(&self, ent: &str) -> bool {
+        self.exclude_ids.contains(ent)
+    }
+
+    /// Whether an entity is left out by its type ids, or by its superclasses
+    /// if it is a class and they are its types. Left out entities only keep
+    /// their label and count for the types of the others. Redirect sources
+    /// only give their labels to their targets and are never left out.
+    fn is_type_filtered(&self, ent: &str, info: &EntityInfo) -> bool {
+        if !self.filters_types || self.redirect_targets.contains_key(ent) {
+            return false;
+        }
+        let superclasses = self
+            .superclasses
+            .map_or(&[][..], |hierarchy| hierarchy.parents(ent));
+        let types = info.types.lock().unwrap();
+        let type_ids: Vec<&str> = if superclasses.is_empty() {
+            types.clone()
+        } else {
+            superclasses.iter().map(String::as_str).collect()
+        };
+        self.include_types
+            .as_ref()
+            .is_some_and(|ids| !type_ids.iter().any(|&t| ids.contains(t)))
+            || type_ids.iter().any(|&t| self.exclude_types.contains(t))
+    }
+}
+
+/// Entities read from the entity file or the dump.
+#[derive(Default)]
+struct ReadEntities {
+    /// lines of an entity file, parsed after reading by [`parse_records`]
+    records: Vec<csv::StringRecord>,
+    /// column of every entity field in the lines
+    columns: Vec<Option<usize>>,
+    /// entities parsed while reading or from the lines
+    entities: Vec<Streamed>,
+    num_records: usize,
+    num_not_included: usize,
+    num_excluded: usize,
+    num_unlabeled: usize,
+    /// distinct strings and bytes saved by interning the entities
+    interned: Option<(usize, usize)>,
+}
+
+/// Reads the entities of --file. A Wikidata JSON dump, and an entity file
+/// with --streaming, are parsed while reading, so only the parsed entities
+/// are kept and not every line of the file. Otherwise the lines are kept
+/// for [`parse_records`].
+fn read_entities(
+    args: &EntitiesArgs,
+    kg: &KnowledgeGraphProcessor,
+    input_format: FileFormat,
+    counts: &HashMap<String, usize>,
+    filter: &EntityFilter,
+) -> anyhow::Result<ReadEntities> {
+    let pbar = progress_bar(
+        &format!("loading {} entities", &args.build.knowledge_base),
+        u64::MAX,
+        !args.io.progress,
+    );
+    let mut read = ReadEntities::default();
+    if args.wikidata_json {
+        let mut reader = WikidataDumpReader::new(&args.io.file, args.ignore_types)?
+            .with_counts(counts)
+            .with_filter(|ent| filter.is_included(ent));
+        (read.entities, read.num_excluded) = take_streamed(
+            pbar.wrap_iter(reader.by_ref()),
+            |ent| filter.is_excluded(ent),
+            |ent, info| filter.is_type_filtered(ent, info),
+        )?;
+        read.num_not_included = reader.num_filtered;
+        read.num_records = reader.num_records;
+        read.num_unlabeled = reader.num_unlabeled;
+        read.interned = Some((reader.interner.len(), reader.interner.saved_bytes()));
+        if reader.num_invalid > 0 {
+            eprintln!("skipped {} invalid lines of the dump", reader.num_invalid);
+        }
+    } else if args.streaming {
+        let mut reader =
+            StreamingEntityReader::new(kg, &args.io.file, input_format, args.ignore_types)?
+                .with_counts(counts)
+                .with_filter(|ent| filter.is_included(ent));
+        report_header_problems(&args.io.file, reader.header_problems(), args.build.strict)?;
+        read.columns = reader.columns().to_vec();
+        if read.columns[0].is_none() || read.columns[1].is_none() {
+            bail!("entity file needs an id and a label column");
+        }
+        (read.entities, read.num_excluded) = take_streamed(
+            pbar.wrap_iter(reader.by_ref()),
+            |ent| filter.is_excluded(ent),
+            |ent, info| filter.is_type_filtered(ent, info),
+        )?;
+        read.num_not_included = reader.num_filtered;
+        read.num_records = reader.num_records;
+        read.interned = Some((reader.interner.len(), reader.interner.saved_bytes()));
+    } else {
+        let mut records = pbar.wrap_iter(record_iter(&args.io.file, input_format)?);
+        let header = records.next().expect("file should have at least 1 line")?;
+        let header: Vec<_> = header.iter().collect();
+        let (columns, problems) = map_header_columns(&header, &ENTITY_COLUMNS);
+        report_header_problems(&args.io.file, &problems, args.build.strict)?;
+        if columns[0].is_none() || columns[1].is_none() {
+            bail!("entity file needs an id and a label column");
+        }
+        read.columns = columns;
+        read.records = records.collect::<anyhow::Result<_>>()?;
+        read.num_records = read.records.len();
+    }
+    pbar.finish_and_clear();
+    Ok(read)
+}
+
+/// Parses the lines read from an entity file into entities. Chunks of lines
+/// are parsed in parallel and merged in file order, so the first occurrence
+/// of an entity still wins. The strings of the parsed entities are interned
+/// like the streamed ones, so the lines are dropped before the labels are
+/// assigned.
+fn parse_records(
+    read: &mut ReadEntities,
+    args: &EntitiesArgs,
+    kg: &KnowledgeGraphProcessor,
+    counts: &HashMap<String, usize>,
+    filter: &EntityFilter,
+) {
+    if read.records.is_empty() {
+        return;
+    }
+    let pbar = progress_bar(
+        &format!("processing {} entities", &args.build.knowledge_base),
+        read.records.len() as u64,
+        !args.io.progress,
+    );
+    let columns = &read.columns;
+    let chunks: Vec<(Vec<_>, usize)> = read
+        .records
+        .par_chunks(PARSE_CHUNK_SIZE)
+        .map(|chunk| {
+            let mut num_not_included = 0;
+            let parsed = chunk
+                .iter()
+                .filter_map(|record| {
+                    let mut fields = select_columns(record, columns);
+                    let ent = fields.first().and_then(|ent| kg.parse_entity_id(ent));
+                    // checked before parsing, so restricted builds stay cheap
+                    if ent.is_some_and(|ent| !filter.is_included(ent)) {
+                        num_not_included += 1;
+                        return None;
+                    }
+                    let count = ent.and_then(|ent| counts.get(ent)).copied();
+                    if count.is_some() {
+                        // the count column may be empty if the counts file
+                        // supplies it
+                        fields[3] = "0";
+                    }
+                    let (ent, mut info) =
+                        kg.parse_entity_fields(&fields, args.ignore_types).ok()?;
+                    if let Some(count) = count {
+                        info.count = count;
+                    }
+                    let ent = ent.as_str();
+                    // the infos of left out entities are dropped right away
+                    if filter.is_type_filtered(ent, &info) {
+                        return Some((ent, Parsed::TypeFiltered(info.label, info.count)));
+                    }
+                    Some((ent, Parsed::Entity(info)))
+                })
+                .collect();
+            pbar.inc(chunk.len() as u64);
+            (parsed, num_not_included)
+        })
+        .collect();
+    read.num_not_included += chunks.iter().map(|(_, num)| num).sum::<usize>();
+    let mut interner = StringInterner::new();
+    read.entities = chunks
+        .into_iter()
+        .flat_map(|(parsed, _)| parsed)
+        .map(|(ent, parsed)| match parsed {
+            Parsed::Entity(info) => Streamed::Entity(OwnedEntity::new(ent, &info, &mut interner)),
+            Parsed::TypeFiltered(label, count) => {
+                Streamed::TypeFiltered(ent.to_string(), interner.intern(label), count)
+            }
+        })
+        .collect();
+    read.interned = Some((interner.len(), interner.saved_bytes()));
+    read.records = vec![];
+    pbar.finish_and_clear();
+}
+
+/// Entities of the build, with what is needed to resolve their types and
+/// to assign their surface forms. Borrows from the read and merged
+/// entities.
+#[derive(Default)]
+struct Collected<'a> {
+    ent_infos: HashMap<&'a str, EntityInfo<'a>>,
+    /// label and count of the entities left out by their types, which still
+    /// label the types of the others
+    type_filtered: HashMap<&'a str, (&'a str, usize)>,
+    /// labels of redirect sources with the entity they redirect to
+    redirect_labels: Vec<(&'a str, &'a str)>,
+    /// entities used as type of another entity
+    class_ids: HashSet<&'a str>,
+    num_duplicates: usize,
+    num_excluded: usize,
+    num_not_included: usize,
+}
+
+/// Collects the read entities and then the merged ones, keeping the first
+/// occurrence of an entity, since different iri schemes can normalize to the
+/// same entity. Excluded entities are dropped, and the labels of redirect
+/// sources go to their targets.
+fn collect_entities<'a>(
+    read: &'a [Streamed],
+    merged: &'a [MergedEntity],
+    redirects: &'a HashMap<String, Vec<String>>,
+    filter: &EntityFilter<'a>,
+) -> anyhow::Result<Collected<'a>> {
+    let mut collected = Collected::default();
+    for streamed in read {
+        let (ent, parsed) = match streamed {
+            Streamed::Entity(ent) => (ent.id.as_str(), Parsed::Entity(ent.info())),
+            Streamed::TypeFiltered(ent, label, count) => {
+                (ent.as_str(), Parsed::TypeFiltered(label, *count))
+            }
+        };
+        if filter.is_excluded(ent) {
+            collected.num_excluded += 1;
+            continue;
+        }
+        if collected.ent_infos.contains_key(ent) || collected.type_filtered.contains_key(ent) {
+            collected.num_duplicates += 1;
+            continue;
+        }
+        let mut info = match parsed {
+            Parsed::Entity(info) => info,
+            Parsed::TypeFiltered(label, count) => {
+                collected.type_filtered.insert(ent, (label, count));
+                continue;
+            }
+        };
+        if let Some(&target) = filter.redirect_targets.get(ent) {
+            collected.redirect_labels.extend(
+                std::iter::once(info.label)
+                    .chain(info.aliases.iter().copied())
+                    .map(|label| (label, target)),
+            );
+            continue;
+        }
+        info.redirects = redirects.get(ent);
+        collected.ent_infos.insert(ent, info);
+    }
+
+    fn strs<'v>(values: &'v [Cow<str>]) -> Vec<&'v str> {
+        values.iter().map(AsRef::as_ref).collect()
+    }
+    for value in merged {
+        let ent = &*value.id;
+        let value_redirect_labels = value.redirect_labels.as_deref();
+        let Some(label) = value.label.as_deref() else {
+            // only the redirect labels of an entity of another shard
+            let Some(labels) = value_redirect_labels else {
+                bail!("invalid merged entity {ent} without label and redirect labels");
+            };
+            collected
+                .redirect_labels
+                .extend(strs(labels).into_iter().map(|label| (label, ent)));
+            continue;
+        };
+        if collected.ent_infos.contains_key(ent) {
+            collected.num_duplicates += 1;
+            continue;
+        } else if filter.is_excluded(ent) {
+            collected.num_excluded += 1;
+            continue;
+        } else if !filter.is_included(ent) {
+            collected.num_not_included += 1;
+            continue;
+        }
+        if value.class {
+            collected.class_ids.insert(ent);
+        }
+        collected.redirect_labels.extend(
+            strs(value_redirect_labels.unwrap_or_default())
+                .into_iter()
+                .map(|label| (label, ent)),
+        );
+        collected.ent_infos.insert(
+            ent,
+            EntityInfo {
+                label,
+                desc: &value.description,
+                aliases: strs(&value.aliases),
+                types: Arc::new(Mutex::new(strs(&value.types))),
+                count: value.count,
+                redirects: redirects.get(ent),
+            },
+        );
+    }
+    Ok(collected)
+}
+
+/// Describes classes by their superclasses (P279), e.g. sovereign state by
+/// country, instead of by what they are an instance of (P31), like regular
+/// items. Returns the number of these classes.
+fn use_superclass_types<'a>(
+    collected: &mut Collected<'a>,
+    type_hierarchy: &'a TypeHierarchy,
+) -> usize {
+    let mut num_classes = 0;
+    for (&ent, info) in &collected.ent_infos {
+        let superclasses = type_hierarchy.parents(ent);
+        if !superclasses.is_empty() {
+            *info.types.lock().unwrap() = superclasses.iter().map(String::as_str).collect();
+            collected.class_ids.insert(ent);
+            num_classes += 1;
+        }
+    }
+    num_classes
+}
+
+/// Fetches the labels of the types that are no entity of the build and
+/// have no label from --type-labels or the --type-label-cache, and adds them
+/// to the type labels. Returns the number of fetched and of still missing
+/// labels.
+fn fetch_missing_type_labels(
+    args: &EntitiesArgs,
+    kg: &KnowledgeGraphProcessor,
+    input_format: FileFormat,
+    collected: &Collected,
+    type_labels: &mut HashMap<EntityId, String>,
+) -> anyhow::Result<(usize, usize)> {
+    let mut cached = match &args.type_label_cache {
+        Some(path) if path.is_file() => load_type_labels(kg, path, input_format, args.io.progress)?,
+        _ => HashMap::new(),
+    };
+    let missing: Vec<_> = collected
+        .ent_infos
+        .values()
+        .flat_map(|info| info.types.lock().unwrap().clone())
+        .filter(|&type_id| {
+            !collected.ent_infos.contains_key(type_id)
+                && !collected.type_filtered.contains_key(type_id)
+                && !type_labels.contains_key(type_id)
+                && !cached.contains_key(type_id)
+        })
+        .unique()
+        .sorted()
+        .collect();
+    let fetched = fetch_type_labels(kg, args, &missing)?;
+    let num_missing = missing.len() - fetched.len();
+    let num_fetched = fetched.len();
+    for (type_id, label) in fetched {
+        cached.insert(kg.entity_id(&kg.format_entity(&type_id, false))?, label);
+    }
+    if let Some(path) = &args.type_label_cache {
+        let mut cache_output = record_writer(path, input_format)?;
+        for (type_id, label) in cached.iter().sorted_by_key(|(type_id, _)| &type_id.id) {
+            cache_output.write_record([type_id.iri.as_str(), label])?;
+        }
+        cache_output.flush()?;
+    }
+    type_labels.extend(cached);
+    Ok((num_fetched, num_missing))
+}
+
+/// Resolves the types of the entities to their labels, sorted by the count
+/// of the type entities, so the most popular type comes last. Left out
+/// entities still label the types of the others, types without any label
+/// are dropped. With specific_types, only the most specific types are kept,
+/// and a type without label is replaced by its nearest labeled superclass.
+/// Returns the number of dropped ancestor types and of replaced types.
+fn resolve_types<'a>(
+    collected: &mut Collected<'a>,
+    type_labels: &'a HashMap<EntityId, String>,
+    type_hierarchy: &'a TypeHierarchy,
+    specific_types: bool,
+) -> (usize, usize) {
+    let Collected {
+        ent_infos,
+        type_filtered,
+        class_ids,
+        ..
+    } = collected;
+    let mut num_ancestor_types = 0;
+    let mut num_generalized_types = 0;
+    if specific_types {
+        let has_label = |type_id: &str| {
+            ent_infos.contains_key(type_id)
+                || type_filtered.contains_key(type_id)
+                || type_labels.contains_key(type_id)
+        };
+        for info in ent_infos.values() {
+            let mut types = info.types.lock().unwrap();
+            // e.g. city instead of human settlement, unless all types are in
+            // a cycle
+            let specific = type_hierarchy.most_specific(&types);
+            if !specific.is_empty() {
+                num_ancestor_types += types.len() - specific.len();
+                *types = specific;
+            }
+            // types without label are left out below, a labeled superclass
+            // still describes the entity
+            for type_id in types.iter_mut().filter(|type_id| !has_label(type_id)) {
+                if let Some(ancestor) = type_hierarchy.nearest_ancestor(type_id, has_label) {
+                    *type_id = ancestor;
+                    num_generalized_types += 1;
+                }
+            }
+            let mut seen = HashSet::new();
+            types.retain(|&type_id| seen.insert(type_id));
+        }
+    }
+    class_ids.extend(
+        ent_infos
+            .values()
+            .flat_map(|info| info.types.lock().unwrap().clone())
+            .filter(|type_id| ent_infos.contains_key(type_id)),
+    );
+    let type_info = |type_id: &str| {
+        ent_infos
+            .get(type_id)
+            .map(|info| (info.label, info.count))
+            .or_else(|| type_filtered.get(type_id).copied())
+    };
+    ent_infos.values().for_each(|info| {
+        let mut types = info.types.lock().unwrap();
+        types.sort_by_key(|&type_id| type_info(type_id).map_or(0, |(_, count)| count));
+        *types = types
+            .iter()
+            .filter_map(|&type_id| {
+                type_info(type_id)
+                    .map(|(label, _)| label)
+                    .or_else(|| type_labels.get(type_id).map(String::as_str))
+            })
+            .collect();
+    });
+    (num_ancestor_types, num_generalized_types)
+}
+
+/// Writes the entities of the build for --save-merged, one at a time, and
+/// the redirect labels of entities that are not part of the build, e.g. of
+/// another shard of the dump, as records of their own.
+fn save_merged(path: &Path, collected: &Collected) -> anyhow::Result<()> {
+    let mut target_redirect_labels: HashMap<_, Vec<_>> = HashMap::new();
+    for &(label, target) in &collected.redirect_labels {
+        target_redirect_labels
+            .entry(target)
+            .or_default()
+            .push(label);
+    }
+    let other_targets: Vec<_> = target_redirect_labels
+        .iter()
+        .filter(|(target, _)| !collected.ent_infos.contains_key(*target))
+        .sorted()
+        .collect();
+    let mut writer = EntityCacheWriter::new(path, collected.ent_infos.len() + other_targets.len())?;
+    fn borrowed<'v>(values: &[&'v str]) -> Vec<Cow<'v, str>> {
+        values.iter().map(|&v| Cow::Borrowed(v)).collect()
+    }
+    for (&ent, info) in collected.ent_infos.iter().sorted_by_key(|&(&ent, _)| ent) {
+        writer.write(&MergedEntity {
+            id: Cow::Borrowed(ent),
+            label: Some(Cow::Borrowed(info.label)),
+            description: Cow::Borrowed(info.desc),
+            count: info.count,
+            types: borrowed(&info.types.lock().unwrap()),
+            aliases: borrowed(&info.aliases),
+            redirects: info
+                .redirects
+                .map(|redirs| redirs.iter().map(|r| Cow::Borrowed(r.as_str())).collect()),
+            redirect_labels: target_redirect_labels
+                .get(ent)
+                .map(|labels| borrowed(labels)),
+            class: collected.class_ids.contains(ent),
+        })?;
+    }
+    for (target, labels) in other_targets {
+        writer.write(&MergedEntity {
+            id: Cow::Borrowed(target),
+            redirect_labels: Some(borrowed(labels)),
+            ..Default::default()
+        })?;
+    }
+    writer.finish()
+}
+
+/// Keeps only the entities of a delta and those sharing a surface form with
+/// it, which are resolved again with --previous-index.
+fn retain_touched_entities<'a>(
+    ent_infos: &mut HashMap<&'a str, EntityInfo<'a>>,
+    redirect_labels: &[(&'a str, &'a str)],
+    delta: &DeltaChanges,
+) {
+    let touched = |form: &str| delta.forms.contains(form);
+    let redirect_label_targets: HashSet<_> = redirect_labels
+        .iter()
+        .filter(|&&(label, _)| touched(label))
+        .map(|&(_, target)| target)
+        .collect();
+    ent_infos.retain(|&ent, info| {
+        delta.entities.contains(ent)
+            || redirect_label_targets.contains(ent)
+            || touched(info.label)
+            || info.aliases.iter().any(|&alias| touched(alias))
+    });
+}
+
+/// The normalizer of labels and aliases for --unicode-form and
+/// --clean-labels, if any.
+fn label_normalizer(args: &EntitiesArgs) -> anyhow::Result<Option<Normalizer>> {
+    let unicode_form = args
+        .unicode_form
+        .as_deref()
+        .map(UnicodeForm::try_from)
+        .transpose()?;
+    Ok(match (unicode_form, args.clean_labels) {
+        (None, false) => None,
+        (form, true) => Some(Normalizer::new(form)),
+        (form, false) => Some(Normalizer {
+            form,
+            ..Default::default()
+        }),
+    })
+}
+
+/// Labels, aliases, and redirect labels that change when they are
+/// normalized, with their normalized form.
+fn normalized_forms<'a>(
+    normalizer: &Normalizer,
+    ent_infos: &HashMap<&'a str, EntityInfo<'a>>,
+    redirect_labels: &[(&'a str, &'a str)],
+) -> HashMap<&'a str, String> {
+    ent_infos
+        .values()
+        .flat_map(|info| std::iter::once(info.label).chain(info.aliases.iter().copied()))
+        .chain(redirect_labels.iter().map(|&(label, _)| label))
+        .filter_map(|form| match normalizer.normalize(form) {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(normalized) => Some((form, normalized)),
+        })
+        .collect()
+}
+
+/// Replaces labels, aliases, and redirect labels by their normalized forms.
+/// Aliases that became equal to the label or another alias are dropped, and
+/// so are empty redirect labels and the entities whose label became empty.
+/// Returns the number of these entities.
+fn apply_normalized_forms<'a>(
+    normalized_forms: &'a HashMap<&'a str, String>,
+    ent_infos: &mut HashMap<&'a str, EntityInfo<'a>>,
+    redirect_labels: &mut Vec<(&'a str, &'a str)>,
+) -> usize {
+    if normalized_forms.is_empty() {
+        return 0;
+    }
+    let normalize = |form| normalized_forms.get(form).map_or(form, String::as_str);
+    let mut num_empty_labels = 0;
+    ent_infos.retain(|_, info| {
+        info.label = normalize(info.label);
+        let mut seen = HashSet::from([info.label]);
+        info.aliases = info
+            .aliases
+            .iter()
+            .map(|&alias| normalize(alias))
+            .filter(|&alias| !alias.is_empty() && seen.insert(alias))
+            .collect();
+        num_empty_labels += usize::from(info.label.is_empty());
+        !info.label.is_empty()
+    });
+    redirect_labels.retain_mut(|(label, _)| {
+        *label = normalize(label);
+        !label.is_empty()
+    });
+    num_empty_labels
+}
+
+/// Truncates labels longer than --max-label-length characters or
+/// --max-label-tokens tokens, or drops their entities with
+/// --drop-long-labels. Returns the number of long labels.
+fn truncate_labels(ent_infos: &mut HashMap<&str, EntityInfo>, args: &EntitiesArgs) -> usize {
+    let mut num_long_labels = 0;
+    ent_infos.retain(|_, info| {
+        let Some(cut) = truncate_label(info.label, args.max_label_length, args.max_label_tokens)
+        else {
+            return true;
+        };
+        num_long_labels += 1;
+        info.label = cut;
+        !args.drop_long_labels && !cut.is_empty()
+    });
+    num_long_labels
+}
+
+/// Merges every class of duplicates of --merge-duplicates into one entity,
+/// which gets the labels and aliases of the others as aliases. Returns the
+/// number of merged entities.
+fn merge_confirmed_duplicates(
+    ent_infos: &mut HashMap<&str, EntityInfo>,
+    pairs: &[(String, String)],
+) -> usize {
+    let mut num_merged = 0;
+    let confirmed: HashSet<_> = pairs
+        .iter()
+        .map(|(duplicate, _)| duplicate.as_str())
+        .collect();
+    for class in equivalence_classes(pairs) {
+        let mut members: Vec<_> = class
+            .into_iter()
+            .filter(|&ent| ent_infos.contains_key(ent))
+            .collect();
+        // the class is merged into an entity that is no duplicate itself,
+        // the most popular one if there are several (or only duplicates, in
+        // a cycle), ties go to the smaller id
+        members.sort_by_key(|&ent| (confirmed.contains(ent), Reverse(ent_infos[ent].count), ent));
+        let Some((&ent, duplicates)) = members.split_first() else {
+            continue;
+        };
+        for &duplicate in duplicates {
+            let duplicate_info = ent_infos.remove(duplicate).unwrap();
+            let info = ent_infos.get_mut(ent).unwrap();
+            for alias in std::iter::once(duplicate_info.label).chain(duplicate_info.aliases) {
+                if alias != info.label && !info.aliases.contains(&alias) {
+                    info.aliases.push(alias);
+                }
+            }
+            num_merged += 1;
+        }
+    }
+    num_merged
+}
+
+/// Drops aliases shorter than min_length characters or on the stoplist
+/// file, with one alias per line, compared in lowercase. Returns the entity,
+/// alias, and reason of every dropped alias, sorted.
+fn filter_aliases<'a>(
+    ent_infos: &mut HashMap<&'a str, EntityInfo<'a>>,
+    stoplist: Option<&Path>,
+    min_length: usize,
+) -> anyhow::Result<Vec<(&'a str, &'a str, &'static str)>> {
+    let stoplist: HashSet<_> = match stoplist {
+        Some(path) => line_iter(path)?
+            .map(|line| Ok(line?.trim().to_lowercase()))
+            .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+            .collect::<anyhow::Result<_>>()?,
+        None => HashSet::new(),
+    };
+    let mut filtered = vec![];
+    for (&ent, info) in ent_infos.iter_mut() {
+        info.aliases.retain(|&alias| {
+            let reason = if alias.chars().count() < min_length {
+                "too short"
+            } else if stoplist.contains(&alias.to_lowercase()) {
+                "stoplist"
+            } else {
+                return true;
+            };
+            filtered.push((ent, alias, reason));
+            false
+        });
+    }
+    filtered.sort();
+    Ok(filtered)
+}
+
+/// Drops aliases that are literals like years, dates, and numbers, and the
+/// entities whose label is one. Returns the entity, surface form, and kind
+/// of every literal, sorted by entity.
+fn remove_literals<'a>(
+    ent_infos: &mut HashMap<&'a str, EntityInfo<'a>>,
+) -> anyhow::Result<Vec<(&'a str, &'a str, LiteralKind)>> {
+    let detector = LiteralDetector::new()?;
+    let mut literals = vec![];
+    ent_infos.retain(|&ent, info| {
+        info.aliases.retain(|&alias| {
+            let kind = detector.detect(alias);
+            literals.extend(kind.map(|kind| (ent, alias, kind)));
+            kind.is_none()
+        });
+        let kind = detector.detect(info.label);
+        literals.extend(kind.map(|kind| (ent, info.label, kind)));
+        kind.is_none()
+    });
+    literals.sort_by_key(|&(ent, form, _)| (ent, form));
+    Ok(literals)
+}
+
+/// Drops aliases within edit distance one of the label or of a more
+/// frequent alias of the same entity, where the frequency of a surface form
+/// is the number of entities using it. Forms shorter than min_length
+/// characters are never collapsed. Returns the number of dropped aliases.
+fn collapse_near_duplicate_aliases(
+    ent_infos: &mut HashMap<&str, EntityInfo>,
+    min_length: usize,
+) -> usize {
+    let mut frequencies: HashMap<&str, usize> = HashMap::new();
+    for info in ent_infos.values() {
+        for &form in std::iter::once(&info.label).chain(&info.aliases) {
+            *frequencies.entry(form).or_default() += 1;
+        }
+    }
+    let mut num_collapsed = 0;
+    for info in ent_infos.values_mut() {
+        // the label is always kept, aliases by descending frequency
+        let mut kept = vec![info.label];
+        for &alias in info
+            .aliases
+            .iter()
+            .sorted_by_key(|&&alias| (Reverse(frequencies[alias]), alias))
+        {
+            let collapse = alias.chars().count() >= min_length
+                && kept.iter().any(|&form| {
+                    form.chars().count() >= min_length && within_edit_distance_one(form, alias)
+                });
+            if !collapse {
+                kept.push(alias);
+            }
+        }
+        let num_aliases = info.aliases.len();
+        info.aliases.retain(|alias| kept.contains(alias));
+        num_collapsed += num_aliases - info.aliases.len();
+    }
+    num_collapsed
+}
+
+/// English plural or singular forms of the labels and aliases of classes,
+/// with their class, the most popular class first.
+fn number_variants<'a>(
+    ent_infos: &HashMap<&'a str, EntityInfo<'a>>,
+    class_ids: &HashSet<&'a str>,
+) -> Vec<(String, &'a str)> {
+    class_ids
+        .iter()
+        .filter_map(|&ent| ent_infos.get_key_value(ent))
+        .sorted_by_key(|&(&ent, info)| (Reverse(info.count), ent))
+        .flat_map(|(&ent, info)| {
+            std::iter::once(info.label)
+                .chain(info.aliases.iter().copied())
+                .filter_map(english_number_variant)
+                .map(move |variant| (variant, ent))
+        })
+        .collect()
+}
+
+/// Name and qualifier of every DBpedia entity whose title has a qualifier,
+/// e.g. Paris and mythology for Paris_(mythology), the most popular entity
+/// first.
+fn title_aliases<'a>(
+    ent_infos: &HashMap<&'a str, EntityInfo<'a>>,
+) -> Vec<(String, String, &'a str)> {
+    ent_infos
+        .iter()
+        .sorted_by_key(|&(&ent, info)| (Reverse(info.count), ent))
+        .filter_map(|(&ent, _)| {
+            let (name, qualifier) = title_qualifier(ent.rsplit('/').next()?)?;
+            Some((name, qualifier, ent))
+        })
+        .collect()
+}
+
+/// Adds the entities to an [`EntityIndexBuilder`] and assigns their labels.
+/// With --disambiguate-id-labels, id-like surface forms must not be indexed
+/// without info.
+fn assign_labels<'a>(
+    args: &'a EntitiesArgs,
+    kg: &'a KnowledgeGraphProcessor,
+    options: EntityIndexOptions,
+    ent_infos: HashMap<&'a str, EntityInfo<'a>>,
+) -> EntityIndexBuilder<'a> {
+    let mut builder = EntityIndexBuilder::new(options)
+        .with_needs_info(move |label| args.disambiguate_id_labels && kg.looks_like_id(label))
+        .with_alias_priors(args.alias_priors)
+        .with_progress(args.io.progress);
+    for (ent, info) in ent_infos {
+        builder.add_entity(ent, info);
+    }
+    builder.resolve_labels();
+    builder
+}
+
+/// Adds the number variants of classes, only if their surface form is still
+/// free. Returns the number of added variants.
+fn add_number_variants<'a>(
+    builder: &mut EntityIndexBuilder<'a>,
+    number_variants: &'a [(String, &'a str)],
+) -> usize {
+    number_variants
+        .iter()
+        .filter(|(variant, ent)| builder.add_surface_form(variant, None, Ent::Alias(ent)))
+        .count()
+}
+
+/// Adds titles without their qualifier, with the qualifier as info if the
+/// name alone is taken. Returns the number of added surface forms.
+fn add_title_aliases<'a>(
+    builder: &mut EntityIndexBuilder<'a>,
+    title_aliases: &'a [(String, String, &'a str)],
+) -> usize {
+    let num_before = builder.index().len();
+    for (name, qualifier, ent) in title_aliases {
+        let named = builder.index().get(&(name.as_str(), None));
+        if named.is_some_and(|named| named.as_str() == *ent)
+            || !builder.needs_info(name) && builder.add_surface_form(name, None, Ent::Alias(ent))
+        {
+            continue;
+        }
+        builder.add_surface_form(name, Some(qualifier), Ent::AliasInfo(ent));
+    }
+    builder.index().len() - num_before
+}
+
+/// Adds the label of an entity said to be the same as another one to the
+/// other, only where its surface form, or with info, is still free. Returns
+/// the number of added surface forms.
+fn add_same_as_labels(builder: &mut EntityIndexBuilder, same_as: &[(String, String)]) -> usize {
+    let num_before = builder.index().len();
+    let ent_infos = builder.entities();
+    let same_as_labels: Vec<_> = same_as
+        .iter()
+        .filter_map(|(a, b)| {
+            let (a, a_info) = ent_infos.get_key_value(a.as_str())?;
+            let (b, b_info) = ent_infos.get_key_value(b.as_str())?;
+            Some([
+                (b_info.label, *a, a_info.count),
+                (a_info.label, *b, b_info.count),
+            ])
+        })
+        .flatten()
+        .sorted_by_key(|&(label, ent, count)| (Reverse(count), ent, label))
+        .collect();
+    for (label, ent, _) in same_as_labels {
+        let info = builder.suffix(ent);
+        if builder.needs_info(label)
+            || builder.add_surface_form(label, None, Ent::Alias(ent))
+            || info.is_empty()
+        {
+            continue;
+        }
+        // usually the label of the other entity, so with info like an
+        // ambiguous alias
+        builder.add_surface_form(label, Some(info), Ent::AliasInfo(ent));
+    }
+    builder.index().len() - num_before
+}
+
+/// Adds redirect labels, which have the lowest priority, so only if their
+/// surface form is still free. Returns the number of added labels.
+fn add_redirect_labels<'a>(
+    builder: &mut EntityIndexBuilder<'a>,
+    mut redirect_labels: Vec<(&'a str, &'a str)>,
+) -> usize {
+    let num_before = builder.index().len();
+    let ent_infos = builder.entities();
+    redirect_labels.retain(|(_, target)| ent_infos.contains_key(target));
+    redirect_labels
+        .sort_by_key(|&(label, target)| (Reverse(ent_infos[target].count), target, label));
+    for (label, target) in redirect_labels {
+        if !builder.needs_info(label) {
+            builder.add_surface_form(label, None, Ent::Redirect(target));
+        }
+    }
+    builder.index().len() - num_before
+}
+
+/// Number of entities and of entities in the index per top-level (most
+/// popular) type, for the top most common types.
+fn type_coverage<'a>(
+    builder: &EntityIndexBuilder<'a>,
+    covered: &HashSet<&str>,
+    top: usize,
+) -> Vec<(&'a str, usize, usize)> {
+    let mut type_coverage: HashMap<_, (usize, usize)> = HashMap::new();
+    for (&ent, info) in builder.entities() {
+        let ent_type = info
+            .types
+            .lock()
+            .unwrap()
+            .last()
+            .copied()
+            .unwrap_or("untyped");
+        let (total, num_covered) = type_coverage.entry(ent_type).or_default();
+        *total += 1;
+        *num_covered += covered.contains(ent) as usize;
+    }
+    type_coverage
+        .into_iter()
+        .sorted_by_key(|&(ent_type, (total, _))| (Reverse(total), ent_type))
+        .take(top)
+        .map(|(ent_type, (total, num_covered))| (ent_type, total, num_covered))
+        .collect()
+}
+
+/// Index rows of the assigned surface forms, with the entity and its
+/// redirects formatted for the output.
+fn index_rows(
+    builder: &EntityIndexBuilder,
+    kg: &KnowledgeGraphProcessor,
+    short_entities: bool,
+) -> Vec<Row> {
+    let ent_infos = builder.entities();
+    builder
+        .rows()
+        .into_par_iter()
+        .map(|(ent, row)| {
+            let info = ent_infos.get(&ent).unwrap();
+            let ent_id = kg.format_entity(ent, short_entities);
+            let redirs: Option<Vec<_>> = info.redirects.map(|redirs| {
+                redirs
+                    .iter()
+                    .map(|r| kg.format_entity(r, short_entities))
+                    .collect()
+            });
+            (ent_id, row, redirs)
+        })
+        .collect()
+}
+
+/// Inputs shared by all outputs written.
+struct OutputContext<'a> {
+    args: &'a EntitiesArgs,
+    kg: &'a KnowledgeGraphProcessor,
+    out: &'a OutputDir,
+    /// format of the outputs besides the index, which are always tables
+    table_format: FileFormat,
+    escaping: SurfaceFormEscaping,
+}
+
+impl OutputContext<'_> {
+    fn table(&self, name: &str) -> anyhow::Result<csv::Writer<File>> {
+        record_writer(
+            self.out.path(&self.table_format.file_name(name)),
+            self.table_format,
+        )
+    }
+
+    /// The output id of an entity.
+    fn id(&self, ids: &mut IdMap, ent: &str) -> String {
+        ids.map(self.kg.format_entity(ent, self.args.short_entities))
+    }
+}
+
+/// Writes the index with its prefixes, redirects, and redirect labels, and
+/// with --dictionary-index and --fst also in these forms.
+fn write_index(
+    ctx: &OutputContext,
+    output_format: FileFormat,
+    rows: Vec<Row>,
+    ent_infos: &HashMap<&str, EntityInfo>,
+    ids: &mut IdMap,
+    stats: &mut Stats,
+) -> anyhow::Result<()> {
+    let OutputContext { args, kg, out, .. } = ctx;
+    let (mut output, mut jsonl_output) = if output_format == FileFormat::Jsonl {
+        let file = File::create(out.path(&output_format.file_name("index")))?;
+        (None, Some(BufWriter::new(file)))
+    } else {
+        let file = out.path(&output_format.file_name("index"));
+        (Some(record_writer(file, output_format)?), None)
+    };
+    let mut prefix_output = ctx.table("prefixes")?;
+    let mut prefixes = kg.entity_prefixes();
+    if args.include_statement_prefixes {
+        prefixes.extend(kg.statement_prefixes());
+    }
+    for (short, long) in prefixes {
+        prefix_output.write_record([short, long])?;
+    }
+    prefix_output.flush()?;
+
+    let mut redirect_output = ctx.table("redirects")?;
+    let mut redirect_label_output = if args.redirect_labels {
+        Some(ctx.table("redirect-labels")?)
+    } else {
+        None
+    };
+
+    // info and count of every entity for the jsonl index
+    let ent_details: HashMap<_, _> = if jsonl_output.is_some() {
+        ent_infos
+            .iter()
+            .map(|(&ent, info)| {
+                let id = kg.format_entity(ent, args.short_entities);
+                (id, (info.info(), info.count))
+            })
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let out_ids: Vec<_> = rows
+        .iter()
+        .map(|(ent_id, ..)| ids.map(ent_id.clone()))
+        .collect();
+    let mut fst_entries = vec![];
+    if args.dictionary_index {
+        let num_strings = write_dictionary_index(
+            out.path("index.dict"),
+            rows.iter().zip(&out_ids).map(|((_, row, _), out_id)| {
+                (out_id.as_str(), row.iter().map(|(form, _)| form.as_str()))
+            }),
+            ctx.escaping,
+        )?;
+        stats.count("dictionary_strings", num_strings);
+    }
+    let pbar = progress_bar("writing outputs", rows.len() as u64, !args.io.progress);
+    for (i, ((ent_id, row, redirs), out_id)) in rows.into_iter().zip(out_ids).enumerate() {
+        pbar.inc(1);
+        let redirs: Option<Vec<_>> =
+            redirs.map(|redirs| redirs.into_iter().map(|r| ids.map(r)).collect());
+        let (row, kinds): (Vec<_>, Vec<_>) = row.into_iter().unzip();
+        let forms_of = |kind: fn(&FormKind) -> bool| {
+            row.iter()
+                .zip(&kinds)
+                .filter(move |(_, k)| kind(k))
+                .map(|(form, _)| form)
+        };
+        if args.fst {
+            fst_entries.extend(row.iter().map(|form| (form.clone(), i as u64)));
+        }
+        if let Some(redirs) = &redirs {
+            redirect_output.write_record(std::iter::once(&out_id).chain(redirs))?;
+        }
+        if let Some(redirect_label_output) = &mut redirect_label_output {
+            let mut redirect_labels = forms_of(|&kind| kind == FormKind::Redirect).peekable();
+            if redirect_labels.peek().is_some() {
+                redirect_label_output
+                    .write_record(std::iter::once(&out_id).chain(redirect_labels))?;
+            }
+        }
+        if let Some(output) = &mut output {
+            output.write_record(std::iter::once(&out_id).chain(&row))?;
+        } else if let Some(output) = &mut jsonl_output {
+            let (info, count) = match ent_details.get(&ent_id) {
+                Some(&(info, count)) => (Some(info), Some(count)),
+                None => (None, None),
+            };
+            // the label, with or without info, comes first if the entity got
+            // it, otherwise the entity is only found by its aliases
+            let label = kinds
+                .first()
+                .filter(|kind| kind.is_label())
+                .map(|_| &row[0]);
+            // the label with info follows the label alone with
+            // --label-info-variants
+            let label_info = kinds
+                .get(1)
+                .filter(|&&kind| kind == FormKind::LabelInfo && label.is_some())
+                .map(|_| &row[1]);
+            let aliases: Vec<_> =
+                forms_of(|&kind| matches!(kind, FormKind::Alias | FormKind::AliasInfo)).collect();
+            let redirect_labels: Vec<_> = forms_of(|&kind| kind == FormKind::Redirect).collect();
+            let mut value = json!({
+                "id": out_id,
+                "label": label,
+                "info": info,
+                "aliases": aliases,
+                "redirect_labels": redirect_labels,
+                "redirects": redirs,
+                "count": count,
+            });
+            if args.label_info_variants {
+                value["label_info"] = json!(label_info);
+            }
+            writeln!(output, "{value}")?;
+        }
+    }
+    pbar.finish_and_clear();
+    if let Some(output) = &mut output {
+        output.flush()?;
+    } else if let Some(output) = &mut jsonl_output {
+        output.flush()?;
+    }
+    redirect_output.flush()?;
+    if let Some(redirect_label_output) = &mut redirect_label_output {
+        redirect_label_output.flush()?;
+    }
+
+    if args.fst {
+        let num_keys = write_fst(out.path("index.fst"), fst_entries)?;
+        stats.count("fst_keys", num_keys);
+    }
+    Ok(())
+}
+
+/// Writes the prior of every entity sharing an alias with --alias-priors,
+/// from the counts of the entities.
+fn write_alias_priors(
+    ctx: &OutputContext,
+    builder: &EntityIndexBuilder,
+    ids: &mut IdMap,
+) -> anyhow::Result<()> {
+    let mut priors_output = ctx.table("alias-priors")?;
+    for (alias, ents) in &builder.shared_aliases {
+        let total: usize = ents.iter().map(|&(_, count)| count).sum();
+        for &(ent, count) in ents {
+            // entities without counts share the alias equally
+            let prior = if total == 0 {
+                1.0 / ents.len() as f64
+            } else {
+                count as f64 / total as f64
+            };
+            priors_output.write_record([
+                alias,
+                ctx.id(ids, ent).as_str(),
+                count.to_string().as_str(),
+                format!("{prior:.4}").as_str(),
+            ])?;
+        }
+    }
+    priors_output.flush()?;
+    Ok(())
+}
+
+/// Writes the sources of the surface forms merged from a label and an alias
+/// with --label-alias-precedence merge.
+fn write_surface_form_sources(
+    ctx: &OutputContext,
+    builder: &EntityIndexBuilder,
+    ids: &mut IdMap,
+) -> anyhow::Result<()> {
+    let mut sources_output = ctx.table("surface-form-sources")?;
+    let index = builder.index();
+    for (&(form, info), sources) in builder
+        .merged_sources
+        .iter()
+        .sorted_by_key(|&(&(form, info), _)| (index[&(form, info)].as_str(), form, info))
+    {
+        sources_output.write_record([
+            ctx.id(ids, index[&(form, info)].as_str()).as_str(),
+            format_surface_form(form, info, ctx.escaping).as_str(),
+            sources.join("+").as_str(),
+        ])?;
+    }
+    sources_output.flush()?;
+    Ok(())
+}
+
+/// Writes the aliases left out by a filter, which have no holder, and those
+/// dropped because another entity holds them.
+fn write_dropped_aliases(
+    ctx: &OutputContext,
+    filtered_aliases: &[(&str, &str, &str)],
+    builder: &EntityIndexBuilder,
+    ids: &mut IdMap,
+) -> anyhow::Result<()> {
+    let mut dropped_output = ctx.table("dropped-aliases")?;
+    for &(ent, alias, reason) in filtered_aliases {
+        dropped_output.write_record([ctx.id(ids, ent).as_str(), alias, reason, ""])?;
+    }
+    for &(ent, alias, reason, holder) in &builder.dropped_aliases {
+        dropped_output.write_record([
+            ctx.id(ids, ent).as_str(),
+            alias,
+            reason,
+            ctx.id(ids, holder).as_str(),
+        ])?;
+    }
+    dropped_output.flush()?;
+    Ok(())
+}
+
+/// Writes the entities with the label and description of another one with
+/// --duplicates.
+fn write_duplicates(
+    ctx: &OutputContext,
+    duplicates: &[(&str, &str, &str, &str)],
+    ids: &mut IdMap,
+) -> anyhow::Result<()> {
+    let mut duplicate_output = ctx.table("duplicates")?;
+    for &(duplicate, ent, label, desc) in duplicates {
+        duplicate_output.write_record([
+            ctx.id(ids, duplicate).as_str(),
+            ctx.id(ids, ent).as_str(),
+            label,
+            desc,
+        ])?;
+    }
+    duplicate_output.flush()?;
+    Ok(())
+}
+
+/// Writes the translated aliases of --translations with their language.
+fn write_translations(
+    ctx: &OutputContext,
+    translations: &[(EntityId, String, String)],
+    ids: &mut IdMap,
+) -> anyhow::Result<()> {
+    let mut translation_output = ctx.table("translations")?;
+    for (ent, alias, lang) in translations {
+        let ent = ids.map_entity(ent, ctx.args.short_entities);
+        translation_output.write_record([ent.as_str(), alias, lang])?;
+    }
+    translation_output.flush()?;
+    Ok(())
+}
+
+/// Writes the literal surface forms left out of the index with
+/// --literal-labels separate.
+fn write_literals(
+    ctx: &OutputContext,
+    literals: &[(&str, &str, LiteralKind)],
+    ids: &mut IdMap,
+) -> anyhow::Result<()> {
+    let mut literal_output = ctx.table("literals")?;
+    for &(ent, form, kind) in literals {
+        literal_output.write_record([ctx.id(ids, ent).as_str(), form, kind.as_str()])?;
+    }
+    literal_output.flush()?;
+    Ok(())
+}
+
+/// Writes the count and rank of all entities, sorted by popularity.
+fn write_popularity(
+    ctx: &OutputContext,
+    popularity: Vec<(Reverse<usize>, String)>,
+    ids: &mut IdMap,
+) -> anyhow::Result<()> {
+    let mut popularity_output = ctx.table("popularity")?;
+    for (rank, (Reverse(count), ent)) in popularity.into_iter().enumerate() {
+        popularity_output.write_record([
+            ids.map(ent),
+            count.to_string(),
+            (rank + 1).to_string(),
+        ])?;
+    }
+    popularity_output.flush()?;
+    Ok(())
+}
+
+/// Writes the numeric ids with their knowledge graph ids.
+fn write_id_map(ctx: &OutputContext, ids: &IdMap) -> anyhow::Result<()> {
+    let mut id_output = ctx.table("id-map")?;
+    for (num, id) in ids.iter() {
+        id_output.write_record([num.to_string().as_str(), id])?;
+    }
+    id_output.flush()?;
+    Ok(())
+}
+
+/// Index row: entity id, surface forms with their kind, and redirects.
+type Row = (String, Vec<(String, FormKind)>, Option<Vec<String>>);
+
+/// Outputs of a previous build, to be augmented with new entities.
+struct ExistingIndex {
+    /// entity id, surface forms, and the number of redirect labels at their
+    /// end, in file order
+    rows: Vec<(String, Vec<String>, usize)>,
+    redirects: HashMap<String, Vec<String>>,
+    counts: Vec<(String, usize)>,
+}
+
+impl ExistingIndex {
+    fn load(dir: &Path, format: FileFormat) -> anyhow::Result<Self> {
+        let index = dir.join(format.file_name("index"));
+        if !index.is_file() {
+            bail!("{} does not exist", index.display());
+        }
+        let table = |name: &str| -> anyhow::Result<Vec<Vec<String>>> {
+            let path = dir.join(format.file_name(name));
+            if !path.is_file() {
+                return Ok(vec![]);
+            }
+            record_iter(path, format)?
+                .map(|record| Ok(record?.iter().map(str::to_string).collect()))
+                .collect()
+        };
+        let split = |mut row: Vec<String>| {
+            let id = row.remove(0);
+            (id, row)
+        };
+        let redirect_labels: HashMap<_, _> = table("redirect-labels")?
+            .into_iter()
+            .filter(|row| !row.is_empty())
+            .map(split)
+            .collect();
+        let rows = table("index")?
+            .into_iter()
+            .filter(|row| !row.is_empty())
+            .map(|row| {
+                let (id, forms) = split(row);
+                let labels = redirect_labels.get(&id).map_or(&[][..], Vec::as_slice);
+                if !forms.ends_with(labels) {
+                    bail!("redirect labels of {id} are not at the end of its index row");
+                }
+                let num_redirect_labels = labels.len();
+                Ok((id, forms, num_redirect_labels))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let redirects = table("redirects")?
+            .into_iter()
+            .filter(|row| !row.is_empty())
+            .map(split)
+            .collect();
+        let counts = table("popularity")?
+            .into_iter()
+            .filter_map(|row| Some((row.first()?.clone(), row.get(1)?.parse().ok()?)))
+            .collect();
+        Ok(Self {
+            rows,
+            redirects,
+            counts,
+        })
+    }
+
+    /// Removes the rows, redirects, and counts of the given entities.
+    fn remove(&mut self, ids: &HashSet<String>) {
+        self.rows.retain(|(id, ..)| !ids.contains(id));
+        self.redirects.retain(|id, _| !ids.contains(id));
+        self.counts.retain(|(id, _)| !ids.contains(id));
+    }
+
+    /// Adds the surface forms of the new rows that are still free: to the
+    /// existing row of the same entity, or as a new row after the existing
+    /// ones. Returns the rows with the number of added and of conflicting
+    /// surface forms.
+    fn augment(self, rows: Vec<Row>) -> (Vec<Row>, usize, usize) {
+        let Self {
+            rows: existing_rows,
+            mut redirects,
+            ..
+        } = self;
+        let mut owners: HashMap<String, String> = HashMap::new();
+        for (id, forms, _) in &existing_rows {
+            for form in forms {
+                owners.entry(form.clone()).or_insert_with(|| id.clone());
+            }
+        }
+        let existing_ids: HashSet<_> = existing_rows.iter().map(|(id, ..)| id.clone()).collect();
+        let mut additions = HashMap::new();
+        let mut new_rows = vec![];
+        let mut num_added = 0;
+        let mut num_conflicts = 0;
+        for (ent_id, row, redirs) in rows {
+            let mut kept = vec![];
+            for (form, kind) in row {
+                match owners.get(&form) {
+                    Some(owner) if *owner != ent_id => num_conflicts += 1,
+                    Some(_) => {}
+                    None => {
+                        owners.insert(form.clone(), ent_id.clone());
+                        kept.push((form, kind));
+                    }
+                }
+            }
+            num_added += kept.len();
+            if existing_ids.contains(&ent_id) {
+                additions.insert(ent_id, (kept, redirs));
+            } else if !kept.is_empty() {
+                new_rows.push((ent_id, kept, redirs));
+            }
+        }
+        let mut merged: Vec<Row> = existing_rows
+            .into_iter()
+            .map(|(id, mut forms, num_redirect_labels)| {
+                let (added, new_redirs): (Vec<_>, _) = additions.remove(&id).unwrap_or_default();
+                // apart from the redirect labels at the end, the kinds of
+                // existing surface forms are unknown, new forms go before
+                // the redirect labels and new redirect labels after them
+                let existing_redirect_labels = forms.split_off(forms.len() - num_redirect_labels);
+                let (added_redirect_labels, added): (Vec<_>, Vec<_>) = added
+                    .into_iter()
+                    .partition(|&(_, kind)| kind == FormKind::Redirect);
+                let forms: Vec<_> = forms
+                    .into_iter()
+                    .map(|form| (form, FormKind::Alias))
+                    .chain(added)
+                    .chain(
+                        existing_redirect_labels
+                            .into_iter()
+                            .map(|form| (form, FormKind::Redirect)),
+                    )
+                    .chain(added_redirect_labels)
+                    .collect();
+                let mut redirs = redirects.remove(&id).unwrap_or_default();
+                for redir in new_redirs.into_iter().flatten() {
+                    if !redirs.contains(&redir) {
+                        redirs.push(redir);
+                    }
+                }
+                let redirs = if redirs.is_empty() {
+                    None
+                } else {
+                    Some(redirs)
+                };
+                (id, forms, redirs)
+            })
+            .collect();
+        merged.extend(new_rows);
+        (merged, num_added, num_conflicts)
+    }
+}
+
+/// Loads a redirect file with an entity and the entities redirecting to it
+/// (separated by "; ") per line.
+pub fn load_redirects(
+    kg: &KnowledgeGraphProcessor,
+    path: &Path,
+    format: FileFormat,
+    progress: bool,
+) -> anyhow::Result<HashMap<String, Vec<String>>> {
+    let pbar = progress_bar("loading entity redirects", u64::MAX, !progress);
+    let records: Vec<_> = pbar
+        .wrap_iter(record_iter(path, format)?)
+        .collect::<anyhow::Result<_>>()?;
+    let mut redirects = HashMap::new();
+    let pbar = progress_bar(
+        "processing entity redirects",
+        records.len() as u64,
+        !progress,
+    );
+    for record in records {
+        pbar.inc(1);
+        let splits: Vec<_> = record.iter().collect();
+        assert!(splits.len() == 2);
+        let Some(ent) = kg.parse_entity_id(splits[0]) else {
+            continue;
+        };
+        let redirs: Vec<_> = splits[1]
+            .split_terminator("; ")
+            .map(|s| {
+                kg.parse_entity_id(s)
+                    .unwrap_or_else(|| panic!("could not find entity in {s}"))
+                    .to_string()
+            })
+            .collect();
+        if redirs.is_empty() {
+            continue;
+        }
+        redirects.insert(ent.to_string(), redirs);
+    }
+    pbar.finish_and_clear();
+    Ok(redirects)
+}
+
+/// Follows redirect chains, where an entity redirects to an entity that
+/// redirects itself (A to B to C), so every redirect points to the end of
+/// its chain (A and B to C). Redirects in or into a cycle have no such end
+/// and are removed. Returns the number of collapsed chains and of removed
+/// redirects.
+pub fn resolve_redirect_chains(redirects: &mut HashMap<String, Vec<String>>) -> (usize, usize) {
+    // an entity redirecting to several targets keeps the smallest
+    let mut target_of = HashMap::new();
+    for (target, sources) in redirects.iter().sorted() {
+        for source in sources {
+            target_of.entry(source.clone()).or_insert(target.clone());
+        }
+    }
+    let mut resolved: HashMap<String, Vec<String>> = HashMap::new();
+    let mut num_collapsed = 0;
+    let mut num_cyclic = 0;
+    for (target, sources) in redirects.drain().sorted() {
+        let mut canonical = &target;
+        let mut seen = HashSet::from([&target]);
+        let mut cyclic = false;
+        while let Some(next) = target_of.get(canonical) {
+            if !seen.insert(next) {
+                cyclic = true;
+                break;
+            }
+            canonical = next;
+        }
+        if cyclic {
+            num_cyclic += sources.len();
+            continue;
+        }
+        num_collapsed += (*canonical != target) as usize;
+        let canonical_sources = resolved.entry(canonical.clone()).or_default();
+        for source in sources {
+            if source != *canonical && !canonical_sources.contains(&source) {
+                canonical_sources.push(source);
+            }
+        }
+    }
+    resolved.retain(|_, sources| !sources.is_empty());
+    *redirects = resolved;
+    (num_collapsed, num_cyclic)
+}
+
+/// Type ids left out by a built-in profile of --profile.
+fn profile_excluded_types(
+    profile: &str,
+    kg: &KnowledgeGraphProcessor,
+) -> anyhow::Result<&'static [&'static str]> {
+    match (profile, &kg.kg) {
+        // scholarly article, gene, and protein
+        ("no-scholarly", KnowledgeGraph::Wikidata) => Ok(&["Q13442814", "Q7187", "Q8054"]),
+        ("no-scholarly", _) => bail!("--profile no-scholarly needs --knowledge-base wikidata"),
+        _ => bail!("unknown profile {profile}, expected no-scholarly"),
+    }
+}
+
+/// Loads entity ids, one per line, either as iris or as plain ids (e.g. Q42).
+fn load_ids(kg: &KnowledgeGraphProcessor, path: &Path) -> anyhow::Result<HashSet<String>> {
+    let mut ids = HashSet::new();
+    for line in line_iter(path)? {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        ids.insert(kg.parse_entity_id(line).unwrap_or(line).to_string());
+    }
+    Ok(ids)
+}
+
+/// Loads the subclass of statements (class id and superclass id per line),
+/// keeping the superclasses of every class in the order of the file.
+fn load_subclass_of(
+    kg: &KnowledgeGraphProcessor,
+    path: &Path,
+    format: FileFormat,
+) -> anyhow::Result<TypeHierarchy> {
+    let mut hierarchy = TypeHierarchy::new();
+    for record in record_iter(path, format)? {
+        let record = record?;
+        // skips the header and malformed lines
+        let (Some(class), Some(superclass)) = (
+            record.get(0).and_then(|ent| kg.parse_entity_id(ent)),
+            record.get(1).and_then(|ent| kg.parse_entity_id(ent)),
+        ) else {
+            continue;
+        };
+        hierarchy.add(class, superclass);
+    }
+    Ok(hierarchy)
+}
+
+/// Loads type labels (type id and label per line).
+fn load_type_labels(
+    kg: &KnowledgeGraphProcessor,
+    path: &Path,
+    format: FileFormat,
+    progress: bool,
+) -> anyhow::Result<HashMap<EntityId, String>> {
+    let pbar = progress_bar("loading type labels", u64::MAX, !progress);
+    let mut type_labels = HashMap::new();
+    for record in pbar.wrap_iter(record_iter(path, format)?) {
+        let record = record?;
+        // skips the header and malformed lines
+        let (Some(ent), Some(label)) = (
+            record.get(0).and_then(|ent| kg.entity_id(ent).ok()),
+            record.get(1).and_then(|label| kg.parse_literal(label)),
+        ) else {
+            continue;
+        };
+        type_labels.insert(ent, label.to_string());
+    }
+    pbar.finish_and_clear();
+    Ok(type_labels)
+}
+
+/// Fetches the labels of types from the --type-label-endpoint. Fails if
+/// built without the fetch feature.
+fn fetch_type_labels(
+    kg: &KnowledgeGraphProcessor,
+    args: &EntitiesArgs,
+    type_ids: &[&str],
+) -> anyhow::Result<HashMap<String, String>> {
+    #[cfg(feature = "fetch")]
+    {
+        use anyhow::Context;
+
+        let endpoint = args
+            .type_label_endpoint
+            .as_deref()
+            .unwrap_or(crate::fetch::default_endpoint(&kg.kg));
+        let pbar = progress_bar(
+            "fetching type labels",
+            type_ids.len() as u64,
+            !args.io.progress,
+        );
+        let labels = crate::fetch::fetch_labels(
+            kg,
+            endpoint,
+            type_ids,
+            TYPE_LABEL_BATCH_SIZE,
+            args.type_label_requests_per_second,
+            3,
+            &pbar,
+        )
+        .context("failed to fetch type labels")?;
+        pbar.finish_and_clear();
+        Ok(labels)
+    }
+    #[cfg(not(feature = "fetch"))]
+    {
+        let _ = (kg, args, type_ids);
+        bail!("fetching type labels requires the fetch feature");
+    }
+}
+
+/// Finds entities with the same label as a more popular entity and the same
+/// description up to case, punctuation, and one character, which are mostly
+/// duplicates. Returns every duplicate with the entity it duplicates, its
+/// label, and its description. Entities without description are skipped.
+fn find_duplicates<'a>(
+    ent_infos: &HashMap<&'a str, EntityInfo<'a>>,
+) -> Vec<(&'a str, &'a str, &'a str, &'a str)> {
+    let mut label_to_ents: HashMap<_, Vec<_>> = HashMap::new();
+    for (&ent, info) in ent_infos {
+        if !info.desc.is_empty() {
+            label_to_ents.entry(info.label).or_default().push(ent);
+        }
+    }
+    let mut duplicates = vec![];
+    for (label, mut ents) in label_to_ents.into_iter().sorted_by_key(|&(label, _)| label) {
+        if ents.len() < 2 {
+            continue;
+        }
+        ents.sort_by_key(|&ent| (Reverse(ent_infos[ent].count), ent));
+        // the most popular entity of a group comes first
+        let mut groups: Vec<(String, &str)> = vec![];
+        for ent in ents {
+            let desc = ent_infos[ent].desc;
+            let normalized = normalize_label(desc).to_lowercase();
+            match groups.iter().find(|(group_desc, _)| {
+                *group_desc == normalized || within_edit_distance_one(group_desc, &normalized)
+            }) {
+                Some(&(_, first)) => duplicates.push((ent, first, label, desc)),
+                None => groups.push((normalized, ent)),
+            }
+        }
+    }
+    duplicates
+}
+
+/// Expected columns of delta entity files: the kind of change followed by
+/// the columns of entity files.
+const DELTA_COLUMNS: [&str; 7] = [
+    "change",
+    "ent",
+    "ent_name",
+    "ent_description",
+    "links",
+    "types",
+    "aliases",
+];
+
+/// An entity read from the entity file, or only the label and count of one
+/// left out by its types.
+enum Parsed<'a> {
+    Entity(EntityInfo<'a>),
+    TypeFiltered(&'a str, usize),
+}
+
+/// An entity of a streaming reader, or only the id, label, and count of one
+/// left out by its types.
+enum Streamed {
+    Entity(OwnedEntity),
+    TypeFiltered(String, Arc<str>, usize),
+}
+
+/// Takes the entities of a streaming reader one at a time and keeps only
+/// what the index needs: excluded entities are dropped and the ones left out
+/// by their types reduced to their label as soon as they are read, so the
+/// output of the reader is never held in full. Returns the kept entities in
+/// reading order and the number of excluded ones.
+fn take_streamed(
+    entities: impl Iterator<Item = anyhow::Result<OwnedEntity>>,
+    is_excluded: impl Fn(&str) -> bool,
+    is_type_filtered: impl Fn(&str, &EntityInfo) -> bool,
+) -> anyhow::Result<(Vec<Streamed>, usize)> {
+    let mut streamed = vec![];
+    let mut num_excluded = 0;
+    for ent in entities {
+        let ent = ent?;
+        if is_excluded(&ent.id) {
+            num_excluded += 1;
+        } else if is_type_filtered(&ent.id, &ent.info()) {
+            let OwnedEntity {
+                id, label, count, ..
+            } = ent;
+            streamed.push(Streamed::TypeFiltered(id, label, count));
+        } else {
+            streamed.push(Streamed::Entity(ent));
+        }
+    }
+    Ok((streamed, num_excluded))
+}
+
+/// Changes of a delta entity file applied to the merged entities.
+#[derive(Default)]
+struct DeltaChanges {
+    num_added: usize,
+    num_changed: usize,
+    num_deleted: usize,
+    /// the added, changed, and deleted entities
+    entities: HashSet<String>,
+    /// labels, aliases, and redirect labels of these entities before and
+    /// after the delta
+    forms: HashSet<String>,
+}
+
+/// Applies a delta entity file to merged entities: deleted entities are
+/// removed, and added or changed entities replace their record with their
+/// types resolved to labels (also with --type-labels), keeping its
+/// redirects. Returns the changes.
+fn apply_delta(
+    kg: &KnowledgeGraphProcessor,
+    path: &Path,
+    format: FileFormat,
+    args: &EntitiesArgs,
+    type_labels: &HashMap<EntityId, String>,
+    merged: &mut Vec<MergedEntity<'static>>,
+) -> anyhow::Result<DeltaChanges> {
+    let mut records = record_iter(path, format)?;
+    let Some(header) = records.next() else {
+        bail!("{} is empty", path.display());
+    };
+    let header = header?;
+    let header: Vec<_> = header.iter().collect();
+    let (columns, problems) = map_header_columns(&header, &DELTA_COLUMNS);
+    report_header_problems(path, &problems, args.build.strict)?;
+    if columns[0].is_none() || columns[1].is_none() {
+        bail!("delta file needs a change and an id column");
+    }
+    let records: Vec<_> = records.collect::<anyhow::Result<_>>()?;
+    let mut deleted = HashSet::new();
+    let mut updated = vec![];
+    for record in &records {
+        let fields = select_columns(record, &columns);
+        let Some(ent) = kg.parse_entity_id(fields[1]) else {
+            bail!("failed to capture entity in {}", fields[1]);
+        };
+        match fields[0].trim() {
+            "deleted" => {
+                deleted.insert(ent);
+            }
+            "added" | "changed" => {
+                let (_, info) = kg.parse_entity_fields(&fields[1..], args.ignore_types)?;
+                updated.push((ent, info));
+            }
+            change => {
+                bail!("unknown change {change} of {ent}, expected added, changed, or deleted")
+            }
+        }
+    }
+
+    // the previous records of the entities, and the label and count of
+    // every entity for the types of the updated ones
+    let previous: HashMap<&str, &MergedEntity> = merged
+        .iter()
+        .filter(|value| value.label.is_some())
+        .map(|value| (&*value.id, value))
+        .collect();
+    let labels: HashMap<&str, (&str, usize)> = type_labels
+        .iter()
+        .map(|(type_id, label)| (type_id.id(), (label.as_str(), 0)))
+        .chain(
+            previous
+                .iter()
+                .filter_map(|(&ent, value)| Some((ent, (value.label.as_deref()?, value.count)))),
+        )
+        .chain(
+            updated
+                .iter()
+                .map(|(ent, info)| (*ent, (info.label, info.count))),
+        )
+        .collect();
+    let mut class_ids = HashSet::new();
+    let mut num_changed = 0;
+    let mut updated_records = vec![];
+    for (ent, info) in &updated {
+        let mut types = info.types.lock().unwrap().clone();
+        types.retain(|type_id| labels.contains_key(type_id));
+        types.sort_by_key(|type_id| labels[type_id].1);
+        class_ids.extend(types.iter().map(|type_id| type_id.to_string()));
+        let previous = previous.get(ent);
+        num_changed += previous.is_some() as usize;
+        let owned = |values: &[&str]| values.iter().map(|v| Cow::Owned(v.to_string())).collect();
+        updated_records.push(MergedEntity {
+            id: Cow::Owned(ent.to_string()),
+            label: Some(Cow::Owned(info.label.to_string())),
+            description: Cow::Owned(info.desc.to_string()),
+            count: info.count,
+            types: owned(
+                &types
+                    .iter()
+                    .map(|type_id| labels[type_id].0)
+                    .collect::<Vec<_>>(),
+            ),
+            aliases: owned(&info.aliases),
+            redirects: previous.and_then(|value| value.redirects.clone()),
+            redirect_labels: previous.and_then(|value| value.redirect_labels.clone()),
+            class: previous.is_some_and(|value| value.class),
+        });
+    }
+    let num_deleted = deleted
+        .iter()
+        .filter(|&ent| previous.contains_key(ent))
+        .count();
+
+    // redirect labels of entities of other shards stay
+    let replaced: HashSet<_> = updated.iter().map(|&(ent, _)| ent).chain(deleted).collect();
+    let mut forms = HashSet::new();
+    for value in replaced.iter().filter_map(|ent| previous.get(ent)) {
+        forms.extend(value.label.iter().map(|label| label.to_string()));
+        forms.extend(value.aliases.iter().map(|alias| alias.to_string()));
+        forms.extend(
+            value
+                .redirect_labels
+                .iter()
+                .flatten()
+                .map(|l| l.to_string()),
+        );
+    }
+    for (_, info) in &updated {
+        forms.insert(info.label.to_string());
+        forms.extend(info.aliases.iter().map(|alias| alias.to_string()));
+    }
+    let entities = replaced.iter().map(|ent| ent.to_string()).collect();
+    let replaced: HashSet<String> = entities;
+    merged.retain(|value| value.label.is_none() || !replaced.contains(&*value.id));
+    for value in merged.iter_mut().chain(&mut updated_records) {
+        if class_ids.contains(&*value.id) {
+            value.class = true;
+        }
+    }
+    merged.extend(updated_records);
+    Ok(DeltaChanges {
+        num_added: updated.len() - num_changed,
+        num_changed,
+        num_deleted,
+        entities: replaced,
+        forms,
+    })
+}
+
+/// Groups the ids of pairs into equivalence classes with union-find, so
+/// chains like A = B and B = C end up in one class whatever the order of
+/// the pairs. Classes and their members are sorted.
+fn equivalence_classes(pairs: &[(String, String)]) -> Vec<Vec<&str>> {
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    let mut parents: Vec<usize> = vec![];
+    fn find(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            // path halving
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+    for (a, b) in pairs {
+        let [a, b] = [a, b].map(|id| {
+            *ids.entry(id.as_str()).or_insert_with(|| {
+                parents.push(parents.len());
+                parents.len() - 1
+            })
+        });
+        let (a, b) = (find(&mut parents, a), find(&mut parents, b));
+        parents[a.max(b)] = a.min(b);
+    }
+    let mut classes: HashMap<usize, Vec<&str>> = HashMap::new();
+    for (&id, &i) in &ids {
+        classes.entry(find(&mut parents, i)).or_default().push(id);
+    }
+    classes
+        .into_values()
+        .map(|mut class| {
+            class.sort();
+            class
+        })
+        .sorted()
+        .collect()
+}
+
+/// Loads pairs of entities said to be the same (two different entity ids
+/// per line), skipping the header and malformed lines.
+fn load_id_pairs(
+    kg: &KnowledgeGraphProcessor,
+    path: &Path,
+    format: FileFormat,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let mut pairs = vec![];
+    for record in record_iter(path, format)? {
+        let record = record?;
+        let (Some(a), Some(b)) = (
+            record.get(0).and_then(|ent| kg.parse_entity_id(ent)),
+            record.get(1).and_then(|ent| kg.parse_entity_id(ent)),
+        ) else {
+            continue;
+        };
+        if a != b {
+            pairs.push((a.to_string(), b.to_string()));
+        }
+    }
+    Ok(pairs)
+}
+
+/// Loads aliases (entity id and alias per line) into the extra aliases,
+/// returns the number of aliases loaded. Aliases may be English or
+/// language independent (@mul) literals.
+fn load_aliases(
+    kg: &KnowledgeGraphProcessor,
+    path: &Path,
+    format: FileFormat,
+    extra_aliases: &mut ExtraAliases,
+) -> anyhow::Result<usize> {
+    let mut num_aliases = 0;
+    for record in record_iter(path, format)? {
+        let record = record?;
+        // skips the header and malformed lines
+        let (Some(ent), Some(alias)) = (
+            record.get(0).and_then(|ent| kg.entity_id(ent).ok()),
+            record
+                .get(1)
+                .and_then(|alias| kg.parse_literal_in(alias, "mul")),
+        ) else {
+            continue;
+        };
+        extra_aliases
+            .aliases
+            .entry(ent)
+            .or_default()
+            .push(alias.to_string());
+        num_aliases += 1;
+    }
+    Ok(num_aliases)
+}
+
+/// Loads translated surface forms as aliases, returns the entity, alias, and
+/// language of every translation. The language is taken from the third
+/// column, or from the language tag of the surface form if it is missing.
+fn load_translations(
+    kg: &KnowledgeGraphProcessor,
+    path: &Path,
+    format: FileFormat,
+    extra_aliases: &mut ExtraAliases,
+) -> anyhow::Result<Vec<(EntityId, String, String)>> {
+    let mut translations = vec![];
+    for record in record_iter(path, format)? {
+        let record = record?;
+        // skips the header and malformed lines
+        let (Some(ent), Some(form)) = (
+            record.get(0).and_then(|ent| kg.entity_id(ent).ok()),
+            record.get(1),
+        ) else {
+            continue;
+        };
+        let lang = match record.get(2).map(str::trim) {
+            Some(lang) if !lang.is_empty() => lang,
+            _ => match form.trim().rsplit_once("\"@") {
+                Some((_, lang)) => lang,
+                None => continue,
+            },
+        };
+        let Some(alias) = kg.parse_literal_in(form.trim(), lang) else {
+            continue;
+        };
+        extra_aliases
+            .aliases
+            .entry(ent.clone())
+            .or_default()
+            .push(alias.to_string());
+        translations.push((ent, alias.to_string(), lang.to_string()));
+    }
+    Ok(translations)
+}
+
+/// Loads external ids of the given properties as aliases, returns the number
+/// of aliases loaded. Properties are matched by their local name, so P297,
+/// wdt:P297, and full iris all work.
+fn load_external_ids(
+    kg: &KnowledgeGraphProcessor,
+    path: &Path,
+    format: FileFormat,
+    properties: &[String],
+    extra_aliases: &mut ExtraAliases,
+) -> anyhow::Result<usize> {
+    let local_name = |s: &str| {
+        let s = s.trim().trim_start_matches('<').trim_end_matches('>');
+        s.rsplit(['/', ':', '#']).next().unwrap_or(s).to_string()
+    };
+    let properties: HashSet<_> = properties.iter().map(|p| local_name(p)).collect();
+    let mut num_ids = 0;
+    for record in record_iter(path, format)? {
+        let record = record?;
+        // skips the header and malformed lines
+        let (Some(ent), Some(prop), Some(value)) = (
+            record.get(0).and_then(|ent| kg.entity_id(ent).ok()),
+            record.get(1).map(local_name),
+            record.get(2),
+        ) else {
+            continue;
+        };
+        if !properties.contains(&prop) {
+            continue;
+        }
+        // external ids are plain or typed string literals
+        let value = value.split("\"^^").next().unwrap_or(value);
+        let value = value.trim().trim_matches('"').trim();
+        if value.is_empty() {
+            continue;
+        }
+        extra_aliases
+            .aliases
+            .entry(ent)
+            .or_default()
+            .push(value.to_string());
+        num_ids += 1;
+    }
+    Ok(num_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn streamed_entities_are_kept_or_dropped_as_they_are_read() {
+        // every entity is decided on right after it was read, so the reader
+        // is never collected first
+        let num_read = Cell::new(0);
+        let entities = (0..10).map(|i| {
+            num_read.set(num_read.get() + 1);
+            Ok(OwnedEntity {
+                id: format!("Q{i}"),
+                label: Arc::from(format!("entity {i}")),
+                desc: Arc::from(""),
+                aliases: vec![Arc::from("alias")],
+                types: vec![Arc::from(if i % 2 == 0 { "Q5" } else { "Q515" })],
+                count: i,
+            })
+        });
+        let num_decided = Cell::new(0);
+        let is_excluded = |ent: &str| {
+            num_decided.set(num_decided.get() + 1);
+            assert_eq!(num_read.get(), num_decided.get(), "{ent} was read ahead");
+            ent == "Q3"
+        };
+        let is_type_filtered = |_: &str, info: &EntityInfo| info.types.lock().unwrap()[0] == "Q5";
+        let (streamed, num_excluded) =
+            take_streamed(entities, is_excluded, is_type_filtered).unwrap();
+        assert_eq!(num_excluded, 1);
+        let kept: Vec<_> = streamed
+            .iter()
+            .map(|streamed| match streamed {
+                Streamed::Entity(ent) => (ent.id.as_str(), ent.aliases.len()),
+                Streamed::TypeFiltered(ent, ..) => (ent.as_str(), 0),
+            })
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("Q0", 0),
+                ("Q1", 1),
+                ("Q2", 0),
+                ("Q4", 0),
+                ("Q5", 1),
+                ("Q6", 0),
+                ("Q7", 1),
+                ("Q8", 0),
+                ("Q9", 1)
+            ]
+        );
+    }
+
+    fn entity<'a>(
+        label: &'a str,
+        aliases: &[&'a str],
+        types: &[&'a str],
+        count: usize,
+    ) -> EntityInfo<'a> {
+        EntityInfo {
+            label,
+            desc: "",
+            aliases: aliases.to_vec(),
+            types: Arc::new(Mutex::new(types.to_vec())),
+            count,
+            redirects: None,
+        }
+    }
+
+    #[test]
+    fn entities_are_filtered_by_id_and_type() {
+        let filter = EntityFilter {
+            include_ids: Some(HashSet::from(["Q1".to_string(), "Q2".to_string()])),
+            exclude_ids: HashSet::from(["Q3".to_string()]),
+            include_types: Some(HashSet::from(["Q5".to_string()])),
+            exclude_types: HashSet::from(["Q4167410".to_string()]),
+            filters_types: true,
+            redirect_targets: HashMap::from([("Q10", "Q1")]),
+            superclasses: None,
+        };
+        // redirect sources of included entities are kept for their labels
+        assert!(filter.is_included("Q1") && filter.is_included("Q10"));
+        assert!(!filter.is_included("Q3"));
+        assert!(filter.is_excluded("Q3") && !filter.is_excluded("Q1"));
+        assert!(!filter.is_type_filtered("Q1", &entity("human", &[], &["Q5"], 0)));
+        assert!(filter.is_type_filtered("Q2", &entity("city", &[], &["Q515"], 0)));
+        assert!(filter.is_type_filtered("Q2", &entity("page", &[], &["Q5", "Q4167410"], 0)));
+        assert!(!filter.is_type_filtered("Q10", &entity("city", &[], &["Q515"], 0)));
+    }
+
+    #[test]
+    fn confirmed_duplicates_are_merged_into_their_original() {
+        let mut ent_infos = HashMap::from([
+            ("Q1", entity("Berlin", &["capital"], &[], 10)),
+            ("Q2", entity("Berlin, Germany", &["Berlin"], &[], 50)),
+            ("Q3", entity("Berlin city", &[], &[], 5)),
+            ("Q4", entity("Paris", &[], &[], 1)),
+        ]);
+        // Q1 is kept although Q2 is more popular, since only Q2 and Q3 are
+        // duplicates
+        let pairs = [
+            ("Q3".to_string(), "Q1".to_string()),
+            ("Q2".to_string(), "Q1".to_string()),
+        ];
+        assert_eq!(merge_confirmed_duplicates(&mut ent_infos, &pairs), 2);
+        assert_eq!(ent_infos.keys().sorted().collect_vec(), [&"Q1", &"Q4"]);
+        assert_eq!(
+            ent_infos["Q1"].aliases,
+            ["capital", "Berlin, Germany", "Berlin city"]
+        );
+    }
+
+    #[test]
+    fn near_duplicate_aliases_collapse_into_the_more_frequent_form() {
+        let mut ent_infos = HashMap::from([
+            (
+                "Q7315",
+                entity(
+                    "Pyotr Ilyich Tchaikovsky",
+                    &["Tchaikovski", "Tchaikovsky", "Chaikovski"],
+                    &[],
+                    0,
+                ),
+            ),
+            ("Q1", entity("Tchaikovsky", &[], &[], 0)),
+            ("Q2", entity("Tom", &["Tim"], &[], 0)),
+        ]);
+        assert_eq!(collapse_near_duplicate_aliases(&mut ent_infos, 4), 1);
+        // Tchaikovsky is used twice, and Chaikovski is two edits away from it
+        assert_eq!(ent_infos["Q7315"].aliases, ["Tchaikovsky", "Chaikovski"]);
+        // too short to be collapsed
+        assert_eq!(ent_infos["Q2"].aliases, ["Tim"]);
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context};
+use itertools::Itertools;
+use rayon::prelude::*;
+
+use crate::progress::Progress;
+use crate::progress_bar;
+use crate::stats::{Stats, StatsFormat};
+use crate::{KnowledgeGraph, KnowledgeGraphProcessor};
+
+/// Queries the input files of the index binaries are downloaded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    Entities,
+    Properties,
+    Redirects,
+    UnitSymbols,
+    SameAs,
+    SubclassOf,
+}
+
+impl TryFrom<&str> for Query {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "entities" => Query::Entities,
+            "properties" => Query::Properties,
+            "redirects" => Query::Redirects,
+            "unit-symbols" => Query::UnitSymbols,
+            "same-as" => Query::SameAs,
+            "subclass-of" => Query::SubclassOf,
+            _ => return Err(anyhow!("invalid query {}", value)),
+        })
+    }
+}
+
+/// The query for a knowledge graph from the queries directory, which the
+/// Makefile downloads with as well.
+pub fn builtin_query(kg: &KnowledgeGraph, query: Query) -> Option<&'static str> {
+    use KnowledgeGraph::*;
+    use Query::*;
+    Some(match (kg, query) {
+        (Wikidata, Entities) => include_str!("../queries/wikidata-entities.sparql"),
+        (Wikidata, Properties) => include_str!("../queries/wikidata-properties.sparql"),
+        (Wikidata, Redirects) => include_str!("../queries/wikidata-redirects.sparql"),
+        (Wikidata, UnitSymbols) => include_str!("../queries/wikidata-unit-symbols.sparql"),
+        (Wikidata, SameAs) => include_str!("../queries/wikidata-same-as.sparql"),
+        (Wikidata, SubclassOf) => include_str!("../queries/wikidata-subclass-of.sparql"),
+        (Freebase, Entities) => include_str!("../queries/freebase-entities.sparql"),
+        (Freebase, Properties) => include_str!("../queries/freebase-properties.sparql"),
+        (DBPedia, Entities) => include_str!("../queries/dbpedia-entities.sparql"),
+        (DBPedia, Properties) => include_str!("../queries/dbpedia-properties.sparql"),
+        (DBPedia, Redirects) => include_str!("../queries/dbpedia-redirects.sparql"),
+        _ => return None,
+    })
+}
+
+/// The QLever endpoint of a knowledge graph, as in the Makefile.
+pub fn default_endpoint(kg: &KnowledgeGraph) -> &'static str {
+    match kg {
+        KnowledgeGraph::Wikidata => "https://qlever.cs.uni-freiburg.de/api/wikidata",
+        KnowledgeGraph::Freebase => "https://qlever.cs.uni-freiburg.de/api/freebase",
+        KnowledgeGraph::DBPedia => "https://qlever.cs.uni-freiburg.de/api/dbpedia",
+    }
+}
+
+/// Arguments of kg-fetch, see [`run`].
+#[derive(clap::Args, Debug)]
+#[clap(about = None, long_about = None)]
+pub struct FetchArgs {
+    /// knowledge graph to query (wikidata, freebase, or dbpedia)
+    #[clap(short, long)]
+    knowledge_base: String,
+
+    /// query to run (entities, properties, redirects, unit-symbols, same-as,
+    /// or subclass-of)
+    #[clap(short, long, required_unless_present = "query_file")]
+    query: Option<String>,
+
+    /// file with a custom query to run instead
+    #[clap(long, conflicts_with = "query")]
+    query_file: Option<PathBuf>,
+
+    /// where to write the results as TSV
+    #[clap(short, long)]
+    output: PathBuf,
+
+    /// SPARQL endpoint, defaults to the QLever endpoint of the knowledge
+    /// graph
+    #[clap(long)]
+    endpoint: Option<String>,
+
+    /// access token sent to the endpoint (QLever)
+    #[clap(long)]
+    access_token: Option<String>,
+
+    /// query timeout sent to the endpoint, e.g. 1h (QLever)
+    #[clap(long)]
+    timeout: Option<String>,
+
+    /// fetch the results in pages of this many rows with LIMIT and OFFSET
+    /// instead of at once; the query should have an ORDER BY, so the pages
+    /// do not overlap
+    #[clap(long)]
+    page_size: Option<usize>,
+
+    /// how often to retry a failed request, waiting 1s, 2s, 4s, and so on
+    /// in between
+    #[clap(long, default_value = "3")]
+    retries: usize,
+
+    /// show progress bars
+    #[clap(short, long)]
+    progress: bool,
+
+    /// format of the statistics printed at the end (text or json)
+    #[clap(long, default_value = "text")]
+    stats_format: String,
+}
+
+impl FetchArgs {
+    pub fn stats_format(&self) -> anyhow::Result<StatsFormat> {
+        StatsFormat::try_from(self.stats_format.as_str())
+    }
+}
+
+/// Whether a failed request may succeed when sent again: connection
+/// problems, rate limits, and server errors.
+fn is_retryable(err: &anyhow::Error) -> bool {
+    if let Some(err) = err.downcast_ref::<ureq::Error>() {
+        match err {
+            ureq::Error::Status(code, _) => *code == 429 || *code >= 500,
+            ureq::Error::Transport(_) => true,
+        }
+    } else {
+        err.downcast_ref::<std::io::Error>().is_some()
+    }
+}
+
+/// Runs a request until it succeeds, retrying failures that may be
+/// temporary up to the given number of times with exponentially growing
+/// waits (1s, 2s, 4s, ...).
+fn with_retries<T>(
+    retries: usize,
+    mut request: impl FnMut() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match request() {
+            Err(err) if attempt < retries && is_retryable(&err) => {
+                let delay = 1 << attempt;
+                eprintln!("request failed, retrying in {delay}s: {err}");
+                thread::sleep(Duration::from_secs(delay));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Limits the rate of requests sent from several threads.
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Fails unless the number of requests per second is positive and finite.
+    pub fn new(requests_per_second: f64) -> anyhow::Result<Self> {
+        if !(requests_per_second.is_finite() && requests_per_second > 0.0) {
+            bail!("requests per second must be positive, got {requests_per_second}");
+        }
+        Ok(Self {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            next: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Blocks until the next request may be sent.
+    pub fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        thread::sleep(slot.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Predicate of the labels of a knowledge graph.
+fn label_predicate(kg: &KnowledgeGraph) -> &'static str {
+    match kg {
+        KnowledgeGraph::Freebase => "<http://rdf.freebase.com/ns/type.object.name>",
+        _ => "<http://www.w3.org/2000/01/rdf-schema#label>",
+    }
+}
+
+/// Fetches the English labels of entities from a SPARQL endpoint, with
+/// batches of ids per query sent in parallel, but at most the given number
+/// of requests per second. Entities without English label are left out.
+pub fn fetch_labels(
+    kg: &KnowledgeGraphProcessor,
+    endpoint: &str,
+    ids: &[&str],
+    batch_size: usize,
+    requests_per_second: f64,
+    retries: usize,
+    pbar: &Progress,
+) -> anyhow::Result<HashMap<String, String>> {
+    let limiter = RateLimiter::new(requests_per_second)?;
+    let batches: Vec<_> = ids
+        .par_chunks(batch_size)
+        .map(|batch| {
+            let values = batch
+                .iter()
+                .map(|id| format!("<{}>", kg.format_entity(id, false)))
+                .join(" ");
+            let query = format!(
+                "SELECT ?ent ?label WHERE {{ VALUES ?ent {{ {values} }} ?ent {} ?label . \
+                FILTER(LANG(?label) = \"en\") }}",
+                label_predicate(&kg.kg)
+            );
+            let labels = with_retries(retries, || {
+                limiter.wait();
+                let response = ureq::post(endpoint)
+                    .set("Accept", "text/tab-separated-values")
+                    .send_form(&[("query", &query)])?;
+                let mut labels = vec![];
+                // skips the header
+                for line in BufReader::new(response.into_reader()).lines().skip(1) {
+                    let line = line?;
+                    let Some((ent, label)) = line.split_once('\t') else {
+                        continue;
+                    };
+                    let (Some(ent), Some(label)) =
+                        (kg.parse_entity_id(ent), kg.parse_literal(label))
+                    else {
+                        continue;
+                    };
+                    labels.push((ent.to_string(), label.to_string()));
+                }
+                Ok(labels)
+            });
+            pbar.inc(batch.len() as u64);
+            labels
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(batches.into_iter().flatten().collect())
+}
+
+/// Runs a query and writes its result rows, with the header only if asked
+/// to. Returns the number of rows.
+fn fetch_page(
+    args: &FetchArgs,
+    endpoint: &str,
+    query: &str,
+    output: &mut File,
+    write_header: bool,
+) -> anyhow::Result<usize> {
+    let mut params = vec![("query", query)];
+    if let Some(token) = &args.access_token {
+        params.push(("access-token", token));
+    }
+    if let Some(timeout) = &args.timeout {
+        params.push(("timeout", timeout));
+    }
+    let response = ureq::post(endpoint)
+        .set("Accept", "text/tab-separated-values")
+        .send_form(&params)?;
+    let mut writer = BufWriter::new(output);
+    let mut num_rows = 0;
+    for (i, line) in BufReader::new(response.into_reader()).lines().enumerate() {
+        let line = line?;
+        if i == 0 && !write_header {
+            continue;
+        } else if i > 0 {
+            num_rows += 1;
+        }
+        writeln!(writer, "{line}")?;
+    }
+    writer.flush()?;
+    Ok(num_rows)
+}
+
+/// Runs a query against a SPARQL endpoint and writes the results as TSV,
+/// so they can be passed to the index binaries. Failed requests are
+/// retried, and a page is written again from its start if its response
+/// breaks off.
+pub fn run(args: FetchArgs) -> anyhow::Result<Stats> {
+    let kg = KnowledgeGraph::try_from(args.knowledge_base.as_str())?;
+    let query = if let Some(path) = &args.query_file {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read query file {}", path.display()))?
+    } else {
+        let name = args.query.as_deref().unwrap_or_default();
+        let Some(query) = builtin_query(&kg, Query::try_from(name)?) else {
+            bail!("there is no {name} query for {}", args.knowledge_base);
+        };
+        query.to_string()
+    };
+    let query = query.trim();
+    let endpoint = args.endpoint.as_deref().unwrap_or(default_endpoint(&kg));
+    if args.page_size == Some(0) {
+        bail!("page size must be positive");
+    }
+
+    let mut output = File::create(&args.output)?;
+    let pbar = progress_bar(
+        &format!("fetching from {endpoint}"),
+        u64::MAX,
+        !args.progress,
+    );
+    let mut num_rows = 0;
+    let mut num_pages = 0;
+    let mut num_retries = 0;
+    loop {
+        let page_query = match args.page_size {
+            Some(size) => format!("{query} LIMIT {size} OFFSET {}", num_pages * size),
+            None => query.to_string(),
+        };
+        let start = output.stream_position()?;
+        let mut attempts = 0;
+        let rows = with_retries(args.retries, || {
+            attempts += 1;
+            output.set_len(start)?;
+            output.seek(SeekFrom::Start(start))?;
+            fetch_page(&args, endpoint, &page_query, &mut output, num_pages == 0)
+        })
+        .with_context(|| format!("failed to fetch page {}", num_pages + 1))?;
+        // only finished pages count, rows of failed attempts are written again
+        pbar.inc(rows as u64);
+        num_retries += attempts - 1;
+        num_rows += rows;
+        num_pages += 1;
+        // a short page is the last one
+        if args.page_size.is_none_or(|size| rows < size) {
+            break;
+        }
+    }
+    pbar.finish_and_clear();
+
+    let mut stats = Stats::new(format!("{} fetch", args.knowledge_base));
+    stats.count("rows", num_rows);
+    stats.count("pages", num_pages);
+    stats.count("retries", num_retries);
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limiter_needs_positive_rate() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(RateLimiter::new(rate).is_err());
+        }
+        let limiter = RateLimiter::new(4.0).unwrap();
+        assert_eq!(limiter.interval, Duration::from_millis(250));
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The subclass of relation between classes (e.g. Wikidata P279), to find
+/// the ancestors and descendants of a class. Cycles, which occur in
+/// Wikidata, are tolerated: every class is visited once.
+#[derive(Debug, Clone, Default)]
+pub struct TypeHierarchy {
+    parents: HashMap<String, Vec<String>>,
+    children: HashMap<String, Vec<String>>,
+}
+
+impl TypeHierarchy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds that a class is a direct subclass of another one.
+    pub fn add(&mut self, class: &str, superclass: &str) {
+        if class == superclass {
+            return;
+        }
+        let parents = self.parents.entry(class.to_string()).or_default();
+        if parents.iter().any(|parent| parent == superclass) {
+            return;
+        }
+        parents.push(superclass.to_string());
+        self.children
+            .entry(superclass.to_string())
+            .or_default()
+            .push(class.to_string());
+    }
+
+    /// Number of classes with a superclass.
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    /// The direct superclasses of a class.
+    pub fn parents(&self, class: &str) -> &[String] {
+        self.parents.get(class).map_or(&[], Vec::as_slice)
+    }
+
+    /// The direct subclasses of a class.
+    pub fn children(&self, class: &str) -> &[String] {
+        self.children.get(class).map_or(&[], Vec::as_slice)
+    }
+
+    fn closure<'h>(&'h self, class: &str, edges: &'h HashMap<String, Vec<String>>) -> Vec<&'h str> {
+        let mut seen = HashSet::from([class]);
+        let mut queue: VecDeque<&str> = VecDeque::from([class]);
+        let mut closure = vec![];
+        while let Some(class) = queue.pop_front() {
+            for next in edges.get(class).into_iter().flatten() {
+                if seen.insert(next) {
+                    closure.push(next.as_str());
+                    queue.push_back(next);
+                }
+            }
+        }
+        closure
+    }
+
+    /// All superclasses of a class, nearest first.
+    pub fn ancestors(&self, class: &str) -> Vec<&str> {
+        self.closure(class, &self.parents)
+    }
+
+    /// All subclasses of a class, nearest first.
+    pub fn descendants(&self, class: &str) -> Vec<&str> {
+        self.closure(class, &self.children)
+    }
+
+    /// Whether a class is a (transitive) superclass of another one.
+    pub fn is_ancestor(&self, ancestor: &str, class: &str) -> bool {
+        self.ancestors(class).contains(&ancestor)
+    }
+
+    /// The given classes without those that are a superclass of another
+    /// one of them, e.g. city but not human settlement. The order is kept.
+    pub fn most_specific<'t>(&self, classes: &[&'t str]) -> Vec<&'t str> {
+        let ancestors: HashSet<_> = classes
+            .iter()
+            .flat_map(|class| self.ancestors(class))
+            .collect();
+        classes
+            .iter()
+            .copied()
+            .filter(|class| !ancestors.contains(class))
+            .collect()
+    }
+
+    /// The nearest superclass of a class for which `useful` holds, ties
+    /// between classes at the same distance go to the first parent.
+    pub fn nearest_ancestor(&self, class: &str, useful: impl Fn(&str) -> bool) -> Option<&str> {
+        self.ancestors(class)
+            .into_iter()
+            .find(|&ancestor| useful(ancestor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_hierarchy_closures() {
+        let mut hierarchy = TypeHierarchy::new();
+        hierarchy.add("big city", "city");
+        hierarchy.add("city", "human settlement");
+        hierarchy.add("human settlement", "geographic location");
+        // cycles are tolerated
+        hierarchy.add("geographic location", "city");
+        hierarchy.add("city", "city");
+        assert_eq!(hierarchy.len(), 4);
+        assert_eq!(
+            hierarchy.ancestors("big city"),
+            ["city", "human settlement", "geographic location"]
+        );
+        assert_eq!(hierarchy.descendants("human settlement").len(), 3);
+        assert!(hierarchy.is_ancestor("human settlement", "big city"));
+        assert!(!hierarchy.is_ancestor("big city", "city"));
+        assert_eq!(
+            hierarchy.most_specific(&["human settlement", "big city", "person"]),
+            ["big city", "person"]
+        );
+        assert_eq!(
+            hierarchy.nearest_ancestor("big city", |class| class.ends_with("settlement")),
+            Some("human settlement")
+        );
+        assert_eq!(hierarchy.nearest_ancestor("person", |_| true), None);
+    }
+}
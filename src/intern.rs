@@ -0,0 +1,216 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::bail;
+
+use crate::{format_surface_form, parse_surface_form, SurfaceFormEscaping};
+
+/// Shares one allocation between identical strings, e.g. aliases like
+/// "album" or descriptions like "scholarly article" that repeat across
+/// millions of entities.
+#[derive(Debug, Default)]
+pub struct StringInterner {
+    strings: HashSet<Arc<str>>,
+    pub num_interned: usize,
+    pub num_bytes: usize,
+}
+
+impl StringInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The shared copy of a string, which is added if it is new.
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        self.num_interned += 1;
+        self.num_bytes += s.len();
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(s);
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    /// Number of distinct strings.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Number of bytes not allocated because a string was already interned.
+    pub fn saved_bytes(&self) -> usize {
+        self.num_bytes - self.strings.iter().map(|s| s.len()).sum::<usize>()
+    }
+}
+
+/// Magic bytes at the start of a dictionary-coded index.
+const DICTIONARY_MAGIC: &[u8; 8] = b"KGDICT1\n";
+
+fn write_u32(writer: &mut impl Write, value: usize) -> anyhow::Result<()> {
+    let Ok(value) = u32::try_from(value) else {
+        bail!("{value} does not fit into a dictionary-coded index");
+    };
+    writer.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> anyhow::Result<usize> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf) as usize)
+}
+
+/// Writes index rows (an entity and its surface forms) dictionary-coded:
+/// every distinct id, label, and info is stored once, and the rows refer to
+/// them by number. Surface forms are split into label and info as with
+/// [`parse_surface_form`], so labels shared by several entities and infos
+/// like "human" are only stored once. Returns the number of distinct strings.
+///
+/// The file starts with `KGDICT1\n`, followed by the number of strings and
+/// every string as its length and UTF-8 bytes, then the number of rows and
+/// every row as its entity, its number of surface forms, and every surface
+/// form as its label and its info plus one (0 if it has none). All numbers
+/// are little-endian u32.
+pub fn write_dictionary_index<'r, F: IntoIterator<Item = &'r str>>(
+    file: impl AsRef<Path>,
+    rows: impl IntoIterator<Item = (&'r str, F)>,
+    escaping: SurfaceFormEscaping,
+) -> anyhow::Result<usize> {
+    let mut strings: Vec<String> = vec![];
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    let mut id = |s: String| {
+        *ids.entry(s).or_insert_with_key(|s| {
+            strings.push(s.clone());
+            strings.len() - 1
+        })
+    };
+    let mut coded_rows = vec![];
+    for (ent, forms) in rows {
+        let ent = id(ent.to_string());
+        let forms: Vec<_> = forms
+            .into_iter()
+            .map(|form| {
+                let (label, info) = parse_surface_form(form, escaping);
+                (id(label), info.map_or(0, |info| id(info) + 1))
+            })
+            .collect();
+        coded_rows.push((ent, forms));
+    }
+
+    let mut writer = BufWriter::new(fs::File::create(file)?);
+    writer.write_all(DICTIONARY_MAGIC)?;
+    write_u32(&mut writer, strings.len())?;
+    for s in &strings {
+        write_u32(&mut writer, s.len())?;
+        writer.write_all(s.as_bytes())?;
+    }
+    write_u32(&mut writer, coded_rows.len())?;
+    for (ent, forms) in coded_rows {
+        write_u32(&mut writer, ent)?;
+        write_u32(&mut writer, forms.len())?;
+        for (label, info) in forms {
+            write_u32(&mut writer, label)?;
+            write_u32(&mut writer, info)?;
+        }
+    }
+    writer.flush()?;
+    Ok(strings.len())
+}
+
+/// Reads a dictionary-coded index written with [`write_dictionary_index`]
+/// back into its rows, with the surface forms formatted like in the TSV
+/// index.
+pub fn read_dictionary_index(
+    file: impl AsRef<Path>,
+    escaping: SurfaceFormEscaping,
+) -> anyhow::Result<Vec<(String, Vec<String>)>> {
+    let file = file.as_ref();
+    let mut reader = BufReader::new(fs::File::open(file)?);
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != DICTIONARY_MAGIC {
+        bail!("{} is no dictionary-coded index", file.display());
+    }
+    let num_strings = read_u32(&mut reader)?;
+    let mut strings = Vec::with_capacity(num_strings);
+    for _ in 0..num_strings {
+        let mut buf = vec![0; read_u32(&mut reader)?];
+        reader.read_exact(&mut buf)?;
+        strings.push(String::from_utf8(buf)?);
+    }
+    let string = |i: usize| match strings.get(i) {
+        Some(s) => Ok(s.as_str()),
+        None => bail!("invalid string {i} in {}", file.display()),
+    };
+    let num_rows = read_u32(&mut reader)?;
+    let mut rows = Vec::with_capacity(num_rows);
+    for _ in 0..num_rows {
+        let ent = string(read_u32(&mut reader)?)?.to_string();
+        let num_forms = read_u32(&mut reader)?;
+        let mut forms = Vec::with_capacity(num_forms);
+        for _ in 0..num_forms {
+            let label = string(read_u32(&mut reader)?)?;
+            let info = match read_u32(&mut reader)? {
+                0 => None,
+                i => Some(string(i - 1)?),
+            };
+            forms.push(format_surface_form(label, info, escaping));
+        }
+        rows.push((ent, forms));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_strings_share_allocations() {
+        let mut interner = StringInterner::new();
+        let a = interner.intern("album");
+        let b = interner.intern(&String::from("album"));
+        let c = interner.intern("single");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.num_interned, 3);
+        assert_eq!(interner.saved_bytes(), 5);
+    }
+
+    #[test]
+    fn dictionary_index_round_trips() {
+        let rows = [
+            (
+                "Q90",
+                vec!["Paris".to_string(), "City of Light".to_string()],
+            ),
+            ("Q167646", vec!["Paris (son of Priam)".to_string()]),
+            ("Q1", vec!["Up (film) (2009 film)".to_string()]),
+        ];
+        let file = std::env::temp_dir().join(format!("dictionary-{}.bin", std::process::id()));
+        let num_strings = write_dictionary_index(
+            &file,
+            rows.iter()
+                .map(|(ent, forms)| (*ent, forms.iter().map(String::as_str))),
+            SurfaceFormEscaping::None,
+        )
+        .unwrap();
+        let read = read_dictionary_index(&file, SurfaceFormEscaping::None).unwrap();
+        fs::remove_file(&file).unwrap();
+        // "Paris" is stored once for both entities
+        assert_eq!(num_strings, 8);
+        let expected: Vec<_> = rows
+            .iter()
+            .map(|(ent, forms)| (ent.to_string(), forms.clone()))
+            .collect();
+        assert_eq!(read, expected);
+    }
+}
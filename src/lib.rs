@@ -1,21 +1,54 @@
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::fmt::Display;
-use std::io::BufRead;
+use std::io::{BufRead, Read};
+use std::ops::AddAssign;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 use std::sync::{Arc, Mutex};
 use std::{fs, io::BufReader};
 
 use anyhow::{anyhow, bail};
-
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use itertools::Itertools;
+use rayon::prelude::*;
 use regex::Regex;
 
+pub mod batch;
+pub mod cache;
+pub mod candidates;
+pub mod cli;
+pub mod entities;
+#[cfg(feature = "fetch")]
+pub mod fetch;
+pub mod hierarchy;
+pub mod intern;
+pub mod literals;
+pub mod mapping;
+pub mod normalize;
+pub mod output;
+pub mod progress;
+pub mod properties;
+#[cfg(feature = "python")]
+mod python;
+pub mod redirects;
+pub mod stats;
+pub mod summary;
+pub mod wikidata_dump;
+
+use intern::StringInterner;
+use output::OutputDir;
+use progress::{Progress, SharedListener};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd)]
 pub enum Ent<'a> {
     Label(&'a str),
     LabelInfo(&'a str),
     Alias(&'a str),
     AliasInfo(&'a str),
+    /// surface form taken from an entity redirecting to this one
+    Redirect(&'a str),
 }
 
 impl<'s> Ent<'s> {
@@ -23,25 +56,60 @@ impl<'s> Ent<'s> {
         match self {
             Ent::Label(s) | Ent::LabelInfo(s) => s,
             Ent::Alias(s) | Ent::AliasInfo(s) => s,
+            Ent::Redirect(s) => s,
+        }
+    }
+
+    /// The kind of surface form, without the entity.
+    pub fn kind(&self) -> FormKind {
+        match self {
+            Ent::Label(_) => FormKind::Label,
+            Ent::LabelInfo(_) => FormKind::LabelInfo,
+            Ent::Alias(_) => FormKind::Alias,
+            Ent::AliasInfo(_) => FormKind::AliasInfo,
+            Ent::Redirect(_) => FormKind::Redirect,
+        }
+    }
+
+    /// Where the surface form comes from: label, alias, or redirect label.
+    pub fn source(&self) -> &'static str {
+        match self {
+            Ent::Label(_) | Ent::LabelInfo(_) => "label",
+            Ent::Alias(_) | Ent::AliasInfo(_) => "alias",
+            Ent::Redirect(_) => "redirect label",
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Ent::Label(_) => 0,
+            Ent::LabelInfo(_) => 1,
+            Ent::Alias(_) => 2,
+            Ent::AliasInfo(_) => 3,
+            Ent::Redirect(_) => 4,
         }
     }
 }
 
 impl Ord for Ent<'_> {
     fn cmp(&self, other: &Self) -> Ordering {
-        match (self, other) {
-            (Ent::Label(_), Ent::LabelInfo(_) | Ent::Alias(_) | Ent::AliasInfo(_)) => {
-                Ordering::Less
-            }
-            (Ent::LabelInfo(_), Ent::Alias(_) | Ent::AliasInfo(_)) => Ordering::Less,
-            (Ent::Alias(_), Ent::AliasInfo(_)) => Ordering::Less,
-            (Ent::AliasInfo(_), Ent::Label(_) | Ent::LabelInfo(_) | Ent::Alias(_)) => {
-                Ordering::Greater
-            }
-            (Ent::Alias(_), Ent::Label(_) | Ent::LabelInfo(_)) => Ordering::Greater,
-            (Ent::LabelInfo(_), Ent::Label(_)) => Ordering::Greater,
-            _ => Ordering::Equal,
-        }
+        self.rank().cmp(&other.rank())
+    }
+}
+
+/// Kind of a surface form in a row of the index, see [`Ent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FormKind {
+    Label,
+    LabelInfo,
+    Alias,
+    AliasInfo,
+    Redirect,
+}
+
+impl FormKind {
+    pub fn is_label(&self) -> bool {
+        matches!(self, FormKind::Label | FormKind::LabelInfo)
     }
 }
 
@@ -61,7 +129,7 @@ pub struct EntityInfo<'a> {
 }
 
 impl<'a> EntityInfo<'a> {
-    pub fn info(&self) -> &str {
+    pub fn info(&self) -> &'a str {
         self.types
             .lock()
             .unwrap()
@@ -71,19 +139,184 @@ impl<'a> EntityInfo<'a> {
     }
 }
 
+/// Id of an entity, validated against the entity patterns of its knowledge
+/// graph when created with [`KnowledgeGraphProcessor::entity_id`]. It
+/// compares, hashes, and borrows as the id returned by
+/// [`KnowledgeGraphProcessor::parse_entity_id`] (e.g. `Q42`), so maps keyed
+/// by entity ids can be queried with a parsed `&str`.
+#[derive(Debug, Clone)]
+pub struct EntityId {
+    id: String,
+    iri: String,
+    prefixed: String,
+    numeric: Option<u64>,
+}
+
+impl EntityId {
+    /// The id as parsed from an entity iri, e.g. `Q42`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The number of a Wikidata id, e.g. 42 for `Q42`.
+    pub fn numeric(&self) -> Option<u64> {
+        self.numeric
+    }
+
+    /// The prefixed id, e.g. `wd:Q42`.
+    pub fn prefixed(&self) -> &str {
+        &self.prefixed
+    }
+
+    /// The full iri, e.g. `http://www.wikidata.org/entity/Q42`.
+    pub fn iri(&self) -> &str {
+        &self.iri
+    }
+}
+
+/// Id of a property, validated against the property pattern of its
+/// knowledge graph when created with [`KnowledgeGraphProcessor::property_id`].
+/// Like [`EntityId`] it compares, hashes, and borrows as the id returned by
+/// [`KnowledgeGraphProcessor::parse_property_id`].
+#[derive(Debug, Clone)]
+pub struct PropertyId {
+    id: String,
+    iri: String,
+    prefixed: String,
+    numeric: Option<u64>,
+}
+
+impl PropertyId {
+    /// The id as parsed from a property iri.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The number of a Wikidata property, e.g. 31 for `P31`.
+    pub fn numeric(&self) -> Option<u64> {
+        self.numeric
+    }
+
+    /// The prefixed id as written to the index, e.g. `wdt:P31`.
+    pub fn prefixed(&self) -> &str {
+        &self.prefixed
+    }
+
+    /// The full iri as written to the index, e.g.
+    /// `http://www.wikidata.org/prop/direct/P31`.
+    pub fn iri(&self) -> &str {
+        &self.iri
+    }
+}
+
+/// The number of a Wikidata id like Q42 or P31 (or its iri).
+fn numeric_id(id: &str) -> Option<u64> {
+    let id = id.rsplit(['/', ':']).next()?;
+    let digits = id.strip_prefix(['Q', 'P'])?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+impl PartialEq for EntityId {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for EntityId {}
+
+impl std::hash::Hash for EntityId {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl std::borrow::Borrow<str> for EntityId {
+    fn borrow(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Display for EntityId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl PartialEq for PropertyId {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for PropertyId {}
+
+impl std::hash::Hash for PropertyId {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl std::borrow::Borrow<str> for PropertyId {
+    fn borrow(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Display for PropertyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+/// Post-processes a parsed entity before labels are assigned, e.g. to
+/// filter it or to add aliases. Returning false drops the entity.
+/// Closures of the form `|ent, info| -> bool` are hooks as well.
+pub trait EntityHook: Send + Sync {
+    fn process<'a>(&'a self, ent: &str, info: &mut EntityInfo<'a>) -> bool;
+}
+
+impl<F> EntityHook for F
+where
+    F: Fn(&str, &mut EntityInfo<'_>) -> bool + Send + Sync,
+{
+    fn process<'a>(&'a self, ent: &str, info: &mut EntityInfo<'a>) -> bool {
+        self(ent, info)
+    }
+}
+
+/// Hook adding extra aliases per entity id, e.g. from an internal alias list.
+#[derive(Debug, Default)]
+pub struct ExtraAliases {
+    pub aliases: HashMap<EntityId, Vec<String>>,
+}
+
+impl EntityHook for ExtraAliases {
+    fn process<'a>(&'a self, ent: &str, info: &mut EntityInfo<'a>) -> bool {
+        for alias in self.aliases.get(ent).into_iter().flatten() {
+            if alias != info.label && !info.aliases.contains(&alias.as_str()) {
+                info.aliases.push(alias);
+            }
+        }
+        true
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd)]
 pub enum Prop<'a> {
     Label(&'a str),
     Alias(&'a str),
+    /// label or alias inherited from a parent property
+    Inherited(&'a str),
 }
 
 impl Ord for Prop<'_> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        match (self, other) {
-            (Prop::Label(_), Prop::Alias(_)) => Ordering::Less,
-            (Prop::Alias(_), Prop::Label(_)) => Ordering::Greater,
-            _ => Ordering::Equal,
-        }
+        self.rank()
+            .cmp(&other.rank())
+            .then_with(|| self.as_str().cmp(other.as_str()))
     }
 }
 
@@ -92,6 +325,15 @@ impl<'s> Prop<'s> {
         match self {
             Prop::Label(s) => s,
             Prop::Alias(s) => s,
+            Prop::Inherited(s) => s,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Prop::Label(_) => 0,
+            Prop::Alias(_) => 1,
+            Prop::Inherited(_) => 2,
         }
     }
 }
@@ -106,7 +348,82 @@ pub struct PropInfo<'a> {
     pub label: String,
     pub aliases: Vec<&'a str>,
     pub inverses: Vec<&'a str>,
+    /// direct parent properties, of which this one is a subproperty (e.g.
+    /// Wikidata P1647)
+    pub parents: Vec<&'a str>,
     pub count: usize,
+    /// label and aliases per additional language
+    pub translations: HashMap<String, (String, Vec<&'a str>)>,
+}
+
+impl<'a> PropInfo<'a> {
+    /// Label and aliases in the given language, None is the primary language.
+    pub fn labels_in(&self, lang: Option<&str>) -> Option<(&str, &[&'a str])> {
+        match lang {
+            None => Some((&self.label, &self.aliases)),
+            Some(lang) => self
+                .translations
+                .get(lang)
+                .map(|(label, aliases)| (label.as_str(), aliases.as_slice())),
+        }
+    }
+}
+
+/// Finds the additional language columns of a property file header, named
+/// ?p_label_<lang> and optionally ?p_aliases_<lang>. Returns the language with
+/// the label and alias column indices, sorted by language.
+pub fn property_language_columns(header: &[&str]) -> Vec<(String, usize, Option<usize>)> {
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().trim_start_matches('?') == name)
+    };
+    let mut columns: Vec<_> = header
+        .iter()
+        .enumerate()
+        .filter_map(|(i, h)| {
+            let lang = h.trim().trim_start_matches('?').strip_prefix("p_label_")?;
+            Some((lang.to_string(), i, column(&format!("p_aliases_{lang}"))))
+        })
+        .collect();
+    columns.sort();
+    columns
+}
+
+/// Makes the inverse property relation symmetric: if P lists P' as inverse
+/// but P' does not list P, P is added to the inverses of P'. If P' already
+/// declares other inverses, or several properties claim P' as inverse
+/// without P' declaring any of them, every such claim is a conflict and P'
+/// is left as is. Returns the number of added inverses and the conflicting
+/// pairs, sorted.
+pub fn symmetrize_inverses<'a>(
+    prop_infos: &mut HashMap<&'a str, PropInfo<'a>>,
+) -> (usize, Vec<(&'a str, &'a str)>) {
+    // one-way claims per inverse, collected before anything is added
+    let mut claims: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (&prop, info) in prop_infos.iter() {
+        for &inv in &info.inverses {
+            if prop_infos
+                .get(inv)
+                .is_some_and(|inv_info| !inv_info.inverses.contains(&prop))
+            {
+                claims.entry(inv).or_default().push(prop);
+            }
+        }
+    }
+    let mut added = 0;
+    let mut conflicts = vec![];
+    for (inv, props) in claims.into_iter().sorted() {
+        let inv_info = prop_infos.get_mut(inv).unwrap();
+        if inv_info.inverses.is_empty() && props.len() == 1 {
+            inv_info.inverses.push(props[0]);
+            added += 1;
+        } else {
+            conflicts.extend(props.into_iter().map(|prop| (prop, inv)));
+        }
+    }
+    conflicts.sort();
+    (added, conflicts)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -129,86 +446,335 @@ impl TryFrom<&str> for KnowledgeGraph {
     }
 }
 
+/// Counts of entries dropped while parsing properties, returned by the parse
+/// functions and summed up by the caller.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PropertyParseCounts {
+    pub empty_aliases: usize,
+    /// empty or invalid inverse property ids
+    pub invalid_inverses: usize,
+}
+
+impl AddAssign for PropertyParseCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.empty_aliases += other.empty_aliases;
+        self.invalid_inverses += other.invalid_inverses;
+    }
+}
+
+/// Host of the English DBPedia, language chapters live on subdomains
+/// like de.dbpedia.org.
+pub const DEFAULT_DBPEDIA_HOST: &str = "dbpedia.org";
+
+/// Default label templates of DBPedia ontology (dbo) and property (dbp)
+/// properties, see [`KnowledgeGraphProcessor::set_dbpedia_label_templates`].
+pub const DEFAULT_DBPEDIA_ONTOLOGY_LABEL: &str = "{label} (ontology)";
+pub const DEFAULT_DBPEDIA_PROPERTY_LABEL: &str = "{label}";
+
+/// Default label template of Freebase properties, see
+/// [`KnowledgeGraphProcessor::set_freebase_label_template`].
+pub const DEFAULT_FREEBASE_LABEL: &str = "{label} ({type})";
+
+/// Checks that a label template contains {label} and no placeholders other
+/// than the given ones.
+pub fn check_label_template(template: &str, placeholders: &[&str]) -> anyhow::Result<()> {
+    if !template.contains("{label}") {
+        bail!("label template {template} does not contain {{label}}");
+    }
+    for cap in Regex::new(r"\{([^{}]*)\}")?.captures_iter(template) {
+        let name = cap.get(1).unwrap().as_str();
+        if !placeholders.contains(&name) {
+            bail!(
+                "unknown placeholder {{{name}}} in label template {template}, expected one of {}",
+                placeholders.iter().map(|p| format!("{{{p}}}")).join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the placeholders ({name}) of a label template with their values
+/// in one pass, so placeholders within the values are kept as they are.
+pub fn fill_label_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut label = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        label.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            let (_, value) = values.iter().find(|(name, _)| *name == &rest[1..end])?;
+            Some((end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                label.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                label.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    label.push_str(rest);
+    label
+}
+
 pub struct KnowledgeGraphProcessor {
     pub label_pattern: Regex,
     pub prop_pattern: Regex,
-    pub ent_pattern: Regex,
+    /// entity iri patterns, each with one capture group for the entity id;
+    /// all of them are normalized to the canonical iri of the knowledge graph
+    pub ent_patterns: Vec<Regex>,
     pub kg: KnowledgeGraph,
+    entity_hooks: Vec<Box<dyn EntityHook>>,
+    dbpedia_resource: String,
+    dbpedia_property: String,
+    dbpedia_ontology_label: String,
+    dbpedia_property_label: String,
+    freebase_label: String,
+    /// full iris of properties whose short forms collide, they are always
+    /// formatted as full iris
+    short_form_collisions: HashSet<String>,
 }
 
 impl KnowledgeGraphProcessor {
     pub fn new(kg: KnowledgeGraph) -> anyhow::Result<Self> {
         let prop_pattern = Regex::new(match kg {
             KnowledgeGraph::Wikidata => r"<?(http://www.wikidata.org/entity/(P\d+))>?",
-            KnowledgeGraph::Freebase => r"<?(http://rdf.freebase.com/ns/(.+))>?",
-            KnowledgeGraph::DBPedia => r"<?(http://dbpedia.org/(property|ontology)/(.+))>?",
+            KnowledgeGraph::Freebase => r"<?(http://rdf.freebase.com/ns/([^>\s]+))>?",
+            KnowledgeGraph::DBPedia => return Self::with_dbpedia_host(DEFAULT_DBPEDIA_HOST),
         })?;
         let label_pattern = Regex::new("^\"(.*)\"@en$")?;
-        let ent_pattern = Regex::new(match kg {
-            KnowledgeGraph::Wikidata => r"<?(http://www.wikidata.org/entity/(Q\d+))>?",
-            KnowledgeGraph::Freebase => r"<?(http://rdf.freebase.com/ns/(m\..+))>?",
-            KnowledgeGraph::DBPedia => r"<?(http://dbpedia.org/resource/(.+))>?",
-        })?;
+        let ent_patterns = match kg {
+            KnowledgeGraph::Wikidata => vec![
+                r"^<?http://www\.wikidata\.org/entity/(Q\d+)>?$",
+                r"^<?https?://www\.wikidata\.org/wiki/(Q\d+)>?$",
+            ],
+            KnowledgeGraph::Freebase => vec![r"^<?http://rdf\.freebase\.com/ns/([mg]\.[^>\s]+)>?$"],
+            KnowledgeGraph::DBPedia => unreachable!(),
+        }
+        .into_iter()
+        .map(Regex::new)
+        .collect::<Result<_, _>>()?;
 
         Ok(Self {
             label_pattern,
             prop_pattern,
-            ent_pattern,
+            ent_patterns,
             kg,
+            entity_hooks: vec![],
+            dbpedia_resource: String::new(),
+            dbpedia_property: String::new(),
+            dbpedia_ontology_label: DEFAULT_DBPEDIA_ONTOLOGY_LABEL.to_string(),
+            dbpedia_property_label: DEFAULT_DBPEDIA_PROPERTY_LABEL.to_string(),
+            freebase_label: DEFAULT_FREEBASE_LABEL.to_string(),
+            short_form_collisions: HashSet::new(),
+        })
+    }
+
+    /// Creates a DBPedia processor for the given host, e.g. de.dbpedia.org
+    /// for the German chapter. Resources and properties are taken from that
+    /// host, the ontology is shared by all chapters.
+    pub fn with_dbpedia_host(host: &str) -> anyhow::Result<Self> {
+        if host.is_empty()
+            || !host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        {
+            bail!("invalid dbpedia host {}", host);
+        }
+        let escaped = regex::escape(host);
+        let prop_pattern = Regex::new(&format!(
+            r"<?(http://(?:{escaped}/(property)|dbpedia\.org/(ontology))/([^>\s]+))>?"
+        ))?;
+        // the default host also accepts iris of the language chapters,
+        // a chapter only accepts its own iris
+        let ent_pattern = if host == DEFAULT_DBPEDIA_HOST {
+            r"^<?https?://(?:[a-z]{2,3}\.)?dbpedia\.org/(?:resource|page)/([^>\s]+)>?$".to_string()
+        } else {
+            format!(r"^<?https?://{escaped}/(?:resource|page)/([^>\s]+)>?$")
+        };
+        Ok(Self {
+            label_pattern: Regex::new("^\"(.*)\"@en$")?,
+            prop_pattern,
+            ent_patterns: vec![Regex::new(&ent_pattern)?],
+            kg: KnowledgeGraph::DBPedia,
+            entity_hooks: vec![],
+            dbpedia_resource: format!("http://{host}/resource/"),
+            dbpedia_property: format!("http://{host}/property/"),
+            dbpedia_ontology_label: DEFAULT_DBPEDIA_ONTOLOGY_LABEL.to_string(),
+            dbpedia_property_label: DEFAULT_DBPEDIA_PROPERTY_LABEL.to_string(),
+            freebase_label: DEFAULT_FREEBASE_LABEL.to_string(),
+            short_form_collisions: HashSet::new(),
+        })
+    }
+
+    /// Sets the label templates of DBPedia ontology (dbo) and property (dbp)
+    /// properties, where {label} is replaced by the property label. By
+    /// default ontology labels get the suffix " (ontology)", so they do not
+    /// clash with the labels of the same property in the property namespace.
+    pub fn set_dbpedia_label_templates(
+        &mut self,
+        ontology: &str,
+        property: &str,
+    ) -> anyhow::Result<()> {
+        check_label_template(ontology, &["label"])?;
+        check_label_template(property, &["label"])?;
+        self.dbpedia_ontology_label = ontology.to_string();
+        self.dbpedia_property_label = property.to_string();
+        Ok(())
+    }
+
+    /// Sets the label template of Freebase properties. Besides {label}, it
+    /// may contain the segments of the property path, e.g. for
+    /// `people.person.spouse_s` {type} is `person` (the second to last
+    /// segment), {domain} is `people` (the first segment), and {path} is
+    /// `people person` (all but the last segment).
+    pub fn set_freebase_label_template(&mut self, template: &str) -> anyhow::Result<()> {
+        check_label_template(template, &["label", "type", "domain", "path"])?;
+        self.freebase_label = template.to_string();
+        Ok(())
+    }
+
+    /// Finds properties whose short forms are not unique, compared after
+    /// percent-decoding, e.g. `dbp:caf%C3%A9` and `dbp:café`, and formats
+    /// them as full iris from then on. Returns the number of properties with
+    /// a colliding short form.
+    pub fn resolve_short_form_collisions<'p>(
+        &mut self,
+        props: impl IntoIterator<Item = &'p str>,
+    ) -> anyhow::Result<usize> {
+        let mut full_iris: HashMap<String, HashSet<String>> = HashMap::new();
+        for prop in props {
+            let short = percent_decode(&self.format_property(prop, true, None)?);
+            let full = self.format_property(prop, false, None)?;
+            full_iris.entry(short).or_default().insert(full);
+        }
+        self.short_form_collisions = full_iris
+            .into_values()
+            .filter(|iris| iris.len() > 1)
+            .flatten()
+            .collect();
+        Ok(self.short_form_collisions.len())
+    }
+
+    /// Adds another entity iri pattern. It must have exactly one capture
+    /// group for the entity id.
+    pub fn add_entity_pattern(&mut self, pattern: &str) -> anyhow::Result<()> {
+        let pattern = Regex::new(pattern)?;
+        if pattern.captures_len() != 2 {
+            bail!("entity pattern {pattern} must have exactly one capture group");
+        }
+        self.ent_patterns.push(pattern);
+        Ok(())
+    }
+
+    /// Adds a hook that is run on every entity before labels are assigned.
+    pub fn add_entity_hook(&mut self, hook: impl EntityHook + 'static) {
+        self.entity_hooks.push(Box::new(hook));
+    }
+
+    /// Runs all entity hooks in the order they were added, returns false
+    /// if any of them drops the entity.
+    pub fn apply_entity_hooks<'a>(&'a self, ent: &str, info: &mut EntityInfo<'a>) -> bool {
+        self.entity_hooks.iter().all(|hook| hook.process(ent, info))
+    }
+
+    /// Extracts the entity id (e.g. Q42 for Wikidata) from an entity iri
+    /// matching any of the entity patterns.
+    #[inline]
+    pub fn parse_entity_id<'s>(&self, s: &'s str) -> Option<&'s str> {
+        let s = s.trim();
+        self.ent_patterns
+            .iter()
+            .find_map(|pattern| pattern.captures(s))
+            .map(|cap| cap.get(1).unwrap().as_str())
+    }
+
+    /// Creates the id of an entity from its iri (or an id the entity
+    /// patterns accept), fails if it is no entity of this knowledge graph.
+    pub fn entity_id(&self, s: &str) -> anyhow::Result<EntityId> {
+        let Some(id) = self.parse_entity_id(s) else {
+            bail!("{} is no {:?} entity", s.trim(), self.kg);
+        };
+        Ok(EntityId {
+            id: id.to_string(),
+            iri: self.format_entity(id, false),
+            prefixed: self.format_entity(id, true),
+            numeric: if self.kg == KnowledgeGraph::Wikidata {
+                numeric_id(id)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Creates the id of a property from its iri, fails if it is no property
+    /// of this knowledge graph.
+    pub fn property_id(&self, s: &str) -> anyhow::Result<PropertyId> {
+        let Some(id) = self.parse_property_id(s) else {
+            bail!("{} is no {:?} property", s.trim(), self.kg);
+        };
+        Ok(PropertyId {
+            id: id.to_string(),
+            iri: self.format_property(id, false, None)?,
+            prefixed: self.format_property(id, true, None)?,
+            numeric: if self.kg == KnowledgeGraph::Wikidata {
+                numeric_id(id)
+            } else {
+                None
+            },
         })
     }
 
+    /// Extracts the property id from a property iri.
+    #[inline]
+    pub fn parse_property_id<'s>(&self, s: &'s str) -> Option<&'s str> {
+        self.prop_pattern
+            .captures(s.trim())
+            .map(|cap| cap.get(1).unwrap().as_str())
+    }
+
     #[inline]
-    pub fn parse_property<'s>(&self, line: &'s str) -> anyhow::Result<(Prop<'s>, PropInfo<'s>)> {
+    pub fn parse_property<'s>(
+        &self,
+        line: &'s str,
+    ) -> anyhow::Result<(Prop<'s>, PropInfo<'s>, PropertyParseCounts)> {
         let splits: Vec<_> = line.split_terminator('\t').collect();
-        if splits.len() < 2 || splits.len() > 5 {
-            bail!("invalid property line: {}", line);
+        self.parse_property_fields(&splits)
+    }
+
+    /// Parses the id, label, count, aliases, and inverses of a property,
+    /// with the counts of the aliases and inverses that were dropped.
+    #[inline]
+    pub fn parse_property_fields<'s>(
+        &self,
+        splits: &[&'s str],
+    ) -> anyhow::Result<(Prop<'s>, PropInfo<'s>, PropertyParseCounts)> {
+        if splits.len() < 3 || splits.len() > 5 {
+            bail!("invalid property line: {}", splits.join("\t"));
         }
-        let Some(prop) = self.prop_pattern.captures(splits[0]) else {
+        let Some(prop) = self.parse_property_id(splits[0]) else {
             bail!("failed to capture property in {}", splits[0]);
         };
-        let prop = prop.get(1).unwrap().as_str();
 
-        let Some(label) = self.label_pattern.captures(splits[1]) else {
+        let Some(label) = self.parse_literal(splits[1]) else {
             bail!("failed to capture label in {}", splits[1]);
         };
-        let label = label.get(1).unwrap().as_str().trim();
-
-        let label = match self.kg {
-            KnowledgeGraph::Wikidata => label.to_string(),
-            KnowledgeGraph::DBPedia => {
-                if prop.starts_with("ontology") {
-                    format!("{label} (ontology)")
-                } else {
-                    label.to_string()
-                }
-            }
-            KnowledgeGraph::Freebase => {
-                let splits: Vec<_> = prop.split_terminator('.').collect();
-                if splits.len() < 2 {
-                    bail!("invalid freebase property: {}", prop);
-                }
-                format!("{label} ({})", splits[splits.len() - 2].replace('_', " "))
-            }
-        };
-        let aliases = splits[3]
-            .trim_start_matches('"')
-            .trim_end_matches('"')
-            .split_terminator(';')
-            .map(str::trim)
-            .collect();
-        let inverses = if splits.len() == 5 {
-            splits[4]
-                .split_terminator(';')
-                .filter_map(|s| {
-                    self.prop_pattern
-                        .captures(s.trim())?
-                        .get(1)
-                        .map(|m| m.as_str())
-                })
-                .collect()
+        let label = self.property_label(prop, label)?;
+        let (aliases, empty_aliases) =
+            self.parse_property_aliases(splits.get(3).copied().unwrap_or(""));
+        let (inverses, invalid_inverses) = if splits.len() == 5 {
+            let inverses: Vec<_> = splits[4].split_terminator(';').collect();
+            let valid: Vec<_> = inverses
+                .iter()
+                .filter_map(|s| self.parse_property_id(s))
+                .collect();
+            let num_invalid = inverses.len() - valid.len();
+            (valid, num_invalid)
         } else {
-            vec![]
+            (vec![], 0)
         };
         Ok((
             Prop::Label(prop),
@@ -217,10 +783,103 @@ impl KnowledgeGraphProcessor {
                 count: splits[2].parse()?,
                 aliases,
                 inverses,
+                parents: vec![],
+                translations: HashMap::new(),
+            },
+            PropertyParseCounts {
+                empty_aliases,
+                invalid_inverses,
             },
         ))
     }
 
+    /// Parses the labels and aliases of the given additional language columns
+    /// (see property_language_columns) into the info of a property. Languages
+    /// with an empty label are skipped. Returns the counts of the dropped
+    /// aliases.
+    pub fn parse_property_translations<'s>(
+        &self,
+        prop: &str,
+        info: &mut PropInfo<'s>,
+        columns: &[(String, usize, Option<usize>)],
+        splits: &[&'s str],
+    ) -> anyhow::Result<PropertyParseCounts> {
+        let mut counts = PropertyParseCounts::default();
+        for (lang, label_idx, aliases_idx) in columns {
+            let Some(label) = splits
+                .get(*label_idx)
+                .and_then(|s| self.parse_literal_in(s, lang))
+                .filter(|l| !l.is_empty())
+            else {
+                continue;
+            };
+            let label = self.property_label(prop, label)?;
+            let (aliases, empty_aliases) = aliases_idx
+                .and_then(|i| splits.get(i))
+                .map(|s| self.parse_property_aliases(s))
+                .unwrap_or_default();
+            counts.empty_aliases += empty_aliases;
+            info.translations.insert(lang.clone(), (label, aliases));
+        }
+        Ok(counts)
+    }
+
+    /// Splits a ;-separated alias list, dropping empty aliases, and returns
+    /// the aliases with the number of dropped ones.
+    fn parse_property_aliases<'s>(&self, s: &'s str) -> (Vec<&'s str>, usize) {
+        let aliases: Vec<_> = s
+            .trim_start_matches('"')
+            .trim_end_matches('"')
+            .split_terminator(';')
+            .map(str::trim)
+            .collect();
+        let num_aliases = aliases.len();
+        let aliases: Vec<_> = aliases.into_iter().filter(|a| !a.is_empty()).collect();
+        let num_empty = num_aliases - aliases.len();
+        (aliases, num_empty)
+    }
+
+    fn property_label(&self, prop: &str, label: &str) -> anyhow::Result<String> {
+        Ok(match self.kg {
+            KnowledgeGraph::Wikidata => label.to_string(),
+            KnowledgeGraph::DBPedia => {
+                let template = if self
+                    .prop_pattern
+                    .captures(prop)
+                    .is_some_and(|cap| cap.get(3).is_some())
+                {
+                    &self.dbpedia_ontology_label
+                } else {
+                    &self.dbpedia_property_label
+                };
+                fill_label_template(template, &[("label", label)])
+            }
+            KnowledgeGraph::Freebase => {
+                let path = self
+                    .prop_pattern
+                    .captures(prop)
+                    .map_or(prop, |cap| cap.get(2).unwrap().as_str());
+                let splits: Vec<_> = path
+                    .split_terminator('.')
+                    .map(|s| s.replace('_', " "))
+                    .collect();
+                if splits.len() < 2 {
+                    bail!("invalid freebase property: {}", prop);
+                }
+                let path = splits[..splits.len() - 1].join(" ");
+                fill_label_template(
+                    &self.freebase_label,
+                    &[
+                        ("label", label),
+                        ("type", &splits[splits.len() - 2]),
+                        ("domain", &splits[0]),
+                        ("path", &path),
+                    ],
+                )
+            }
+        })
+    }
+
     #[inline]
     pub fn parse_entity<'s>(
         &self,
@@ -228,31 +887,31 @@ impl KnowledgeGraphProcessor {
         ignore_types: bool,
     ) -> anyhow::Result<(Ent<'s>, EntityInfo<'s>)> {
         let splits: Vec<_> = line.split_terminator('\t').collect();
-        if splits.len() < 2 || splits.len() > 6 {
-            bail!("invalid entity line: {}", line);
+        self.parse_entity_fields(&splits, ignore_types)
+    }
+
+    #[inline]
+    pub fn parse_entity_fields<'s>(
+        &self,
+        splits: &[&'s str],
+        ignore_types: bool,
+    ) -> anyhow::Result<(Ent<'s>, EntityInfo<'s>)> {
+        if splits.len() < 4 || splits.len() > 6 {
+            bail!("invalid entity line: {}", splits.join("\t"));
         }
-        let Some(ent) = self.ent_pattern.captures(splits[0]) else {
+        let Some(ent) = self.parse_entity_id(splits[0]) else {
             bail!("failed to capture entity in {}", splits[0]);
         };
-        let ent = ent.get(1).unwrap().as_str();
-        let Some(label) = self.label_pattern.captures(splits[1]) else {
+        let Some(label) = self.parse_literal(splits[1]) else {
             bail!("failed to capture label in {}", splits[1]);
         };
-        let label = label.get(1).unwrap().as_str().trim();
-        let desc = if let Some(desc) = self.label_pattern.captures(splits[2]) {
-            desc.get(1).unwrap().as_str().trim()
-        } else {
-            ""
-        };
-        let types = Arc::new(Mutex::new(if ignore_types {
+        let desc = self.parse_literal(splits[2]).unwrap_or("");
+        let types = Arc::new(Mutex::new(if ignore_types || splits.len() < 5 {
             vec![]
         } else {
             splits[4]
                 .split_terminator(';')
-                .filter_map(|s| {
-                    let cap = self.ent_pattern.captures(s)?;
-                    Some(cap.get(1).unwrap().as_str().trim())
-                })
+                .filter_map(|s| self.parse_entity_id(s))
                 .collect()
         }));
         let aliases = if splits.len() == 6 {
@@ -278,6 +937,58 @@ impl KnowledgeGraphProcessor {
         ))
     }
 
+    /// The domain and type of a Freebase property from its path, e.g. people
+    /// and people.person for people.person.spouse_s. None for other
+    /// knowledge graphs and paths with less than three segments.
+    pub fn freebase_property_type<'s>(&self, p: &'s str) -> Option<(&'s str, &'s str)> {
+        if self.kg != KnowledgeGraph::Freebase {
+            return None;
+        }
+        let path = self.prop_pattern.captures(p)?.get(2)?.as_str();
+        let (ty, _) = path.rsplit_once('.')?;
+        let (domain, _) = ty.split_once('.')?;
+        Some((domain, ty))
+    }
+
+    /// Key to order properties by their id, numerically for Wikidata
+    /// (P2 before P10) and lexicographically otherwise.
+    pub fn property_order_key<'s>(&self, p: &'s str) -> (u64, &'s str) {
+        let num = match self.kg {
+            KnowledgeGraph::Wikidata => self
+                .prop_pattern
+                .captures(p)
+                .and_then(|cap| cap.get(2).unwrap().as_str()[1..].parse().ok())
+                .unwrap_or(u64::MAX),
+            _ => 0,
+        };
+        (num, p)
+    }
+
+    /// Parses an english literal like `"label"@en`. Unquoted values are
+    /// accepted as is, because SPARQL CSV results drop quotes and language tags.
+    #[inline]
+    pub fn parse_literal<'s>(&self, s: &'s str) -> Option<&'s str> {
+        if let Some(cap) = self.label_pattern.captures(s) {
+            Some(cap.get(1).unwrap().as_str().trim())
+        } else if !s.starts_with('"') && !s.trim().is_empty() {
+            Some(s.trim())
+        } else {
+            None
+        }
+    }
+
+    /// Like parse_literal, but for a literal in the given language.
+    pub fn parse_literal_in<'s>(&self, s: &'s str, lang: &str) -> Option<&'s str> {
+        match s
+            .strip_suffix(lang)
+            .and_then(|s| s.strip_suffix("\"@"))
+            .and_then(|s| s.strip_prefix('"'))
+        {
+            Some(literal) => Some(literal.trim()),
+            None => self.parse_literal(s),
+        }
+    }
+
     #[inline]
     pub fn format_property(
         &self,
@@ -285,6 +996,12 @@ impl KnowledgeGraphProcessor {
         short: bool,
         pfx: Option<&str>,
     ) -> anyhow::Result<String> {
+        if short && pfx.is_none() && !self.short_form_collisions.is_empty() {
+            let full = self.format_property(p, false, None)?;
+            if self.short_form_collisions.contains(&full) {
+                return Ok(full);
+            }
+        }
         let Some(p) = self.prop_pattern.captures(p) else {
             bail!("failed to capture property in {}", p);
         };
@@ -309,17 +1026,15 @@ impl KnowledgeGraphProcessor {
                 }
             }
             KnowledgeGraph::DBPedia => {
-                let p_type = p.get(2).unwrap().as_str();
-                let p = p.get(3).unwrap().as_str();
+                let ontology = p.get(3).is_some();
+                let p = p.get(4).unwrap().as_str();
                 if short {
-                    let pfx = if p_type == "ontology" { "dbo" } else { "dbp" };
+                    let pfx = if ontology { "dbo" } else { "dbp" };
                     format!("{pfx}:{p}")
+                } else if ontology {
+                    format!("{}{p}", pfx.unwrap_or("http://dbpedia.org/ontology/"))
                 } else {
-                    format!(
-                        "{}{}",
-                        pfx.unwrap_or(&format!("http://dbpedia.org/{p_type}")),
-                        p
-                    )
+                    format!("{}{p}", pfx.unwrap_or(&self.dbpedia_property))
                 }
             }
         })
@@ -329,7 +1044,20 @@ impl KnowledgeGraphProcessor {
         match self.kg {
             KnowledgeGraph::Wikidata => vec![("wd:", "http://www.wikidata.org/entity/")],
             KnowledgeGraph::Freebase => vec![("fb:", "http://rdf.freebase.com/ns/")],
-            KnowledgeGraph::DBPedia => vec![("dbr:", "http://dbpedia.org/resource/")],
+            KnowledgeGraph::DBPedia => vec![("dbr:", &self.dbpedia_resource)],
+        }
+    }
+
+    /// Prefixes of statement, reference, and value nodes, needed for queries
+    /// over qualified statements (only Wikidata has them).
+    pub fn statement_prefixes(&self) -> Vec<(&str, &str)> {
+        match self.kg {
+            KnowledgeGraph::Wikidata => vec![
+                ("wds:", "http://www.wikidata.org/entity/statement/"),
+                ("wdref:", "http://www.wikidata.org/reference/"),
+                ("wdv:", "http://www.wikidata.org/value/"),
+            ],
+            KnowledgeGraph::Freebase | KnowledgeGraph::DBPedia => vec![],
         }
     }
 
@@ -351,29 +1079,277 @@ impl KnowledgeGraphProcessor {
             ],
             KnowledgeGraph::Freebase => vec![("fbp:", "http://rdf.freebase.com/ns/property.")],
             KnowledgeGraph::DBPedia => vec![
-                ("dbp:", "http://dbpedia.org/property/"),
+                ("dbp:", &self.dbpedia_property),
                 ("dbo:", "http://dbpedia.org/ontology/"),
             ],
         }
     }
 
+    /// Formats an entity id as returned by `parse_entity_id`, either
+    /// prefixed (wd:Q42) or as canonical iri.
     #[inline]
-    pub fn format_entity(&self, e: &str, short: bool) -> anyhow::Result<String> {
-        if !short {
-            return Ok(e.to_string());
+    pub fn format_entity(&self, e: &str, short: bool) -> String {
+        let (short_pfx, long_pfx) = self.entity_prefixes()[0];
+        if short {
+            format!("{short_pfx}{e}")
+        } else {
+            format!("{long_pfx}{e}")
+        }
+    }
+
+    /// Whether a surface form looks like a bare id of the knowledge graph
+    /// (Q1, P31, m.0abc), so it could be mistaken for an id lookup.
+    pub fn looks_like_id(&self, s: &str) -> bool {
+        match self.kg {
+            KnowledgeGraph::Wikidata => s
+                .strip_prefix(['Q', 'P', 'L'])
+                .is_some_and(|num| !num.is_empty() && num.bytes().all(|b| b.is_ascii_digit())),
+            KnowledgeGraph::Freebase => s
+                .strip_prefix("m.")
+                .or_else(|| s.strip_prefix("g."))
+                .is_some_and(|id| {
+                    !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+                }),
+            // dbpedia ids are the titles themselves
+            KnowledgeGraph::DBPedia => false,
         }
-        let Some(e) = self.ent_pattern.captures(e) else {
-            bail!("failed to capture entity in {}", e);
-        };
-        let e = e.get(2).unwrap().as_str();
-        Ok(match self.kg {
-            KnowledgeGraph::Wikidata => format!("wd:{}", e),
-            KnowledgeGraph::Freebase => format!("fb:{}", e),
-            KnowledgeGraph::DBPedia => format!("dbr:{}", e),
-        })
     }
 }
 
+/// Normalizes a label for comparison by replacing punctuation (including
+/// hyphens) with spaces and collapsing whitespace, so "Spider-Man" and
+/// "Spider  Man" both become "Spider Man".
+pub fn normalize_label(label: &str) -> String {
+    label
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .join(" ")
+}
+
+/// Whether two strings differ by at most one inserted, deleted, or
+/// substituted character.
+pub fn within_edit_distance_one(a: &str, b: &str) -> bool {
+    let a: Vec<_> = a.chars().collect();
+    let b: Vec<_> = b.chars().collect();
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if long.len() - short.len() > 1 {
+        return false;
+    }
+    let prefix = short.iter().zip(&long).take_while(|(x, y)| x == y).count();
+    if short.len() == long.len() {
+        // one substitution at the first mismatch
+        short[prefix..]
+            .iter()
+            .skip(1)
+            .eq(long[prefix..].iter().skip(1))
+    } else {
+        // one insertion at the first mismatch
+        short[prefix..] == long[prefix + 1..]
+    }
+}
+
+/// Number of inserted, deleted, or substituted characters needed to turn
+/// one string into the other (Levenshtein distance).
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut prev: Vec<_> = (0..=b.len()).collect();
+    for (i, x) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, &y) in b.iter().enumerate() {
+            cur[j + 1] = (prev[j] + usize::from(x != y))
+                .min(prev[j + 1] + 1)
+                .min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Quality of an alias between 0 and 1, the product of
+/// - how distinct it is: 1 over the number of properties with the same
+///   normalized surface form (`frequency`),
+/// - how much it adds to the label: its edit distance to the label without
+///   a parenthesized suffix, up to 3,
+/// - how specific it is: aliases of one or two characters ("of", "in")
+///   rarely mean one property, up to 4 characters.
+pub fn alias_quality(alias: &str, label: &str, frequency: usize) -> f64 {
+    let alias = normalize_label(alias).to_lowercase();
+    let label = label.split(" (").next().unwrap_or(label);
+    let label = normalize_label(label).to_lowercase();
+    let distinct = 1.0 / frequency.max(1) as f64;
+    let novel = edit_distance(&alias, &label).min(3) as f64 / 3.0;
+    let specific = alias.chars().count().saturating_sub(1).min(3) as f64 / 3.0;
+    distinct * novel * specific
+}
+
+/// Decodes percent-encoded bytes like `%C3%A9`, invalid utf-8 is replaced.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The label cut to at most `max_chars` characters and `max_tokens`
+/// whitespace-separated tokens, none if it is not longer. The label is cut
+/// after the last whole token that fits, or within its first token if that
+/// alone is longer than `max_chars`.
+pub fn truncate_label(
+    label: &str,
+    max_chars: Option<usize>,
+    max_tokens: Option<usize>,
+) -> Option<&str> {
+    let mut cut = label;
+    if let Some(token) = max_tokens.and_then(|max| cut.split_whitespace().nth(max)) {
+        cut = &cut[..token.as_ptr() as usize - cut.as_ptr() as usize];
+    }
+    if let Some((end, c)) = max_chars.and_then(|max| cut.char_indices().nth(max)) {
+        cut = &cut[..end];
+        if !c.is_whitespace() {
+            if let Some(start) = cut.rfind(char::is_whitespace) {
+                cut = &cut[..start];
+            }
+        }
+    }
+    let cut = cut.trim_end();
+    (cut.len() < label.trim_end().len()).then_some(cut)
+}
+
+/// Splits a Wikipedia-style title like `Paris_(mythology)` into the name and
+/// its parenthetical qualifier, here "Paris" and "mythology". Percent-encoded
+/// bytes are decoded and underscores become spaces. Titles without a
+/// qualifier, or with nothing in front of it, give none.
+pub fn title_qualifier(title: &str) -> Option<(String, String)> {
+    let title = percent_decode(title).replace('_', " ");
+    let (name, qualifier) = title.strip_suffix(')')?.rsplit_once(" (")?;
+    let (name, qualifier) = (name.trim(), qualifier.trim());
+    if name.is_empty() || qualifier.is_empty() || qualifier.contains(['(', ')']) {
+        return None;
+    }
+    Some((name.to_string(), qualifier.to_string()))
+}
+
+/// Whether a surface form is plausibly English. Forms in a non-Latin script
+/// or reliably detected as another language are not; short forms are
+/// usually not detected reliably and are kept.
+#[cfg(feature = "language-filter")]
+fn likely_english(s: &str) -> bool {
+    match whatlang::detect(s) {
+        Some(info) if info.script() != whatlang::Script::Latin => false,
+        Some(info) => !info.is_reliable() || info.lang() == whatlang::Lang::Eng,
+        None => true,
+    }
+}
+
+/// Removes aliases that are unlikely to be English and returns how many
+/// were removed. Fails if built without the language-filter feature.
+pub fn remove_non_english(aliases: &mut Vec<&str>) -> anyhow::Result<usize> {
+    #[cfg(feature = "language-filter")]
+    {
+        let num_aliases = aliases.len();
+        aliases.retain(|alias| likely_english(alias));
+        Ok(num_aliases - aliases.len())
+    }
+    #[cfg(not(feature = "language-filter"))]
+    {
+        let _ = aliases;
+        bail!("language filtering requires the language-filter feature");
+    }
+}
+
+/// English nouns whose singular and plural are the same.
+const ENGLISH_INVARIANT_NOUNS: [&str; 17] = [
+    "aircraft",
+    "chess",
+    "deer",
+    "economics",
+    "fish",
+    "headquarters",
+    "mathematics",
+    "means",
+    "news",
+    "offspring",
+    "physics",
+    "politics",
+    "series",
+    "sheep",
+    "species",
+    "spacecraft",
+    "tennis",
+];
+
+/// English plurals in -ies whose singular ends in -ie, not in -y.
+const ENGLISH_IE_PLURALS: [&str; 12] = [
+    "brownies", "calories", "cookies", "hippies", "lies", "movies", "pies", "prairies", "rookies",
+    "selfies", "ties", "zombies",
+];
+
+/// Plural or singular form of an English label, built by simple rules on
+/// its last word ("programming language" -> "programming languages" and
+/// back). Returns None if the last word is not lowercase, e.g. for names,
+/// if it is the same in both forms ("series"), or if the rules cannot tell
+/// its singular ("causes" of "cause", but "bonuses" of "bonus").
+pub fn english_number_variant(label: &str) -> Option<String> {
+    let (prefix, word) = match label.rsplit_once(' ') {
+        Some((prefix, word)) => (format!("{prefix} "), word),
+        None => (String::new(), label),
+    };
+    if word.len() < 3
+        || !word.chars().all(|c| c.is_ascii_lowercase())
+        || ENGLISH_INVARIANT_NOUNS.contains(&word)
+    {
+        return None;
+    }
+    let variant = if ENGLISH_IE_PLURALS.contains(&word) {
+        word[..word.len() - 1].to_string()
+    } else if let Some(stem) = word.strip_suffix("ies") {
+        format!("{stem}y")
+    } else if word.ends_with("yses") || word.ends_with("eses") {
+        // analyses -> analysis, theses -> thesis
+        format!("{}is", &word[..word.len() - 2])
+    } else if word.ends_with("uses") && !word.ends_with("ouses") {
+        return None;
+    } else if let Some(stem) = word.strip_suffix("es").filter(|stem| {
+        ["ss", "x", "zz", "ch", "sh"]
+            .iter()
+            .any(|suffix| stem.ends_with(suffix))
+    }) {
+        // classes -> class, but houses -> house
+        stem.to_string()
+    } else if let Some(stem) = word.strip_suffix("is") {
+        format!("{stem}es")
+    } else if word.ends_with('s') && !["ss", "us"].iter().any(|s| word.ends_with(s)) {
+        word[..word.len() - 1].to_string()
+    } else if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| word.ends_with(suffix))
+    {
+        format!("{word}es")
+    } else if word.ends_with('y') && !word[..word.len() - 1].ends_with(['a', 'e', 'i', 'o', 'u']) {
+        format!("{}ies", &word[..word.len() - 1])
+    } else {
+        format!("{word}s")
+    };
+    Some(format!("{prefix}{variant}"))
+}
+
 pub fn wikidata_qualifiers(label: &str) -> Vec<(String, String, String)> {
     vec![
         (
@@ -404,25 +1380,2003 @@ pub fn wikidata_qualifiers(label: &str) -> Vec<(String, String, String)> {
     ]
 }
 
+/// Groups the qualifier variants of the given property labels by prefix, in
+/// the order of `wikidata_qualifiers`. Returns `(short prefix, long prefix, labels)`.
+pub fn wikidata_qualifier_groups<'a>(
+    labels: impl IntoIterator<Item = &'a str>,
+) -> Vec<(String, String, Vec<String>)> {
+    let mut groups: Vec<_> = wikidata_qualifiers("")
+        .into_iter()
+        .map(|(_, short, long)| (short, long, vec![]))
+        .collect();
+    for label in labels {
+        for ((lbl, ..), (.., lbls)) in wikidata_qualifiers(label).into_iter().zip(&mut groups) {
+            lbls.push(lbl);
+        }
+    }
+    groups.retain(|(.., lbls)| !lbls.is_empty());
+    groups
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    Tsv,
+    Csv,
+    /// one JSON object per line, only supported for some outputs
+    Jsonl,
+}
+
+impl TryFrom<&str> for FileFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "tsv" => FileFormat::Tsv,
+            "csv" => FileFormat::Csv,
+            "jsonl" => FileFormat::Jsonl,
+            _ => return Err(anyhow!("invalid file format {}", value)),
+        })
+    }
+}
+
+impl FileFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FileFormat::Tsv => "tsv",
+            FileFormat::Csv => "csv",
+            FileFormat::Jsonl => "jsonl",
+        }
+    }
+
+    /// Joins a file name with the extension of this format, e.g. `index` -> `index.csv`.
+    pub fn file_name(&self, name: &str) -> String {
+        format!("{name}.{}", self.extension())
+    }
+}
+
+/// How the label and info of a surface form are written, see
+/// [`format_surface_form`] and [`parse_surface_form`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SurfaceFormEscaping {
+    /// as they are, so tabs and line breaks break TSV rows, and parentheses
+    /// in a label look like an info
+    #[default]
+    None,
+    /// tabs and line breaks become spaces and parentheses become brackets
+    Sanitize,
+    /// backslashes, tabs, line breaks, and parentheses are escaped with a
+    /// backslash (`\\`, `\t`, `\n`, `\r`, `\(`, and `\)`)
+    Escape,
+}
+
+impl TryFrom<&str> for SurfaceFormEscaping {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "none" => SurfaceFormEscaping::None,
+            "sanitize" => SurfaceFormEscaping::Sanitize,
+            "escape" => SurfaceFormEscaping::Escape,
+            _ => return Err(anyhow!("invalid surface form escaping {}", value)),
+        })
+    }
+}
+
+impl SurfaceFormEscaping {
+    fn apply(&self, s: &str) -> String {
+        match self {
+            SurfaceFormEscaping::None => s.to_string(),
+            SurfaceFormEscaping::Sanitize => s
+                .chars()
+                .map(|c| match c {
+                    '\t' | '\n' | '\r' => ' ',
+                    '(' => '[',
+                    ')' => ']',
+                    c => c,
+                })
+                .collect(),
+            SurfaceFormEscaping::Escape => {
+                let mut escaped = String::with_capacity(s.len());
+                for c in s.chars() {
+                    match c {
+                        '\\' => escaped.push_str("\\\\"),
+                        '\t' => escaped.push_str("\\t"),
+                        '\n' => escaped.push_str("\\n"),
+                        '\r' => escaped.push_str("\\r"),
+                        '(' | ')' => {
+                            escaped.push('\\');
+                            escaped.push(c);
+                        }
+                        c => escaped.push(c),
+                    }
+                }
+                escaped
+            }
+        }
+    }
+}
+
+/// Writes a surface form as "{label} ({info})", or only its label without
+/// info, escaping both parts.
+pub fn format_surface_form(
+    label: &str,
+    info: Option<&str>,
+    escaping: SurfaceFormEscaping,
+) -> String {
+    match info {
+        Some(info) => format!("{} ({})", escaping.apply(label), escaping.apply(info)),
+        None => escaping.apply(label),
+    }
+}
+
+/// Splits a surface form written by [`format_surface_form`] into its label
+/// and info. A form ending with ")" has an info, which starts after the last
+/// " (". With escaping, only unescaped parentheses delimit the info, and
+/// both parts are unescaped; unknown escapes keep the escaped character.
+/// Sanitized forms cannot be restored and are split like unescaped ones.
+pub fn parse_surface_form(form: &str, escaping: SurfaceFormEscaping) -> (String, Option<String>) {
+    if escaping != SurfaceFormEscaping::Escape {
+        return match form
+            .strip_suffix(')')
+            .and_then(|rest| rest.rsplit_once(" ("))
+        {
+            Some((label, info)) => (label.to_string(), Some(info.to_string())),
+            None => (form.to_string(), None),
+        };
+    }
+    // unescapes while remembering where the last unescaped " (" was
+    let mut unescaped = String::with_capacity(form.len());
+    let mut info_start = None;
+    let mut ends_with_paren = false;
+    let mut chars = form.chars();
+    while let Some(c) = chars.next() {
+        ends_with_paren = false;
+        match c {
+            '\\' => unescaped.push(match chars.next() {
+                Some('t') => '\t',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some(c) => c,
+                None => '\\',
+            }),
+            '(' if unescaped.ends_with(' ') => {
+                info_start = Some(unescaped.len());
+                unescaped.push(c);
+            }
+            ')' => {
+                ends_with_paren = true;
+                unescaped.push(c);
+            }
+            c => unescaped.push(c),
+        }
+    }
+    match info_start {
+        Some(start) if ends_with_paren => {
+            let info = unescaped[start + 1..unescaped.len() - 1].to_string();
+            unescaped.truncate(start - 1);
+            (unescaped, Some(info))
+        }
+        _ => (unescaped, None),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdFormat {
+    /// ids of the knowledge graph, e.g. wdt:P31 or its full iri
+    Kg,
+    /// consecutive numbers, mapped back to knowledge graph ids by an id map
+    Numeric,
+}
+
+impl TryFrom<&str> for IdFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "kg" => IdFormat::Kg,
+            "numeric" => IdFormat::Numeric,
+            _ => return Err(anyhow!("invalid id format {}", value)),
+        })
+    }
+}
+
+/// Maps knowledge graph ids to output ids. With the numeric id format new ids
+/// are numbered in the order given to [`IdMap::number`], which a build calls
+/// with all its ids before writing, otherwise they are kept as is.
+pub struct IdMap {
+    format: IdFormat,
+    ids: HashMap<String, usize>,
+    /// numeric id, knowledge graph id, and whether the id was used, sorted
+    /// by numeric id
+    kg_ids: Vec<(usize, String, bool)>,
+    num_previous: usize,
+}
+
+impl IdMap {
+    pub fn new(format: IdFormat) -> Self {
+        Self {
+            format,
+            ids: HashMap::new(),
+            kg_ids: vec![],
+            num_previous: 0,
+        }
+    }
+
+    /// Continues the numbering of the id map of a previous build, so ids
+    /// keep their numbers across rebuilds. Numbers of ids that are no longer
+    /// used stay reserved, new ids are numbered after the largest one.
+    pub fn load_previous(&mut self, file: impl AsRef<Path>) -> anyhow::Result<usize> {
+        if self.format != IdFormat::Numeric {
+            bail!("a previous id map requires the numeric id format");
+        }
+        if !self.kg_ids.is_empty() {
+            bail!("a previous id map has to be loaded before mapping ids");
+        }
+        let file = file.as_ref();
+        let format = match file.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => FileFormat::Csv,
+            _ => FileFormat::Tsv,
+        };
+        let mut nums = HashSet::new();
+        for record in record_iter(file, format)? {
+            let record = record?;
+            let (Some(num), Some(id)) = (record.get(0), record.get(1)) else {
+                bail!("invalid id map line in {}: {:?}", file.display(), record);
+            };
+            let Ok(num) = num.parse() else {
+                bail!("invalid numeric id {num} in {}", file.display());
+            };
+            if !nums.insert(num) || self.ids.insert(id.to_string(), 0).is_some() {
+                bail!("duplicate id {num} or {id} in {}", file.display());
+            }
+            self.kg_ids.push((num, id.to_string(), false));
+        }
+        self.kg_ids.sort_by_key(|&(num, ..)| num);
+        for (idx, (_, id, _)) in self.kg_ids.iter().enumerate() {
+            self.ids.insert(id.clone(), idx);
+        }
+        self.num_previous = self.kg_ids.len();
+        Ok(self.num_previous)
+    }
+
+    fn index(&mut self, id: String) -> usize {
+        match self.ids.get(&id) {
+            Some(&idx) => idx,
+            None => {
+                let num = self.kg_ids.last().map(|&(num, ..)| num + 1).unwrap_or(0);
+                self.ids.insert(id.clone(), self.kg_ids.len());
+                self.kg_ids.push((num, id, false));
+                self.kg_ids.len() - 1
+            }
+        }
+    }
+
+    /// Numbers the given ids that have no number yet in the given order, so
+    /// the numbers do not depend on the order in which outputs are written.
+    pub fn number(&mut self, ids: impl IntoIterator<Item = String>) {
+        if self.format == IdFormat::Kg {
+            return;
+        }
+        for id in ids {
+            self.index(id);
+        }
+    }
+
+    /// The output id of a knowledge graph id. Ids not passed to
+    /// [`IdMap::number`] before are numbered on first use.
+    pub fn map(&mut self, id: String) -> String {
+        if self.format == IdFormat::Kg {
+            return id;
+        }
+        let idx = self.index(id);
+        let (num, _, used) = &mut self.kg_ids[idx];
+        *used = true;
+        num.to_string()
+    }
+
+    /// The output id of an entity, written as its iri or, with short, its
+    /// prefixed id (see [`IdMap::map`]).
+    pub fn map_entity(&mut self, ent: &EntityId, short: bool) -> String {
+        let id = if short { ent.prefixed() } else { ent.iri() };
+        self.map(id.to_string())
+    }
+
+    /// The output id of a property, written as its iri or, with short, its
+    /// prefixed id (see [`IdMap::map`]).
+    pub fn map_property(&mut self, prop: &PropertyId, short: bool) -> String {
+        let id = if short { prop.prefixed() } else { prop.iri() };
+        self.map(id.to_string())
+    }
+
+    /// Numeric ids with their knowledge graph ids, empty for the kg id format.
+    /// Includes the unused ids of a previous id map.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.kg_ids.iter().map(|(num, id, _)| (*num, id.as_str()))
+    }
+
+    /// Number of ids of the previous id map that were used again, that were
+    /// not used, and of ids that were newly numbered.
+    pub fn previous_counts(&self) -> (usize, usize, usize) {
+        let reused = self.kg_ids[..self.num_previous]
+            .iter()
+            .filter(|&&(_, _, used)| used)
+            .count();
+        (
+            reused,
+            self.num_previous - reused,
+            self.kg_ids.len() - self.num_previous,
+        )
+    }
+}
+
+/// Iterates over the records of a TSV or CSV file, including the header.
+/// TSV is read verbatim (no quoting), CSV follows RFC 4180, so quoted fields
+/// may contain delimiters and newlines.
+/// Compressed files are decompressed on the fly, see [`open_input`].
+pub fn record_iter(
+    file: impl AsRef<Path>,
+    format: FileFormat,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<csv::StringRecord>>> {
+    read_records(open_input(file)?, format)
+}
+
+fn read_records(
+    input: impl Read,
+    format: FileFormat,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<csv::StringRecord>>> {
+    let mut builder = csv::ReaderBuilder::new();
+    builder.has_headers(false).flexible(true);
+    match format {
+        FileFormat::Tsv => builder.delimiter(b'\t').quoting(false),
+        FileFormat::Csv => builder.delimiter(b','),
+        FileFormat::Jsonl => bail!("jsonl files cannot be read as records"),
+    };
+    let reader = builder.from_reader(input);
+    Ok(reader
+        .into_records()
+        .map(|record| record.map_err(anyhow::Error::from)))
+}
+
+/// The first records of a file (including the header), with the number of
+/// records in the file extrapolated from their size.
+pub struct RecordSample {
+    pub records: Vec<csv::StringRecord>,
+    pub file_bytes: u64,
+    /// bytes taken by the sampled records
+    pub sample_bytes: u64,
+    /// exact if the whole file was sampled
+    pub estimated_records: u64,
+}
+
+pub fn sample_records(
+    file: impl AsRef<Path>,
+    format: FileFormat,
+    size: usize,
+) -> anyhow::Result<RecordSample> {
+    let file_bytes = fs::metadata(&file)?.len();
+    let compressed = Compression::detect(&file)? != Compression::None;
+    let bytes_read = Arc::new(AtomicU64::new(0));
+    let mut records = read_records(open_counted(&file, bytes_read.clone())?, format)?;
+    let mut sample = vec![];
+    let mut sample_bytes = file_bytes;
+    for record in records.by_ref() {
+        let record = record?;
+        if sample.len() > size {
+            // the start of the next record is the end of the sample, for
+            // compressed files the compressed bytes read so far (which
+            // includes some read ahead)
+            sample_bytes = if compressed {
+                bytes_read.load(AtomicOrdering::Relaxed)
+            } else {
+                record.position().map_or(file_bytes, |pos| pos.byte())
+            };
+            break;
+        }
+        sample.push(record);
+    }
+    let estimated_records = if sample_bytes == file_bytes || sample_bytes == 0 {
+        sample.len() as u64
+    } else {
+        (sample.len() as f64 * file_bytes as f64 / sample_bytes as f64).round() as u64
+    };
+    Ok(RecordSample {
+        records: sample,
+        file_bytes,
+        sample_bytes,
+        estimated_records,
+    })
+}
+
+/// Creates a writer for rows with a variable number of fields. TSV is written
+/// verbatim (no quoting), CSV quotes fields as necessary.
+pub fn record_writer(
+    file: impl AsRef<Path>,
+    format: FileFormat,
+) -> anyhow::Result<csv::Writer<fs::File>> {
+    let mut builder = csv::WriterBuilder::new();
+    builder.flexible(true);
+    match format {
+        FileFormat::Tsv => builder.delimiter(b'\t').quote_style(csv::QuoteStyle::Never),
+        FileFormat::Csv => builder.delimiter(b','),
+        FileFormat::Jsonl => bail!("jsonl files cannot be written as records"),
+    };
+    Ok(builder.from_path(file)?)
+}
+
+/// Writes a finite state transducer mapping keys to values, e.g. surface
+/// forms to their row in an index. Keys that occur more than once keep their
+/// smallest value. Returns the number of keys written.
+pub fn write_fst(
+    file: impl AsRef<Path>,
+    entries: impl IntoIterator<Item = (String, u64)>,
+) -> anyhow::Result<usize> {
+    let mut entries: Vec<_> = entries.into_iter().collect();
+    entries.sort();
+    entries.dedup_by(|(key, _), (prev, _)| key == prev);
+    let mut builder = fst::MapBuilder::new(std::io::BufWriter::new(fs::File::create(file)?))?;
+    for (key, value) in &entries {
+        builder.insert(key, *value)?;
+    }
+    builder.finish()?;
+    Ok(entries.len())
+}
+
+/// Expected columns of entity files.
+pub const ENTITY_COLUMNS: [&str; 6] = [
+    "ent",
+    "ent_name",
+    "ent_description",
+    "links",
+    "types",
+    "aliases",
+];
+
+/// Expected columns of property files.
+pub const PROPERTY_COLUMNS: [&str; 5] = ["p", "p_label", "p_count", "p_aliases", "p_invs"];
+
+/// Finds the expected columns in a header by name (ignoring a leading ? and
+/// case). Columns not named in the header fall back to their expected position
+/// if no other expected column was found there. Returns the index of every
+/// expected column and a description of each mismatch.
+pub fn map_header_columns(header: &[&str], expected: &[&str]) -> (Vec<Option<usize>>, Vec<String>) {
+    let normalize = |s: &str| s.trim().trim_start_matches('?').to_lowercase();
+    let names: Vec<_> = header.iter().map(|h| normalize(h)).collect();
+    let mut columns: Vec<_> = expected
+        .iter()
+        .map(|e| names.iter().position(|n| *n == normalize(e)))
+        .collect();
+    let mut problems = vec![];
+    for (i, name) in expected.iter().enumerate() {
+        if columns[i].is_some() {
+            continue;
+        } else if i < header.len() && !columns.contains(&Some(i)) {
+            problems.push(format!(
+                "column {name} not in header, using column {} ({})",
+                i + 1,
+                header[i].trim()
+            ));
+            columns[i] = Some(i);
+        } else {
+            problems.push(format!("column {name} not in header, leaving it empty"));
+        }
+    }
+    (columns, problems)
+}
+
+/// Prints header mismatches as warnings, or fails on them if strict.
+pub fn report_header_problems(
+    file: impl AsRef<Path>,
+    problems: &[String],
+    strict: bool,
+) -> anyhow::Result<()> {
+    if strict && !problems.is_empty() {
+        bail!(
+            "unexpected header in {}: {}",
+            file.as_ref().display(),
+            problems.join("; ")
+        );
+    }
+    for problem in problems {
+        eprintln!("warning: {}: {problem}", file.as_ref().display());
+    }
+    Ok(())
+}
+
+/// Selects the given columns of a record, missing columns are empty.
+pub fn select_columns<'s>(
+    record: &'s csv::StringRecord,
+    columns: &[Option<usize>],
+) -> Vec<&'s str> {
+    columns
+        .iter()
+        .map(|&i| i.and_then(|i| record.get(i)).unwrap_or(""))
+        .collect()
+}
+
+/// An entity parsed from an entity file that owns its fields, so the lines
+/// of the file do not need to be kept in memory. Its strings are interned,
+/// so labels, descriptions, aliases, and types that repeat across entities
+/// share one allocation.
+#[derive(Debug, Clone)]
+pub struct OwnedEntity {
+    pub id: String,
+    pub label: Arc<str>,
+    pub desc: Arc<str>,
+    pub aliases: Vec<Arc<str>>,
+    pub types: Vec<Arc<str>>,
+    pub count: usize,
+}
+
+impl OwnedEntity {
+    /// The entity with its strings interned.
+    pub fn new(ent: &str, info: &EntityInfo<'_>, interner: &mut StringInterner) -> Self {
+        Self {
+            id: ent.to_string(),
+            label: interner.intern(info.label),
+            desc: interner.intern(info.desc),
+            aliases: info.aliases.iter().map(|s| interner.intern(s)).collect(),
+            types: info
+                .types
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|s| interner.intern(s))
+                .collect(),
+            count: info.count,
+        }
+    }
+
+    /// The entity info borrowing from this entity.
+    pub fn info(&self) -> EntityInfo<'_> {
+        EntityInfo {
+            label: &self.label,
+            desc: &self.desc,
+            aliases: self.aliases.iter().map(AsRef::as_ref).collect(),
+            types: Arc::new(Mutex::new(self.types.iter().map(AsRef::as_ref).collect())),
+            count: self.count,
+            redirects: None,
+        }
+    }
+}
+
+/// Reads an entity file record by record and yields the parsed entities,
+/// keeping only the current record in memory. Records that cannot be parsed
+/// are skipped and counted.
+pub struct StreamingEntityReader<'a> {
+    kg: &'a KnowledgeGraphProcessor,
+    records: Box<dyn Iterator<Item = anyhow::Result<csv::StringRecord>> + 'a>,
+    columns: Vec<Option<usize>>,
+    header_problems: Vec<String>,
+    ignore_types: bool,
+    counts: Option<&'a HashMap<String, usize>>,
+    filter: Box<dyn Fn(&str) -> bool + 'a>,
+    pub interner: StringInterner,
+    pub num_records: usize,
+    pub num_filtered: usize,
+    pub num_invalid: usize,
+}
+
+impl<'a> StreamingEntityReader<'a> {
+    /// Opens the entity file and maps its header to the entity columns.
+    pub fn new(
+        kg: &'a KnowledgeGraphProcessor,
+        file: impl AsRef<Path>,
+        format: FileFormat,
+        ignore_types: bool,
+    ) -> anyhow::Result<Self> {
+        let file = file.as_ref();
+        let mut records = record_iter(file.to_path_buf(), format)?;
+        let Some(header) = records.next() else {
+            bail!("{} is empty", file.display());
+        };
+        let header = header?;
+        let header: Vec<_> = header.iter().collect();
+        let (columns, header_problems) = map_header_columns(&header, &ENTITY_COLUMNS);
+        Ok(Self {
+            kg,
+            records: Box::new(records),
+            columns,
+            header_problems,
+            ignore_types,
+            counts: None,
+            filter: Box::new(|_| true),
+            interner: StringInterner::new(),
+            num_records: 0,
+            num_filtered: 0,
+            num_invalid: 0,
+        })
+    }
+
+    /// Takes the counts of the given entities from the map instead of the
+    /// count column, which may be empty for them.
+    pub fn with_counts(mut self, counts: &'a HashMap<String, usize>) -> Self {
+        self.counts = Some(counts);
+        self
+    }
+
+    /// Skips entities whose id does not satisfy the filter before parsing
+    /// the rest of their record.
+    pub fn with_filter(mut self, filter: impl Fn(&str) -> bool + 'a) -> Self {
+        self.filter = Box::new(filter);
+        self
+    }
+
+    /// Column of every entity field in the file, see [`ENTITY_COLUMNS`].
+    pub fn columns(&self) -> &[Option<usize>] {
+        &self.columns
+    }
+
+    /// Mismatches between the header and the entity columns.
+    pub fn header_problems(&self) -> &[String] {
+        &self.header_problems
+    }
+}
+
+impl Iterator for StreamingEntityReader<'_> {
+    type Item = anyhow::Result<OwnedEntity>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.records.next()? {
+                Ok(record) => record,
+                Err(e) => return Some(Err(e)),
+            };
+            self.num_records += 1;
+            let mut fields = select_columns(&record, &self.columns);
+            let ent = fields.first().and_then(|ent| self.kg.parse_entity_id(ent));
+            if ent.is_some_and(|ent| !(self.filter)(ent)) {
+                self.num_filtered += 1;
+                continue;
+            }
+            let count = ent
+                .and_then(|ent| self.counts.and_then(|counts| counts.get(ent)))
+                .copied();
+            if count.is_some() {
+                fields[3] = "0";
+            }
+            let Ok((ent, mut info)) = self.kg.parse_entity_fields(&fields, self.ignore_types)
+            else {
+                self.num_invalid += 1;
+                continue;
+            };
+            if let Some(count) = count {
+                info.count = count;
+            }
+            return Some(Ok(OwnedEntity::new(
+                ent.as_str(),
+                &info,
+                &mut self.interner,
+            )));
+        }
+    }
+}
+
+/// How an [`EntityIndexBuilder`] disambiguates entities with the same label
+/// or alias.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Disambiguation {
+    /// entities whose label is not unique are left out
+    Drop,
+    /// the most popular entity gets the label, the others are left out
+    MostCommon,
+    /// the label with info (most popular type or description), entities
+    /// whose label and info are not unique either are left out
+    #[default]
+    InfoSuffix,
+    /// the label with the entity id, e.g. "Paris (Q90)"
+    QidSuffix,
+    /// the label with info, and the most popular of the entities whose label
+    /// and info are not unique either
+    All,
+}
+
+impl TryFrom<&str> for Disambiguation {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "drop" => Disambiguation::Drop,
+            "most-common" => Disambiguation::MostCommon,
+            "info-suffix" => Disambiguation::InfoSuffix,
+            "qid-suffix" => Disambiguation::QidSuffix,
+            "all" => Disambiguation::All,
+            _ => return Err(anyhow!("invalid disambiguation {}", value)),
+        })
+    }
+}
+
+impl Disambiguation {
+    /// The suffix that disambiguates a surface form of an entity, empty if
+    /// surface forms are not disambiguated by a suffix.
+    pub fn suffix<'a>(&self, ent: &'a str, info: &EntityInfo<'a>) -> &'a str {
+        match self {
+            Disambiguation::Drop | Disambiguation::MostCommon => "",
+            Disambiguation::InfoSuffix | Disambiguation::All => info.info(),
+            Disambiguation::QidSuffix => ent,
+        }
+    }
+}
+
+/// What an [`EntityIndexBuilder`] does with a surface form an entity gets
+/// from more than one source, e.g. an alias that equals its label with info.
+/// Labels are assigned before aliases and redirect labels, so the label
+/// always holds the form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LabelAliasPrecedence {
+    /// the form is the label's, the other sources are ignored
+    #[default]
+    Label,
+    /// the form is tagged with all of its sources, see
+    /// [`EntityIndexBuilder::merged_sources`]
+    Merge,
+}
+
+impl TryFrom<&str> for LabelAliasPrecedence {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "label" => LabelAliasPrecedence::Label,
+            "merge" => LabelAliasPrecedence::Merge,
+            _ => return Err(anyhow!("invalid label alias precedence {}", value)),
+        })
+    }
+}
+
+/// Options of an [`EntityIndexBuilder`], see the kg-entities flags of the
+/// same name.
+#[derive(Debug, Clone)]
+pub struct EntityIndexOptions {
+    pub check_for_popular_aliases: bool,
+    pub popular_alias_ratio: f64,
+    pub disambiguation: Disambiguation,
+    pub resolve_alias_conflicts: bool,
+    pub alias_conflict_ratio: f64,
+    pub normalize_labels: bool,
+    pub escaping: SurfaceFormEscaping,
+    pub label_alias_precedence: LabelAliasPrecedence,
+    pub label_info_variants: bool,
+    /// receives the progress of the stages of the builder
+    pub progress_listener: Option<SharedListener>,
+}
+
+impl Default for EntityIndexOptions {
+    fn default() -> Self {
+        Self {
+            check_for_popular_aliases: false,
+            popular_alias_ratio: 1.0,
+            disambiguation: Disambiguation::InfoSuffix,
+            resolve_alias_conflicts: false,
+            alias_conflict_ratio: 1.0,
+            normalize_labels: false,
+            escaping: SurfaceFormEscaping::None,
+            label_alias_precedence: LabelAliasPrecedence::Label,
+            label_info_variants: false,
+            progress_listener: None,
+        }
+    }
+}
+
+impl EntityIndexOptions {
+    fn progress_bar(&self, msg: &str, size: u64, hidden: bool) -> Progress {
+        Progress::with_listener(msg, size, hidden, self.progress_listener.clone())
+    }
+}
+
+/// Builds the mapping from surface forms to entities of an entity index.
+///
+/// [`resolve`](Self::resolve) gives every entity its label if no other
+/// entity has the same label, and otherwise disambiguates it according to
+/// the [`Disambiguation`] of the options, by default with its label with info
+/// (its most popular type or its description), e.g. "Paris (city in France)".
+/// Entities whose label and info are not unique either are left out, unless
+/// the disambiguation is [`Disambiguation::All`], which keeps the most popular
+/// of them. Aliases are added afterwards by descending popularity of their
+/// entity, with the same suffix if another entity already has the alias.
+pub struct EntityIndexBuilder<'a> {
+    options: EntityIndexOptions,
+    entities: HashMap<&'a str, EntityInfo<'a>>,
+    needs_info: Box<dyn Fn(&str) -> bool + 'a>,
+    progress: bool,
+    alias_priors: bool,
+    index: HashMap<(&'a str, Option<&'a str>), Ent<'a>>,
+    variant_aliases: Vec<(&'a str, &'a str)>,
+    /// aliases that could not be added, with the reason and the entity that
+    /// already holds the surface form
+    pub dropped_aliases: Vec<(&'a str, &'a str, &'static str, &'a str)>,
+    /// aliases of several entities with these entities and their counts,
+    /// most popular first, see [`with_alias_priors`](Self::with_alias_priors)
+    pub shared_aliases: Vec<(&'a str, Vec<(&'a str, usize)>)>,
+    /// surface forms an entity got from several sources, with these sources
+    /// in the order they were added (the holder first), with
+    /// [`LabelAliasPrecedence::Merge`]
+    pub merged_sources: HashMap<(&'a str, Option<&'a str>), Vec<&'static str>>,
+    pub num_label_unique: usize,
+    pub num_label_info_unique: usize,
+    /// labels with info added to entities with a unique label, with
+    /// `label_info_variants`
+    pub num_label_info_variants: usize,
+    pub num_entities_left: usize,
+    pub num_aliases: usize,
+    pub num_added_aliases: usize,
+    pub num_variant_aliases: usize,
+}
+
+impl<'a> EntityIndexBuilder<'a> {
+    pub fn new(options: EntityIndexOptions) -> Self {
+        Self {
+            options,
+            entities: HashMap::new(),
+            needs_info: Box::new(|_| false),
+            progress: false,
+            alias_priors: false,
+            index: HashMap::new(),
+            variant_aliases: vec![],
+            dropped_aliases: vec![],
+            shared_aliases: vec![],
+            merged_sources: HashMap::new(),
+            num_label_unique: 0,
+            num_label_info_unique: 0,
+            num_label_info_variants: 0,
+            num_entities_left: 0,
+            num_aliases: 0,
+            num_added_aliases: 0,
+            num_variant_aliases: 0,
+        }
+    }
+
+    /// Surface forms for which `needs_info` returns true are only indexed
+    /// with info, e.g. labels that look like ids.
+    pub fn with_needs_info(mut self, needs_info: impl Fn(&str) -> bool + 'a) -> Self {
+        self.needs_info = Box::new(needs_info);
+        self
+    }
+
+    /// Shows progress bars while resolving.
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Records the aliases shared by several entities in `shared_aliases`
+    /// while resolving, before conflicting aliases are dropped or resolved.
+    pub fn with_alias_priors(mut self, alias_priors: bool) -> Self {
+        self.alias_priors = alias_priors;
+        self
+    }
+
+    /// Adds an entity, replacing an earlier one with the same id.
+    pub fn add_entity(&mut self, ent: &'a str, info: EntityInfo<'a>) {
+        self.entities.insert(ent, info);
+    }
+
+    /// Adds an entity by its validated id, see
+    /// [`KnowledgeGraphProcessor::entity_id`].
+    pub fn add_entity_id(&mut self, ent: &'a EntityId, info: EntityInfo<'a>) {
+        self.add_entity(ent.id(), info);
+    }
+
+    pub fn entities(&self) -> &HashMap<&'a str, EntityInfo<'a>> {
+        &self.entities
+    }
+
+    pub fn needs_info(&self, form: &str) -> bool {
+        (self.needs_info)(form)
+    }
+
+    /// The suffix that disambiguates the surface forms of an entity, see
+    /// [`Disambiguation::suffix`].
+    pub fn suffix(&self, ent: &'a str) -> &'a str {
+        self.options.disambiguation.suffix(ent, &self.entities[ent])
+    }
+
+    /// The surface forms (with their info, if any) and their entities.
+    pub fn index(&self) -> &HashMap<(&'a str, Option<&'a str>), Ent<'a>> {
+        &self.index
+    }
+
+    /// Adds a surface form if it is still free, for additional forms with a
+    /// lower priority than all aliases. Returns whether it was added.
+    pub fn add_surface_form(&mut self, form: &'a str, info: Option<&'a str>, ent: Ent<'a>) -> bool {
+        match self.index.entry((form, info)) {
+            Entry::Vacant(entry) => {
+                entry.insert(ent);
+                true
+            }
+            Entry::Occupied(entry) => {
+                if self.options.label_alias_precedence == LabelAliasPrecedence::Merge {
+                    merge_source(&mut self.merged_sources, (form, info), entry.get(), &ent);
+                }
+                false
+            }
+        }
+    }
+
+    /// Assigns the labels and then the aliases of all entities.
+    pub fn resolve(&mut self) {
+        self.resolve_labels();
+        self.add_aliases();
+    }
+
+    /// Assigns the labels of all entities, with info where needed.
+    pub fn resolve_labels(&mut self) {
+        let options = &self.options;
+        let entities = &self.entities;
+        let needs_info = &self.needs_info;
+        let index = &mut self.index;
+        let mut label_to_ents: HashMap<&str, Vec<Ent>> = HashMap::new();
+        let mut aliases_to_ents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (&ent, info) in entities.iter().sorted_by_key(|&(&ent, _)| ent) {
+            label_to_ents
+                .entry(info.label)
+                .or_default()
+                .push(Ent::Label(ent));
+
+            if options.check_for_popular_aliases || self.alias_priors {
+                for &alias in &info.aliases {
+                    aliases_to_ents.entry(alias).or_default().push(ent);
+                }
+            }
+        }
+
+        let mut variant_aliases = vec![];
+        if options.normalize_labels {
+            let mut groups: HashMap<_, Vec<_>> = HashMap::new();
+            for &label in label_to_ents.keys() {
+                groups
+                    .entry(normalize_label(label))
+                    .or_default()
+                    .push(label);
+            }
+            for labels in groups.into_values().filter(|labels| labels.len() > 1) {
+                let winner = labels
+                    .iter()
+                    .flat_map(|label| &label_to_ents[label])
+                    .map(|ent| ent.as_str())
+                    .max_by_key(|&ent| (entities[ent].count, Reverse(ent)))
+                    .unwrap();
+                let winner_label = entities[winner].label;
+                for label in labels {
+                    if label == winner_label {
+                        continue;
+                    }
+                    let ents = label_to_ents.remove(label).unwrap();
+                    label_to_ents.get_mut(winner_label).unwrap().extend(ents);
+                    variant_aliases.push((label, winner));
+                }
+            }
+        }
+
+        if self.alias_priors {
+            self.shared_aliases = aliases_to_ents
+                .iter()
+                .filter(|(_, ents)| ents.len() > 1)
+                .map(|(&alias, ents)| {
+                    let ents = ents
+                        .iter()
+                        .map(|&ent| (ent, entities[ent].count))
+                        .sorted_by_key(|&(ent, count)| (Reverse(count), ent))
+                        .collect();
+                    (alias, ents)
+                })
+                .sorted()
+                .collect();
+        }
+
+        // filter out aliases that are aliases for multiple entities,
+        // or keep only the clearly most popular one if conflicts are resolved
+        aliases_to_ents.retain(|_, ents| {
+            if ents.len() <= 1 {
+                return true;
+            } else if !options.resolve_alias_conflicts {
+                return false;
+            }
+            ents.sort_by_key(|&ent| (Reverse(entities[ent].count), ent));
+            let first = entities[ents[0]].count;
+            let second = entities[ents[1]].count;
+            if first > second && first as f64 >= options.alias_conflict_ratio * second as f64 {
+                ents.truncate(1);
+                true
+            } else {
+                false
+            }
+        });
+
+        // whether another entity with the label as alias is clearly more
+        // popular, so the label should only be indexed with info
+        let has_more_popular_alias = |label: &str, ent: &str| {
+            if !options.check_for_popular_aliases {
+                return false;
+            }
+            let Some(alias_ents) = aliases_to_ents.get(label) else {
+                return false;
+            };
+            let count = entities[ent].count;
+            alias_ents
+                .iter()
+                .filter(|&&alias_ent| alias_ent != ent)
+                .map(|&alias_ent| (entities[alias_ent].count, Reverse(alias_ent)))
+                .max()
+                .is_some_and(|(alias_count, _)| {
+                    alias_count > count
+                        && alias_count as f64 >= options.popular_alias_ratio * count as f64
+                })
+        };
+
+        assert!(label_to_ents.values().map(|ents| ents.len()).sum::<usize>() == entities.len());
+        let mut ents_left: HashSet<&str> = HashSet::new();
+        let mut label_info_to_ents: HashMap<_, Vec<_>> = HashMap::new();
+        let pbar = options.progress_bar(
+            "adding unique labels",
+            label_to_ents.len() as u64,
+            !self.progress,
+        );
+        for (label, ents) in label_to_ents.into_iter().sorted_by_key(|&(label, _)| label) {
+            pbar.inc(1);
+            assert!(!ents.is_empty());
+            if ents.len() <= 1
+                && !needs_info(label)
+                && !has_more_popular_alias(label, ents[0].as_str())
+            {
+                let ent = ents.into_iter().next().unwrap();
+                assert!(index.insert((label, None), ent).is_none());
+                continue;
+            }
+            match options.disambiguation {
+                Disambiguation::Drop => {
+                    ents_left.extend(ents.iter().map(Ent::as_str));
+                    continue;
+                }
+                Disambiguation::MostCommon => {
+                    let ent = ents
+                        .iter()
+                        .map(|ent| ent.as_str())
+                        .max_by_key(|&ent| (entities[ent].count, Reverse(ent)))
+                        .unwrap();
+                    let kept =
+                        ents.len() > 1 && !needs_info(label) && !has_more_popular_alias(label, ent);
+                    if kept {
+                        assert!(index.insert((label, None), Ent::Label(ent)).is_none());
+                    }
+                    ents_left.extend(ents.iter().map(Ent::as_str).filter(|&e| !kept || e != ent));
+                    continue;
+                }
+                Disambiguation::InfoSuffix | Disambiguation::QidSuffix | Disambiguation::All => {}
+            }
+            // if the label alone is not unique, we add the type or
+            // description (or the id) to it and try again
+            for ent in ents {
+                let ent_info = &entities[ent.as_str()];
+                let info = options.disambiguation.suffix(ent.as_str(), ent_info);
+                if info.is_empty() {
+                    continue;
+                }
+                let label_info = format!("{label} ({info})");
+                if index.contains_key(&(label_info.as_str(), None)) {
+                    continue;
+                }
+                label_info_to_ents
+                    .entry((label, info))
+                    .or_default()
+                    .push((ent_info.count, ent));
+            }
+        }
+        self.num_label_unique = index.len();
+        pbar.finish_with_counts(&[("label_unique", self.num_label_unique)]);
+
+        let pbar = options.progress_bar(
+            "adding label-info pairs",
+            label_info_to_ents.len() as u64,
+            !self.progress,
+        );
+        for ((label, info), mut ents) in
+            label_info_to_ents.into_iter().sorted_by_key(|(key, ents)| {
+                let max = ents.iter().map(|(c, _)| c).max().copied().unwrap_or(0);
+                (Reverse(max), ents.len(), *key)
+            })
+        {
+            pbar.inc(1);
+            let ent = if ents.len() <= 1 {
+                ents.pop().unwrap().1.as_str()
+            } else if options.disambiguation == Disambiguation::All {
+                // most popular last, ties go to the smallest id
+                ents.sort_by_key(|(c, ent)| (*c, Reverse(ent.as_str())));
+                let ent = ents.pop().unwrap().1.as_str();
+                // if the label and type/description are not unique
+                // record the entities with no entry for statistics
+                ents_left.extend(ents.iter().map(|(_, e)| e.as_str()));
+                ent
+            } else {
+                ents_left.extend(ents.iter().map(|(_, e)| e.as_str()));
+                continue;
+            };
+            if index.contains_key(&(label, None))
+                || has_more_popular_alias(label, ent)
+                || needs_info(label)
+            {
+                assert!(index
+                    .insert((label, Some(info)), Ent::LabelInfo(ent))
+                    .is_none());
+            } else {
+                assert!(index.insert((label, None), Ent::Label(ent)).is_none());
+            }
+        }
+        self.num_label_info_unique = index.len();
+        pbar.finish_with_counts(&[
+            ("label_info_unique", self.num_label_info_unique),
+            ("entities_left", ents_left.len()),
+        ]);
+
+        // entities with their label alone also get it with info, for models
+        // that always generate the disambiguated form
+        if options.label_info_variants {
+            let variants: Vec<_> = index
+                .iter()
+                .filter_map(|(&(label, info), ent)| match ent {
+                    Ent::Label(ent) if info.is_none() => Some((label, *ent)),
+                    _ => None,
+                })
+                .collect();
+            for (label, ent) in variants {
+                let info = options.disambiguation.suffix(ent, &entities[ent]);
+                if info.is_empty() {
+                    continue;
+                }
+                if let Entry::Vacant(entry) = index.entry((label, Some(info))) {
+                    entry.insert(Ent::LabelInfo(ent));
+                    self.num_label_info_variants += 1;
+                }
+            }
+        }
+        self.num_entities_left = ents_left.len();
+        self.variant_aliases = variant_aliases;
+    }
+
+    /// Adds the variant labels of `normalize_labels` and then the aliases,
+    /// so one entity can be found by several names. Call after
+    /// [`resolve_labels`](Self::resolve_labels).
+    pub fn add_aliases(&mut self) {
+        let disambiguation = self.options.disambiguation;
+        let merge = self.options.label_alias_precedence == LabelAliasPrecedence::Merge;
+        let entities = &self.entities;
+        let needs_info = &self.needs_info;
+        let index = &mut self.index;
+        let dropped_aliases = &mut self.dropped_aliases;
+        let merged_sources = &mut self.merged_sources;
+        // variant forms of normalized labels come first, they belong to the
+        // winner
+        for (alias, ent) in std::mem::take(&mut self.variant_aliases) {
+            if let Entry::Vacant(entry) = index.entry((alias, None)) {
+                entry.insert(Ent::Alias(ent));
+                self.num_variant_aliases += 1;
+            }
+        }
+        let pbar =
+            self.options
+                .progress_bar("adding aliases", entities.len() as u64, !self.progress);
+        for (&ent, info) in entities
+            .iter()
+            .sorted_by_key(|&(key, info)| (Reverse(info.count), key))
+        {
+            pbar.inc(1);
+            self.num_aliases += info.aliases.len();
+            let suffix = disambiguation.suffix(ent, info);
+            for &alias in &info.aliases {
+                if needs_info(alias) {
+                    if suffix.is_empty() {
+                        // the holder is the id the alias would shadow
+                        dropped_aliases.push((ent, alias, "id-like", alias));
+                        continue;
+                    }
+                    match index.entry((alias, Some(suffix))) {
+                        Entry::Vacant(entry) => {
+                            entry.insert(Ent::AliasInfo(ent));
+                        }
+                        Entry::Occupied(entry) if merge && entry.get().as_str() == ent => {
+                            let key = (alias, Some(suffix));
+                            merge_source(merged_sources, key, entry.get(), &Ent::AliasInfo(ent));
+                        }
+                        Entry::Occupied(_) => {}
+                    }
+                    continue;
+                }
+                let holder = match index.entry((alias, None)) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(Ent::Alias(ent));
+                        continue;
+                    }
+                    Entry::Occupied(entry) => entry.get().clone(),
+                };
+                if holder.as_str() == ent && merge {
+                    merge_source(merged_sources, (alias, None), &holder, &Ent::Alias(ent));
+                }
+                let holder = holder.as_str();
+                if suffix.is_empty() {
+                    // the entity itself already has the bare form
+                    if holder != ent {
+                        dropped_aliases.push((ent, alias, "ambiguous", holder));
+                    }
+                    continue;
+                }
+                match index.entry((alias, Some(suffix))) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(Ent::AliasInfo(ent));
+                    }
+                    Entry::Occupied(entry) if entry.get().as_str() != ent => {
+                        dropped_aliases.push((
+                            ent,
+                            alias,
+                            "ambiguous with info",
+                            entry.get().as_str(),
+                        ));
+                    }
+                    // e.g. the alias Paris of the entity with the label
+                    // Paris (city)
+                    Entry::Occupied(entry) if merge => {
+                        let key = (alias, Some(suffix));
+                        merge_source(merged_sources, key, entry.get(), &Ent::AliasInfo(ent));
+                    }
+                    Entry::Occupied(_) => {}
+                }
+            }
+        }
+        self.num_added_aliases =
+            index.len() - self.num_label_info_unique - self.num_label_info_variants;
+        pbar.finish_with_counts(&[
+            ("aliases", self.num_aliases),
+            ("added_aliases", self.num_added_aliases),
+        ]);
+    }
+
+    /// The rows of the index by descending popularity and then id: every
+    /// entity with its surface forms and their kind, first its label (with
+    /// or without info), then its aliases without and with info, and then
+    /// the labels of redirects, each alphabetically.
+    pub fn rows(&self) -> Vec<(&'a str, Vec<(String, FormKind)>)> {
+        let mut forms: HashMap<_, Vec<_>> = HashMap::new();
+        for (&(form, info), ent) in &self.index {
+            forms
+                .entry(ent.as_str())
+                .or_default()
+                .push((ent, form, info));
+        }
+        let mut forms: Vec<_> = forms.into_iter().collect();
+        forms.sort_by_key(|&(ent, _)| (Reverse(self.entities[ent].count), ent));
+
+        let escaping = self.options.escaping;
+        let label_info_variants = self.options.label_info_variants;
+        let pbar =
+            self.options
+                .progress_bar("creating outputs", forms.len() as u64, !self.progress);
+        let rows = forms
+            .into_par_iter()
+            .map(|(ent, mut forms)| {
+                pbar.inc(1);
+                let num_labels = forms
+                    .iter()
+                    .filter(|(ent, ..)| matches!(ent, Ent::Label(_)))
+                    .count();
+                let num_label_infos = forms
+                    .iter()
+                    .filter(|(ent, ..)| matches!(ent, Ent::LabelInfo(_)))
+                    .count();
+                // both only with label_info_variants
+                let max_label_infos = if label_info_variants {
+                    1
+                } else {
+                    1 - num_labels.min(1)
+                };
+                assert!(
+                    num_labels <= 1 && num_label_infos <= max_label_infos,
+                    "expected either an original label or a label + info for {ent}"
+                );
+                // labels, labels with info, aliases, aliases with info, and
+                // redirect labels
+                let group = |ent: &Ent, info: Option<&str>| match ent {
+                    Ent::Label(_) | Ent::LabelInfo(_) => info.is_some() as u8,
+                    Ent::Alias(_) | Ent::AliasInfo(_) => 2 + info.is_some() as u8,
+                    Ent::Redirect(_) => 4,
+                };
+                forms.sort_by_key(|&(ent, form, info)| (group(ent, info), form, info));
+                let row = forms
+                    .iter()
+                    .map(|&(ent, form, info)| {
+                        (format_surface_form(form, info, escaping), ent.kind())
+                    })
+                    .collect();
+                (ent, row)
+            })
+            .collect();
+        pbar.finish_and_clear();
+        rows
+    }
+
+    /// Writes the rows of the index as TSV, every entity followed by its
+    /// surface forms.
+    pub fn write_tsv(&self, file: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut output = record_writer(file, FileFormat::Tsv)?;
+        for (ent, row) in self.rows() {
+            output.write_record(
+                std::iter::once(ent).chain(row.iter().map(|(form, _)| form.as_str())),
+            )?;
+        }
+        output.flush()?;
+        Ok(())
+    }
+}
+
+/// Records that a surface form of an entity also comes from another of its
+/// sources. Forms held by another entity are not merged.
+fn merge_source<'a>(
+    merged_sources: &mut HashMap<(&'a str, Option<&'a str>), Vec<&'static str>>,
+    key: (&'a str, Option<&'a str>),
+    holder: &Ent<'a>,
+    ent: &Ent<'a>,
+) {
+    if holder.as_str() != ent.as_str() || holder.source() == ent.source() {
+        return;
+    }
+    let sources = merged_sources
+        .entry(key)
+        .or_insert_with(|| vec![holder.source()]);
+    if !sources.contains(&ent.source()) {
+        sources.push(ent.source());
+    }
+}
+
+/// Name of the file in an output directory that holds the dump date.
+pub const DUMP_DATE_FILE: &str = "dump-date.txt";
+
+/// Checks that a dump date has the form YYYY-MM-DD.
+pub fn check_dump_date(date: &str) -> anyhow::Result<()> {
+    if !Regex::new(r"^\d{4}-\d{2}-\d{2}$")?.is_match(date) {
+        bail!("invalid dump date {date}, expected YYYY-MM-DD");
+    }
+    Ok(())
+}
+
+/// Labels an output directory with the date (YYYY-MM-DD) of the dump it was
+/// built from.
+pub fn write_dump_date(out: &OutputDir, date: &str) -> anyhow::Result<()> {
+    check_dump_date(date)?;
+    fs::write(out.path(DUMP_DATE_FILE), format!("{date}\n"))?;
+    Ok(())
+}
+
+/// Reads the dump date of an output directory, if it has one.
+pub fn read_dump_date(dir: impl AsRef<Path>) -> anyhow::Result<Option<String>> {
+    let path = dir.as_ref().join(DUMP_DATE_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(fs::read_to_string(path)?.trim().to_string()))
+}
+
+/// Iterates over the lines of a file, compressed files are decompressed on
+/// the fly, see [`open_input`].
 pub fn line_iter(
     file: impl AsRef<Path>,
 ) -> anyhow::Result<impl Iterator<Item = anyhow::Result<String>>> {
-    let file = fs::File::open(file)?;
-    let file = BufReader::new(file);
+    let file = open_input(file)?;
     Ok(file.lines().map(|line| line.map_err(anyhow::Error::from)))
 }
 
-pub fn progress_bar(msg: &str, size: u64, hidden: bool) -> ProgressBar {
-    let pb = ProgressBar::new(size)
-        .with_style(
-            ProgressStyle::with_template(
-                "{msg}: {wide_bar} [{pos}/{len}] [{elapsed_precise}|{eta_precise}]",
-            )
-            .unwrap(),
+/// Compression of an input file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression from the magic bytes at the start of the
+    /// file, or from its extension (gz, bz2, or zst) if it is too short.
+    pub fn detect(file: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = file.as_ref();
+        let mut magic = vec![];
+        fs::File::open(file)?.take(4).read_to_end(&mut magic)?;
+        Ok(match magic[..] {
+            [0x1f, 0x8b, ..] => Self::Gzip,
+            [b'B', b'Z', b'h', ..] => Self::Bzip2,
+            [0x28, 0xb5, 0x2f, 0xfd] => Self::Zstd,
+            _ if magic.len() < 4 => match file.extension().and_then(|ext| ext.to_str()) {
+                Some("gz") => Self::Gzip,
+                Some("bz2") => Self::Bzip2,
+                Some("zst") => Self::Zstd,
+                _ => Self::None,
+            },
+            _ => Self::None,
+        })
+    }
+}
+
+/// Counts the bytes read from the inner reader.
+struct CountingReader<R> {
+    inner: R,
+    bytes_read: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes_read.fetch_add(n as u64, AtomicOrdering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Opens a file for reading. Gzip, bzip2, and zstd compressed files (e.g.
+/// `entities.tsv.gz` or `entities.tsv.zst`) are decompressed on the fly.
+pub fn open_input(file: impl AsRef<Path>) -> anyhow::Result<Box<dyn BufRead>> {
+    open_counted(file, Arc::default())
+}
+
+fn open_counted(
+    file: impl AsRef<Path>,
+    bytes_read: Arc<AtomicU64>,
+) -> anyhow::Result<Box<dyn BufRead>> {
+    let compression = Compression::detect(&file)?;
+    let reader = CountingReader {
+        inner: fs::File::open(file)?,
+        bytes_read,
+    };
+    Ok(match compression {
+        Compression::None => Box::new(BufReader::new(reader)),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Bzip2 => Box::new(BufReader::new(MultiBzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::new(reader)?)),
+    })
+}
+
+/// Creates the progress of a stage, see [`progress::Progress`].
+pub fn progress_bar(msg: &str, size: u64, hidden: bool) -> Progress {
+    Progress::new(msg, size, hidden)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity<'a>(
+        label: &'a str,
+        desc: &'a str,
+        aliases: &[&'a str],
+        count: usize,
+    ) -> EntityInfo<'a> {
+        EntityInfo {
+            label,
+            desc,
+            aliases: aliases.to_vec(),
+            types: Arc::new(Mutex::new(vec![])),
+            count,
+            redirects: None,
+        }
+    }
+
+    fn build<'a>(
+        options: EntityIndexOptions,
+        entities: Vec<(&'a str, EntityInfo<'a>)>,
+    ) -> EntityIndexBuilder<'a> {
+        let mut builder = EntityIndexBuilder::new(options);
+        for (ent, info) in entities {
+            builder.add_entity(ent, info);
+        }
+        builder.resolve();
+        builder
+    }
+
+    fn rows(builder: &EntityIndexBuilder) -> Vec<(String, Vec<String>)> {
+        builder
+            .rows()
+            .into_iter()
+            .map(|(ent, row)| {
+                let forms = row.into_iter().map(|(form, _)| form).collect();
+                (ent.to_string(), forms)
+            })
+            .collect()
+    }
+
+    fn row(ent: &str, forms: &[&str]) -> (String, Vec<String>) {
+        (
+            ent.to_string(),
+            forms.iter().map(|form| form.to_string()).collect(),
         )
-        .with_message(msg.to_string());
-    if hidden {
-        pb.set_draw_target(ProgressDrawTarget::hidden());
     }
-    pb
+
+    #[test]
+    fn unique_labels_have_no_info() {
+        let builder = build(
+            EntityIndexOptions::default(),
+            vec![
+                ("Q1", entity("Paris", "capital of France", &[], 10)),
+                ("Q2", entity("Berlin", "capital of Germany", &[], 20)),
+            ],
+        );
+        assert_eq!(builder.num_label_unique, 2);
+        assert_eq!(
+            rows(&builder),
+            [row("Q2", &["Berlin"]), row("Q1", &["Paris"])]
+        );
+    }
+
+    #[test]
+    fn shared_labels_get_info() {
+        let builder = build(
+            EntityIndexOptions::default(),
+            vec![
+                ("Q1", entity("Paris", "capital of France", &[], 10)),
+                ("Q2", entity("Paris", "son of Priam", &[], 5)),
+                ("Q3", entity("Paris", "", &[], 1)),
+            ],
+        );
+        // the most popular entity gets the label alone, which is still free,
+        // the entity without info is left out
+        assert_eq!(builder.num_label_unique, 0);
+        assert_eq!(builder.num_label_info_unique, 2);
+        assert_eq!(
+            rows(&builder),
+            [row("Q1", &["Paris"]), row("Q2", &["Paris (son of Priam)"])]
+        );
+    }
+
+    #[test]
+    fn most_common_non_unique_is_kept() {
+        let entities = || {
+            vec![
+                ("Q1", entity("Springfield", "city", &[], 10)),
+                ("Q2", entity("Springfield", "city", &[], 20)),
+            ]
+        };
+        let builder = build(EntityIndexOptions::default(), entities());
+        assert!(builder.index().is_empty());
+        assert_eq!(builder.num_entities_left, 2);
+
+        let options = EntityIndexOptions {
+            disambiguation: Disambiguation::All,
+            ..Default::default()
+        };
+        let builder = build(options, entities());
+        // the label alone is free, so the winner gets it without info
+        assert_eq!(rows(&builder), [row("Q2", &["Springfield"])]);
+        assert_eq!(builder.num_entities_left, 1);
+    }
+
+    #[test]
+    fn disambiguation_strategies() {
+        let entities = || {
+            vec![
+                ("Q1", entity("Paris", "city", &[], 100)),
+                ("Q2", entity("Paris", "city", &[], 10)),
+                ("Q3", entity("Paris", "myth", &[], 1)),
+            ]
+        };
+        let build_with = |disambiguation| {
+            let options = EntityIndexOptions {
+                disambiguation,
+                ..Default::default()
+            };
+            build(options, entities())
+        };
+
+        let builder = build_with(Disambiguation::Drop);
+        assert!(builder.index().is_empty());
+        assert_eq!(builder.num_entities_left, 3);
+
+        let builder = build_with(Disambiguation::MostCommon);
+        assert_eq!(rows(&builder), [row("Q1", &["Paris"])]);
+        assert_eq!(builder.num_entities_left, 2);
+
+        let builder = build_with(Disambiguation::InfoSuffix);
+        assert_eq!(rows(&builder), [row("Q3", &["Paris"])]);
+
+        let builder = build_with(Disambiguation::QidSuffix);
+        assert_eq!(
+            rows(&builder),
+            [
+                row("Q1", &["Paris"]),
+                row("Q2", &["Paris (Q2)"]),
+                row("Q3", &["Paris (Q3)"]),
+            ]
+        );
+        assert_eq!(builder.num_entities_left, 0);
+
+        let builder = build_with(Disambiguation::All);
+        assert_eq!(
+            rows(&builder),
+            [row("Q1", &["Paris"]), row("Q3", &["Paris (myth)"])]
+        );
+    }
+
+    #[test]
+    fn taken_aliases_get_info() {
+        let builder = build(
+            EntityIndexOptions::default(),
+            vec![
+                (
+                    "Q1",
+                    entity("New York City", "city", &["New York", "NYC"], 20),
+                ),
+                ("Q2", entity("New York", "state", &["NY"], 10)),
+                ("Q3", entity("Big Apple", "", &["NYC"], 1)),
+            ],
+        );
+        assert_eq!(
+            rows(&builder),
+            [
+                row("Q1", &["New York City", "NYC", "New York (city)"]),
+                row("Q2", &["New York", "NY"]),
+                row("Q3", &["Big Apple"]),
+            ]
+        );
+        assert_eq!(builder.dropped_aliases, [("Q3", "NYC", "ambiguous", "Q1")]);
+        assert_eq!(builder.num_aliases, 4);
+        assert_eq!(builder.num_added_aliases, 3);
+    }
+
+    #[test]
+    fn popular_aliases_take_labels() {
+        let entities = || {
+            vec![
+                ("Q1", entity("United States", "country", &["America"], 100)),
+                ("Q2", entity("America", "song", &[], 1)),
+            ]
+        };
+        let builder = build(EntityIndexOptions::default(), entities());
+        assert_eq!(
+            rows(&builder),
+            [
+                row("Q1", &["United States", "America (country)"]),
+                row("Q2", &["America"]),
+            ]
+        );
+
+        let options = EntityIndexOptions {
+            check_for_popular_aliases: true,
+            ..Default::default()
+        };
+        let builder = build(options, entities());
+        assert_eq!(
+            rows(&builder),
+            [
+                row("Q1", &["United States", "America"]),
+                row("Q2", &["America (song)"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unique_labels_get_label_info_variants() {
+        let options = EntityIndexOptions {
+            label_info_variants: true,
+            ..Default::default()
+        };
+        let builder = build(
+            options,
+            vec![
+                ("Q1", entity("Paris", "city", &[], 100)),
+                ("Q2", entity("Paris", "myth", &[], 10)),
+                ("Q3", entity("Berlin", "city", &[], 50)),
+            ],
+        );
+        assert_eq!(
+            rows(&builder),
+            [
+                row("Q1", &["Paris", "Paris (city)"]),
+                row("Q3", &["Berlin", "Berlin (city)"]),
+                row("Q2", &["Paris (myth)"]),
+            ]
+        );
+        assert_eq!(builder.num_label_info_variants, 2);
+    }
+
+    #[test]
+    fn progress_listener_gets_the_stage_counts() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let listener = {
+            let events = events.clone();
+            SharedListener::new(move |event: &progress::ProgressEvent| {
+                events.lock().unwrap().push(event.clone())
+            })
+        };
+        let options = EntityIndexOptions {
+            progress_listener: Some(listener),
+            ..Default::default()
+        };
+        build(
+            options,
+            vec![
+                ("Q1", entity("Paris", "city", &["City of Light"], 100)),
+                ("Q2", entity("Paris", "myth", &[], 10)),
+            ],
+        );
+        let finished: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                progress::ProgressEvent::StageFinished { stage, counts, .. } => {
+                    Some((stage.clone(), counts.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        let counts = |stage: &str| {
+            finished
+                .iter()
+                .find(|(s, _)| s == stage)
+                .map(|(_, counts)| counts.clone())
+                .unwrap()
+        };
+        assert_eq!(counts("adding unique labels"), [("label_unique".into(), 0)]);
+        assert_eq!(
+            counts("adding aliases"),
+            [("aliases".into(), 1), ("added_aliases".into(), 1)]
+        );
+    }
+
+    #[test]
+    fn label_alias_collisions_are_merged() {
+        // Q1 gets the label alone, so its alias is its label, and the alias
+        // of Q2 can only be added with info, which is its label with info
+        let entities = || {
+            vec![
+                ("Q1", entity("Paris", "city", &["Paris"], 100)),
+                ("Q2", entity("Paris", "myth", &["Paris"], 10)),
+            ]
+        };
+        let builder = build(EntityIndexOptions::default(), entities());
+        assert!(builder.merged_sources.is_empty());
+        let options = EntityIndexOptions {
+            label_alias_precedence: LabelAliasPrecedence::Merge,
+            ..Default::default()
+        };
+        let builder = build(options, entities());
+        assert_eq!(
+            rows(&builder),
+            [
+                row("Q1", &["Paris", "Paris (city)"]),
+                row("Q2", &["Paris (myth)"])
+            ]
+        );
+        assert_eq!(
+            builder.merged_sources,
+            HashMap::from([
+                (("Paris", None), vec!["label", "alias"]),
+                (("Paris", Some("myth")), vec!["label", "alias"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn id_like_forms_need_info() {
+        let mut builder = EntityIndexBuilder::new(EntityIndexOptions::default())
+            .with_needs_info(|form| form.starts_with('Q'));
+        builder.add_entity("Q1", entity("Q7", "band", &[], 1));
+        builder.resolve();
+        assert_eq!(rows(&builder), [row("Q1", &["Q7 (band)"])]);
+        assert!(!builder.add_surface_form("Q7", Some("band"), Ent::Alias("Q2")));
+    }
+
+    #[test]
+    fn writes_tsv() {
+        let builder = build(
+            EntityIndexOptions::default(),
+            vec![("Q1", entity("Paris", "", &["City of Light"], 1))],
+        );
+        let file = std::env::temp_dir().join(format!("entity-index-{}.tsv", std::process::id()));
+        builder.write_tsv(&file).unwrap();
+        let content = fs::read_to_string(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+        assert_eq!(content, "Q1\tParis\tCity of Light\n");
+    }
+
+    #[test]
+    fn escaped_forms_round_trip() {
+        let forms = [
+            ("Up (film)", Some("2009 film")),
+            ("C:\\Windows\tfolder", None),
+            ("line\nbreak", Some("info (with parens)")),
+            ("Foo (", None),
+        ];
+        for (label, info) in forms {
+            let form = format_surface_form(label, info, SurfaceFormEscaping::Escape);
+            assert!(!form.contains(['\t', '\n']));
+            assert_eq!(
+                parse_surface_form(&form, SurfaceFormEscaping::Escape),
+                (label.to_string(), info.map(str::to_string))
+            );
+        }
+        let form = format_surface_form("Up (film)", Some("a\tb"), SurfaceFormEscaping::Sanitize);
+        assert_eq!(form, "Up [film] (a b)");
+        assert_eq!(
+            parse_surface_form(&form, SurfaceFormEscaping::Sanitize),
+            ("Up [film]".to_string(), Some("a b".to_string()))
+        );
+    }
+
+    #[test]
+    fn long_labels_are_truncated() {
+        let label = "A study of the effects of light";
+        assert_eq!(truncate_label(label, Some(40), None), None);
+        assert_eq!(truncate_label(label, Some(13), None), Some("A study of"));
+        assert_eq!(
+            truncate_label(label, Some(14), None),
+            Some("A study of the")
+        );
+        assert_eq!(truncate_label(label, None, Some(3)), Some("A study of"));
+        assert_eq!(truncate_label(label, Some(20), Some(2)), Some("A study"));
+        assert_eq!(
+            truncate_label("Supercalifragilistic", Some(5), None),
+            Some("Super")
+        );
+    }
+
+    #[test]
+    fn titles_split_into_name_and_qualifier() {
+        let split = |name: &str, qualifier: &str| Some((name.to_string(), qualifier.to_string()));
+        assert_eq!(
+            title_qualifier("Paris_(mythology)"),
+            split("Paris", "mythology")
+        );
+        assert_eq!(
+            title_qualifier("Caf%C3%A9_(band)_(album)"),
+            split("Café (band)", "album")
+        );
+        assert_eq!(title_qualifier("Berlin,_New_Hampshire"), None);
+        assert_eq!(title_qualifier("(Untitled)"), None);
+    }
+
+    #[test]
+    fn id_map_numbers_do_not_depend_on_the_order_of_use() {
+        let numbers = |used: &[&str]| {
+            let mut ids = IdMap::new(IdFormat::Numeric);
+            ids.number(["P2", "P10", "P31"].map(String::from));
+            used.iter()
+                .map(|&id| ids.map(id.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(&["P31", "P2", "P10"]), ["2", "0", "1"]);
+        assert_eq!(numbers(&["P10", "P31", "P2"]), ["1", "2", "0"]);
+        // ids that were not numbered before get the next number
+        assert_eq!(numbers(&["P5", "P2"]), ["3", "0"]);
+        let mut ids = IdMap::new(IdFormat::Kg);
+        ids.number(["P2".to_string()]);
+        assert_eq!(ids.map("P2".to_string()), "P2");
+        assert_eq!(ids.iter().count(), 0);
+    }
+
+    #[test]
+    fn property_parse_counts_are_returned() {
+        let kg = KnowledgeGraphProcessor::new(KnowledgeGraph::Wikidata).unwrap();
+        let line = "<http://www.wikidata.org/entity/P31>\t\"instance of\"@en\t100\t\
+            \"is a;;type\"\t<http://www.wikidata.org/entity/P2>;invalid";
+        let (prop, info, counts) = kg.parse_property(line).unwrap();
+        assert_eq!(prop.as_str(), "http://www.wikidata.org/entity/P31");
+        assert_eq!(info.aliases, ["is a", "type"]);
+        assert_eq!(info.inverses, ["http://www.wikidata.org/entity/P2"]);
+        assert_eq!(
+            counts,
+            PropertyParseCounts {
+                empty_aliases: 1,
+                invalid_inverses: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn freebase_property_ids_end_with_the_iri() {
+        let kg = KnowledgeGraphProcessor::new(KnowledgeGraph::Freebase).unwrap();
+        let id = "http://rdf.freebase.com/ns/people.person.spouse_s";
+        assert_eq!(kg.parse_property_id(&format!("<{id}>")), Some(id));
+        assert_eq!(kg.parse_property_id(id), Some(id));
+        // as in a triple, up to the closing bracket only
+        assert_eq!(
+            kg.parse_property_id(&format!("<{id}> \"Spouse\"@en .")),
+            Some(id)
+        );
+    }
+
+    #[test]
+    fn conflicting_inverse_claims_are_reported() {
+        let prop = |inverses: &[&'static str]| PropInfo {
+            label: String::new(),
+            aliases: vec![],
+            inverses: inverses.to_vec(),
+            parents: vec![],
+            count: 0,
+            translations: HashMap::new(),
+        };
+        // P1 and P2 both claim P3, P4 alone claims P5, and P6 claims P7,
+        // which declares P8
+        let mut props = HashMap::from([
+            ("P1", prop(&["P3"])),
+            ("P2", prop(&["P3"])),
+            ("P3", prop(&[])),
+            ("P4", prop(&["P5"])),
+            ("P5", prop(&[])),
+            ("P6", prop(&["P7"])),
+            ("P7", prop(&["P8"])),
+            ("P8", prop(&["P7"])),
+        ]);
+        let (added, conflicts) = symmetrize_inverses(&mut props);
+        assert_eq!(added, 1);
+        assert_eq!(conflicts, [("P1", "P3"), ("P2", "P3"), ("P6", "P7")]);
+        assert!(props["P3"].inverses.is_empty());
+        assert_eq!(props["P5"].inverses, ["P4"]);
+        assert_eq!(props["P7"].inverses, ["P8"]);
+    }
+    #[test]
+    fn english_number_variants() {
+        let variants = [
+            ("programming language", Some("programming languages")),
+            ("programming languages", Some("programming language")),
+            ("city", Some("cities")),
+            ("cities", Some("city")),
+            ("movies", Some("movie")),
+            ("houses", Some("house")),
+            ("databases", Some("database")),
+            ("horses", Some("horse")),
+            ("classes", Some("class")),
+            ("class", Some("classes")),
+            ("boxes", Some("box")),
+            ("churches", Some("church")),
+            ("analysis", Some("analyses")),
+            ("analyses", Some("analysis")),
+            ("virus", Some("viruses")),
+            ("television series", None),
+            ("species", None),
+            ("news", None),
+            ("means", None),
+            ("causes", None),
+            ("Paris", None),
+        ];
+        for (label, variant) in variants {
+            assert_eq!(english_number_variant(label).as_deref(), variant, "{label}");
+        }
+    }
+    #[test]
+    fn label_templates_are_filled_in_one_pass() {
+        // the placeholder in the label is not filled with the type
+        let values = [("label", "the {type} award"), ("type", "film")];
+        assert_eq!(
+            fill_label_template("{label} ({type})", &values),
+            "the {type} award (film)"
+        );
+        assert_eq!(
+            fill_label_template("{type}: {unknown} {label", &values),
+            "film: {unknown} {label"
+        );
+    }
+    #[test]
+    fn ids_are_validated() {
+        let kg = KnowledgeGraphProcessor::new(KnowledgeGraph::Wikidata).unwrap();
+        let ent = kg
+            .entity_id("<http://www.wikidata.org/entity/Q42>")
+            .unwrap();
+        assert_eq!(ent.id(), "Q42");
+        assert_eq!(ent.numeric(), Some(42));
+        assert_eq!(ent.prefixed(), "wd:Q42");
+        assert_eq!(ent.iri(), "http://www.wikidata.org/entity/Q42");
+        assert!(kg.entity_id("Douglas Adams").is_err());
+        let prop = kg
+            .property_id("<http://www.wikidata.org/entity/P31>")
+            .unwrap();
+        assert_eq!(prop.numeric(), Some(31));
+        assert_eq!(prop.prefixed(), "wdt:P31");
+        assert!(kg.property_id("instance of").is_err());
+
+        // maps keyed by ids are queried with parsed ids
+        let labels = HashMap::from([(ent.clone(), "Douglas Adams")]);
+        assert_eq!(labels.get("Q42"), Some(&"Douglas Adams"));
+        let mut builder = EntityIndexBuilder::new(EntityIndexOptions::default());
+        builder.add_entity_id(&ent, entity("Douglas Adams", "human", &[], 1));
+        assert!(builder.entities().contains_key("Q42"));
+        let mut ids = IdMap::new(IdFormat::Kg);
+        assert_eq!(ids.map_entity(&ent, true), "wd:Q42");
+        assert_eq!(
+            ids.map_property(&prop, false),
+            "http://www.wikidata.org/prop/direct/P31"
+        );
+    }
 }
@@ -0,0 +1,149 @@
+use anyhow::anyhow;
+use regex::Regex;
+
+/// What kg-entities does with labels and aliases that are literals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LiteralLabels {
+    /// they stay in the index
+    #[default]
+    Keep,
+    /// literal aliases are removed, entities with a literal label left out
+    Exclude,
+    /// like exclude, but they are written to literals.tsv
+    Separate,
+}
+
+impl TryFrom<&str> for LiteralLabels {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "keep" => LiteralLabels::Keep,
+            "exclude" => LiteralLabels::Exclude,
+            "separate" => LiteralLabels::Separate,
+            _ => return Err(anyhow!("invalid literal label handling {}", value)),
+        })
+    }
+}
+
+/// Kind of literal a label consists of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiteralKind {
+    Number,
+    Year,
+    Date,
+}
+
+impl LiteralKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LiteralKind::Number => "number",
+            LiteralKind::Year => "year",
+            LiteralKind::Date => "date",
+        }
+    }
+}
+
+/// Month names and their abbreviations in English, German, French, and
+/// Spanish.
+const MONTHS: &str = "january|february|march|april|may|june|july|august|september|october|\
+    november|december|jan|feb|mar|apr|jun|jul|aug|sep|sept|oct|nov|dec|januar|februar|märz|\
+    mai|juni|juli|oktober|dezember|jänner|janvier|février|mars|avril|juin|juillet|août|\
+    septembre|octobre|novembre|décembre|janv|févr|avr|juil|déc|enero|febrero|marzo|abril|\
+    mayo|junio|julio|agosto|septiembre|setiembre|octubre|noviembre|diciembre|ene|abr|ago|dic";
+
+/// Eras after or before a year, e.g. "79 AD", "44 v. Chr.", "52 av. J.-C.",
+/// or "218 a. C.".
+const ERAS: &str =
+    r"bc|bce|ad|ce|v\.\s?chr\.|n\.\s?chr\.|av\.\s?j\.-c\.|apr\.\s?j\.-c\.|a\.\s?c\.|d\.\s?c\.";
+
+/// Detects labels that are purely a number, a year, or a date, in the
+/// notations of English, German, French, and Spanish. Such labels, like
+/// "1984", "1.000.000", or "11. September 2001", are better parsed as
+/// literals than looked up as entities.
+pub struct LiteralDetector {
+    number: Regex,
+    year: Regex,
+    dates: Vec<Regex>,
+}
+
+impl LiteralDetector {
+    pub fn new() -> anyhow::Result<Self> {
+        // thousands separated by commas, dots, spaces, or apostrophes, and
+        // decimals by dots or commas
+        let number =
+            Regex::new(r"^[+\-−]?(\d{1,3}([,.'’ \u{a0}\u{202f}]\d{3})+|\d+)([.,]\d+)?(\s?%)?$")?;
+        let year = Regex::new(&format!(
+            r"(?i)^(\d{{1,4}}\s?({ERAS})|(ad|ce)\s?\d{{1,4}}|\d{{4}}|\d{{3}}0\s?'?s|\d{{3}}0er)$"
+        ))?;
+        let dates = [
+            r"^\d{4}-\d{1,2}-\d{1,2}$".to_string(),
+            r"^\d{4}/\d{1,2}/\d{1,2}$".to_string(),
+            r"^\d{1,2}[./-]\d{1,2}[./-]\d{2}(\d{2})?$".to_string(),
+            // 11 September 2001, 11. September 2001, 1er janvier 2000,
+            // 12 de octubre de 1492, or without the year
+            format!(
+                r"(?i)^\d{{1,2}}(st|nd|rd|th|er|º|\.)?\s(de\s)?({MONTHS})\.?(,?\s(de\s)?\d{{1,4}})?$"
+            ),
+            // September 11, 2001, September 11th, or September 2001
+            format!(r"(?i)^({MONTHS})\.?\s\d{{1,2}}(st|nd|rd|th)?(,?\s\d{{1,4}})?$"),
+            format!(r"(?i)^({MONTHS})\.?\s(de\s)?\d{{4}}$"),
+        ]
+        .iter()
+        .map(|pattern| Regex::new(pattern))
+        .collect::<Result<_, _>>()?;
+        Ok(Self {
+            number,
+            year,
+            dates,
+        })
+    }
+
+    /// The kind of literal the label consists of, none if it is more than a
+    /// number, year, or date. Four-digit numbers are taken as years.
+    pub fn detect(&self, label: &str) -> Option<LiteralKind> {
+        let label = label.trim();
+        if self.year.is_match(label) {
+            Some(LiteralKind::Year)
+        } else if self.dates.iter().any(|date| date.is_match(label)) {
+            Some(LiteralKind::Date)
+        } else if self.number.is_match(label) {
+            Some(LiteralKind::Number)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_labels_are_detected() {
+        let detector = LiteralDetector::new().unwrap();
+        let kinds = [
+            ("1984", Some(LiteralKind::Year)),
+            ("44 v. Chr.", Some(LiteralKind::Year)),
+            ("AD 79", Some(LiteralKind::Year)),
+            ("1990s", Some(LiteralKind::Year)),
+            ("2001-09-11", Some(LiteralKind::Date)),
+            ("11.09.2001", Some(LiteralKind::Date)),
+            ("11. September 2001", Some(LiteralKind::Date)),
+            ("September 11, 2001", Some(LiteralKind::Date)),
+            ("12 de octubre de 1492", Some(LiteralKind::Date)),
+            ("1er janvier 2000", Some(LiteralKind::Date)),
+            ("1,000,000", Some(LiteralKind::Number)),
+            ("1.000.000", Some(LiteralKind::Number)),
+            ("3,14", Some(LiteralKind::Number)),
+            ("42", Some(LiteralKind::Number)),
+            ("September", None),
+            ("Apollo 11", None),
+            ("2001: A Space Odyssey", None),
+            ("Q42", None),
+        ];
+        for (label, kind) in kinds {
+            assert_eq!(detector.detect(label), kind, "{label}");
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::stats::{Stats, StatsFormat};
+use crate::{
+    line_iter, record_iter, record_writer, FileFormat, KnowledgeGraph, KnowledgeGraphProcessor,
+};
+
+/// Arguments of kg-index mapping, see [`run`].
+#[derive(clap::Args, Debug)]
+#[clap(about = None, long_about = None)]
+pub struct MappingArgs {
+    /// output directory of a Freebase entity index
+    #[clap(long)]
+    freebase: PathBuf,
+
+    /// output directory of a Wikidata entity index
+    #[clap(long)]
+    wikidata: PathBuf,
+
+    /// the published mapping between Freebase and Wikidata, either as
+    /// N-Triples of owl:sameAs statements (fb2w.nt) or as a table of
+    /// Wikidata ids and their Freebase id (P646, e.g. /m/02mjmr)
+    #[clap(long)]
+    mapping: PathBuf,
+
+    /// where to write the mapping between the two indices
+    #[clap(short, long)]
+    output: PathBuf,
+
+    /// format of the indices and the output (tsv or csv)
+    #[clap(long, default_value = "tsv")]
+    format: String,
+
+    /// format of the statistics printed at the end (text or json)
+    #[clap(long, default_value = "text")]
+    stats_format: String,
+}
+
+impl MappingArgs {
+    pub fn stats_format(&self) -> anyhow::Result<StatsFormat> {
+        StatsFormat::try_from(self.stats_format.as_str())
+    }
+}
+
+/// Loads the preferred (first) surface form of every entity in an index,
+/// by entity id. Ids may be iris or prefixed.
+fn load_index(
+    kg: &KnowledgeGraphProcessor,
+    dir: &Path,
+    format: FileFormat,
+) -> anyhow::Result<HashMap<String, String>> {
+    let (short_pfx, _) = kg.entity_prefixes()[0];
+    let mut index = HashMap::new();
+    for record in record_iter(dir.join(format.file_name("index")), format)? {
+        let record = record?;
+        let (Some(ent), Some(form)) = (record.get(0), record.get(1)) else {
+            continue;
+        };
+        let Some(id) = kg
+            .parse_entity_id(ent)
+            .or_else(|| ent.strip_prefix(short_pfx))
+        else {
+            continue;
+        };
+        index
+            .entry(id.to_string())
+            .or_insert_with(|| form.to_string());
+    }
+    Ok(index)
+}
+
+/// Parses a Freebase id of the mapping, an iri or a P646 value like
+/// /m/02mjmr.
+fn freebase_id(freebase: &KnowledgeGraphProcessor, s: &str) -> Option<String> {
+    if let Some(id) = freebase.parse_entity_id(s) {
+        return Some(id.to_string());
+    }
+    let s = s.trim_matches('"');
+    let (kind, id) = s.strip_prefix('/')?.split_once('/')?;
+    if !matches!(kind, "m" | "g") || id.is_empty() || id.contains('/') {
+        return None;
+    }
+    Some(format!("{kind}.{id}"))
+}
+
+/// Parses a Wikidata id of the mapping, an iri or a bare id like Q76.
+fn wikidata_id<'s>(wikidata: &KnowledgeGraphProcessor, s: &'s str) -> Option<&'s str> {
+    wikidata.parse_entity_id(s).or_else(|| {
+        let s = s.trim_matches('"');
+        s.strip_prefix('Q')
+            .is_some_and(|num| !num.is_empty() && num.bytes().all(|b| b.is_ascii_digit()))
+            .then_some(s)
+    })
+}
+
+/// Connects a Freebase and a Wikidata entity index with the published
+/// mapping between Freebase mids and Wikidata qids, so that entity linkers
+/// trained on Freebase data can be evaluated against Wikidata. Writes every
+/// pair of the mapping whose entities are in both indices, with the
+/// Freebase entity, the Wikidata entity, and their preferred surface forms.
+pub fn run(args: MappingArgs) -> anyhow::Result<Stats> {
+    let format = FileFormat::try_from(args.format.as_str())?;
+    let freebase = KnowledgeGraphProcessor::new(KnowledgeGraph::Freebase)?;
+    let wikidata = KnowledgeGraphProcessor::new(KnowledgeGraph::Wikidata)?;
+    let freebase_index = load_index(&freebase, &args.freebase, format)?;
+    let wikidata_index = load_index(&wikidata, &args.wikidata, format)?;
+
+    let mut pairs = HashSet::new();
+    let mut num_invalid = 0;
+    for line in line_iter(&args.mapping)? {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // the order of the columns differs between the mapping files
+        let fields: Vec<_> = line
+            .split(['\t', ' ', ','])
+            .filter(|field| !field.is_empty())
+            .collect();
+        let mid = fields
+            .iter()
+            .find_map(|field| freebase_id(&freebase, field));
+        let qid = fields
+            .iter()
+            .find_map(|field| wikidata_id(&wikidata, field));
+        let (Some(mid), Some(qid)) = (mid, qid) else {
+            num_invalid += 1;
+            continue;
+        };
+        pairs.insert((mid, qid.to_string()));
+    }
+
+    let mut num_missing_freebase = 0;
+    let mut num_missing_wikidata = 0;
+    let mut mapped = vec![];
+    for (mid, qid) in pairs.into_iter().sorted() {
+        let Some(freebase_form) = freebase_index.get(&mid) else {
+            num_missing_freebase += 1;
+            continue;
+        };
+        let Some(wikidata_form) = wikidata_index.get(&qid) else {
+            num_missing_wikidata += 1;
+            continue;
+        };
+        mapped.push((mid, qid, freebase_form, wikidata_form));
+    }
+
+    let mut output = record_writer(&args.output, format)?;
+    for (mid, qid, freebase_form, wikidata_form) in &mapped {
+        output.write_record([
+            freebase.format_entity(mid, false).as_str(),
+            wikidata.format_entity(qid, false).as_str(),
+            freebase_form,
+            wikidata_form,
+        ])?;
+    }
+    output.flush()?;
+
+    let num_mids = mapped.iter().map(|(mid, ..)| mid).unique().count();
+    let num_qids = mapped.iter().map(|(_, qid, ..)| qid).unique().count();
+    let mut stats = Stats::new("freebase to wikidata mapping");
+    stats.count("invalid_lines", num_invalid);
+    stats.count("mapped_pairs", mapped.len());
+    stats.count("mapped_freebase_entities", num_mids);
+    stats.count("mapped_wikidata_entities", num_qids);
+    stats.count("not_in_freebase_index", num_missing_freebase);
+    stats.count("not_in_wikidata_index", num_missing_wikidata);
+    stats.percent("freebase_coverage", num_mids, freebase_index.len());
+
+    Ok(stats)
+}
@@ -0,0 +1,133 @@
+use std::borrow::Cow;
+
+use anyhow::anyhow;
+use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+/// Unicode normalization form of a [`Normalizer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnicodeForm {
+    /// canonical composition, e.g. "e" followed by a combining acute accent
+    /// becomes "é"
+    Nfc,
+    /// compatibility composition, which also folds ligatures, full-width
+    /// letters, and superscripts, e.g. "ﬁ" becomes "fi"
+    Nfkc,
+}
+
+impl TryFrom<&str> for UnicodeForm {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "nfc" => UnicodeForm::Nfc,
+            "nfkc" => UnicodeForm::Nfkc,
+            _ => return Err(anyhow!("invalid unicode normalization form {}", value)),
+        })
+    }
+}
+
+/// Characters without width that are invisible in a label, like zero-width
+/// spaces and joiners or the byte order mark.
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{2060}' | '\u{feff}' | '\u{00ad}'
+    )
+}
+
+/// Cleans labels so that visually identical ones are also equal: normalizes
+/// them to a unicode form, removes zero-width and control characters, and
+/// collapses whitespace.
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+    pub form: Option<UnicodeForm>,
+    /// replace runs of whitespace (including non-breaking spaces) with one
+    /// space and trim the ends
+    pub collapse_whitespace: bool,
+    /// remove zero-width spaces, joiners, and soft hyphens
+    pub remove_zero_width: bool,
+    /// remove control characters, tabs and line breaks become spaces
+    pub strip_control: bool,
+}
+
+impl Normalizer {
+    /// A normalizer to the given form with all cleaning steps.
+    pub fn new(form: Option<UnicodeForm>) -> Self {
+        Self {
+            form,
+            collapse_whitespace: true,
+            remove_zero_width: true,
+            strip_control: true,
+        }
+    }
+
+    fn is_normalized(&self, s: &str) -> bool {
+        let in_form = match self.form {
+            None => true,
+            Some(UnicodeForm::Nfc) => is_nfc_quick(s.chars()) == IsNormalized::Yes,
+            Some(UnicodeForm::Nfkc) => is_nfkc_quick(s.chars()) == IsNormalized::Yes,
+        };
+        in_form
+            && !(self.remove_zero_width && s.chars().any(is_zero_width))
+            && !(self.strip_control && s.chars().any(char::is_control))
+            // single spaces between words only
+            && !(self.collapse_whitespace
+                && !s.is_empty()
+                && s.split(' ').any(|word| word.is_empty() || word.contains(char::is_whitespace)))
+    }
+
+    /// The normalized string, borrowed if it is already normalized, which is
+    /// the case for most labels.
+    pub fn normalize<'s>(&self, s: &'s str) -> Cow<'s, str> {
+        if self.is_normalized(s) {
+            return Cow::Borrowed(s);
+        }
+        let mut normalized: String = match self.form {
+            None => s.to_string(),
+            Some(UnicodeForm::Nfc) => s.nfc().collect(),
+            Some(UnicodeForm::Nfkc) => s.nfkc().collect(),
+        };
+        if self.remove_zero_width {
+            normalized.retain(|c| !is_zero_width(c));
+        }
+        if self.strip_control {
+            normalized = normalized
+                .chars()
+                .filter_map(|c| match c {
+                    '\t' | '\n' | '\r' => Some(' '),
+                    c if c.is_control() => None,
+                    c => Some(c),
+                })
+                .collect();
+        }
+        if self.collapse_whitespace {
+            normalized = normalized.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        Cow::Owned(normalized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_normalized() {
+        let normalizer = Normalizer::new(Some(UnicodeForm::Nfc));
+        assert!(matches!(normalizer.normalize("Café"), Cow::Borrowed(_)));
+        assert_eq!(normalizer.normalize("Cafe\u{301}"), "Café");
+        assert_eq!(
+            normalizer.normalize(" Ber\u{200b}lin\u{a0} \tcity\u{7}"),
+            "Berlin city"
+        );
+        assert_eq!(normalizer.normalize("\u{fb01}sh"), "\u{fb01}sh");
+        let normalizer = Normalizer::new(Some(UnicodeForm::Nfkc));
+        assert_eq!(normalizer.normalize("\u{fb01}sh"), "fish");
+        // only the form, whitespace stays
+        let normalizer = Normalizer {
+            form: Some(UnicodeForm::Nfc),
+            ..Default::default()
+        };
+        assert_eq!(normalizer.normalize("Cafe\u{301}  Paris"), "Café  Paris");
+    }
+}
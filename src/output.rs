@@ -0,0 +1,221 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use anyhow::bail;
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+
+/// Lists the files of a build with their number of lines and SHA-256
+/// checksum, one per line.
+pub const MANIFEST_FILE: &str = "manifest.tsv";
+
+/// Holds the fingerprint of the inputs of the build in an output directory,
+/// see [`input_fingerprint`].
+pub const FINGERPRINT_FILE: &str = "fingerprint.txt";
+
+/// Output directory of an index build. It refuses to reuse the directory of
+/// a previous build unless forced, and records the files written to it for
+/// the manifest.
+pub struct OutputDir {
+    dir: PathBuf,
+    files: Mutex<Vec<String>>,
+    fingerprint: Option<String>,
+}
+
+impl OutputDir {
+    /// Creates the directory. If it holds a previous build (a manifest or an
+    /// index file), this fails unless `force` is set, in which case the files
+    /// listed in the previous manifest are removed first.
+    pub fn create(dir: impl AsRef<Path>, force: bool) -> anyhow::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let manifest = dir.join(MANIFEST_FILE);
+        let has_index = ["tsv", "csv", "jsonl"]
+            .iter()
+            .any(|ext| dir.join(format!("index.{ext}")).exists());
+        if manifest.exists() || has_index {
+            if !force {
+                bail!(
+                    "output directory {} already contains a build, pass --force to overwrite it",
+                    dir.display()
+                );
+            }
+            if manifest.exists() {
+                for name in read_manifest(&dir)? {
+                    let path = dir.join(name);
+                    if path.is_file() {
+                        fs::remove_file(path)?;
+                    }
+                }
+                fs::remove_file(&manifest)?;
+            }
+            // an interrupted build must not look up to date
+            let fingerprint = dir.join(FINGERPRINT_FILE);
+            if fingerprint.exists() {
+                fs::remove_file(fingerprint)?;
+            }
+        }
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            files: Mutex::new(vec![]),
+            fingerprint: None,
+        })
+    }
+
+    /// Writes the fingerprint of the inputs together with the manifest, so a
+    /// later build with the same inputs can be skipped.
+    pub fn with_fingerprint(mut self, fingerprint: String) -> Self {
+        self.fingerprint = Some(fingerprint);
+        self
+    }
+
+    /// Whether the directory holds a complete build with the given input
+    /// fingerprint, i.e. a manifest whose files all still have their
+    /// checksums.
+    pub fn is_up_to_date(dir: impl AsRef<Path>, fingerprint: &str) -> anyhow::Result<bool> {
+        let dir = dir.as_ref();
+        let Ok(previous) = fs::read_to_string(dir.join(FINGERPRINT_FILE)) else {
+            return Ok(false);
+        };
+        if previous.trim() != fingerprint || !dir.join(MANIFEST_FILE).is_file() {
+            return Ok(false);
+        }
+        for line in fs::read_to_string(dir.join(MANIFEST_FILE))?.lines() {
+            let Some((name, _, checksum)) = line.split('\t').collect_tuple() else {
+                return Ok(false);
+            };
+            let path = dir.join(name);
+            if !path.is_file() || rows_and_checksum(&path)?.1 != checksum {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of an output file, which is listed in the manifest.
+    pub fn path(&self, name: &str) -> PathBuf {
+        let mut files = self.files.lock().unwrap();
+        if !files.iter().any(|file| file == name) {
+            files.push(name.to_string());
+        }
+        self.dir.join(name)
+    }
+
+    /// Writes the manifest of all output files that exist, sorted by name,
+    /// and returns their number.
+    pub fn write_manifest(&self) -> anyhow::Result<usize> {
+        let mut manifest = fs::File::create(self.dir.join(MANIFEST_FILE))?;
+        let files = self.files.lock().unwrap();
+        let mut num_files = 0;
+        for name in files.iter().sorted() {
+            let path = self.dir.join(name);
+            if !path.is_file() {
+                continue;
+            }
+            let (rows, checksum) = rows_and_checksum(&path)?;
+            writeln!(manifest, "{name}\t{rows}\t{checksum}")?;
+            num_files += 1;
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            fs::write(self.dir.join(FINGERPRINT_FILE), format!("{fingerprint}\n"))?;
+        }
+        Ok(num_files)
+    }
+}
+
+/// SHA-256 of the parsed arguments of a build (e.g. their `Debug` form)
+/// together with the size and modification time of every input file, like
+/// the timestamps make compares, and the contents of every input directory,
+/// so it changes whenever an option or an input changes.
+pub fn input_fingerprint(args: &str, inputs: &[&Path]) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(args.as_bytes());
+    for input in inputs {
+        hasher.update([0]);
+        let Ok(path) = input.canonicalize() else {
+            // a missing input, e.g. a cache that is not written yet
+            hasher.update(input.as_os_str().as_encoded_bytes());
+            continue;
+        };
+        hasher.update(path.as_os_str().as_encoded_bytes());
+        if path.is_dir() {
+            hash_dir(&mut hasher, &path)?;
+        } else {
+            let metadata = fs::metadata(&path)?;
+            let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+            hasher.update(metadata.len().to_le_bytes());
+            hasher.update(modified.as_nanos().to_le_bytes());
+        }
+    }
+    Ok(hex(hasher.finalize().as_slice()))
+}
+
+/// Hashes the names and contents of the files in a directory and its
+/// subdirectories, in order of their names. The fingerprint of a previous
+/// build is left out, since it is rewritten by the build.
+fn hash_dir(hasher: &mut Sha256, dir: &Path) -> anyhow::Result<()> {
+    let entries: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<anyhow::Result<_>>()?;
+    for path in entries.into_iter().sorted() {
+        if path
+            .file_name()
+            .is_some_and(|name| name == FINGERPRINT_FILE)
+        {
+            continue;
+        }
+        hasher.update(path.as_os_str().as_encoded_bytes());
+        if path.is_dir() {
+            hash_dir(hasher, &path)?;
+        } else {
+            hasher.update(rows_and_checksum(&path)?.1.as_bytes());
+        }
+    }
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).join("")
+}
+
+/// Names of the files listed in the manifest of an output directory.
+pub fn read_manifest(dir: impl AsRef<Path>) -> anyhow::Result<Vec<String>> {
+    let file = fs::File::open(dir.as_ref().join(MANIFEST_FILE))?;
+    BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let line = line?;
+            let Some((name, _)) = line.split_once('\t') else {
+                bail!("invalid manifest line: {line}");
+            };
+            if name.contains('/') || name.contains('\\') || name == ".." {
+                bail!("invalid file name in manifest: {name}");
+            }
+            Ok(name.to_string())
+        })
+        .collect()
+}
+
+fn rows_and_checksum(path: &Path) -> anyhow::Result<(usize, String)> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut rows = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        rows += buf.iter().filter(|&&b| b == b'\n').count();
+        hasher.update(buf);
+        let len = buf.len();
+        reader.consume(len);
+    }
+    Ok((rows, hex(hasher.finalize().as_slice())))
+}
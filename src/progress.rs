@@ -0,0 +1,213 @@
+use std::fmt;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// Records between two `StageProgress` events of a stage.
+pub const PROGRESS_EVENT_INTERVAL: u64 = 10_000;
+
+/// Progress of the processing stages, for wrappers that render progress
+/// themselves instead of the indicatif bars.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent {
+    /// `total` is None if the number of records is not known in advance
+    StageStarted { stage: String, total: Option<u64> },
+    StageProgress {
+        stage: String,
+        done: u64,
+        total: Option<u64>,
+    },
+    /// `peak_rss` is the largest resident set size in bytes sampled
+    /// during the stage, if memory sampling is enabled, and `counts` are
+    /// the statistics of the stage, e.g. the number of added aliases
+    StageFinished {
+        stage: String,
+        done: u64,
+        elapsed: Duration,
+        peak_rss: Option<u64>,
+        counts: Vec<(String, usize)>,
+    },
+}
+
+pub trait ProgressListener: Send + Sync {
+    fn on_event(&self, event: &ProgressEvent);
+}
+
+impl<F> ProgressListener for F
+where
+    F: Fn(&ProgressEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &ProgressEvent) {
+        self(event)
+    }
+}
+
+/// A listener shared by the stages of a build, e.g. a closure forwarding
+/// the events to a channel, see
+/// [`EntityIndexOptions::progress_listener`](crate::EntityIndexOptions).
+#[derive(Clone)]
+pub struct SharedListener(Arc<dyn ProgressListener>);
+
+impl SharedListener {
+    pub fn new(listener: impl ProgressListener + 'static) -> Self {
+        Self(Arc::new(listener))
+    }
+}
+
+impl fmt::Debug for SharedListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedListener")
+    }
+}
+
+static MEMORY_SAMPLING: AtomicBool = AtomicBool::new(false);
+static STAGE_MEMORY: Mutex<Vec<(String, u64)>> = Mutex::new(vec![]);
+
+/// Samples the resident set size at the start and end of every stage and
+/// every `PROGRESS_EVENT_INTERVAL` records (only supported on Linux).
+pub fn enable_memory_sampling() {
+    MEMORY_SAMPLING.store(true, Ordering::Relaxed);
+}
+
+/// Peak resident set size in bytes of every finished stage, in the order
+/// the stages finished.
+pub fn stage_memory() -> Vec<(String, u64)> {
+    STAGE_MEMORY.lock().unwrap().clone()
+}
+
+/// Current (VmRSS) or peak (VmHWM) resident set size of the process in
+/// bytes, read from /proc/self/status.
+pub fn resident_set_size(peak: bool) -> Option<u64> {
+    let key = if peak { "VmHWM:" } else { "VmRSS:" };
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kb: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix(key))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+fn sample_memory(peak_rss: &AtomicU64) {
+    if MEMORY_SAMPLING.load(Ordering::Relaxed) {
+        if let Some(rss) = resident_set_size(false) {
+            peak_rss.fetch_max(rss, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A processing stage, drawn as indicatif bar and reported to its progress
+/// listener if it has one.
+pub struct Progress {
+    bar: ProgressBar,
+    stage: String,
+    total: Option<u64>,
+    done: AtomicU64,
+    start: Instant,
+    peak_rss: AtomicU64,
+    listener: Option<SharedListener>,
+}
+
+impl Progress {
+    /// Use u64::MAX as size if the number of records is unknown.
+    pub fn new(stage: &str, size: u64, hidden: bool) -> Self {
+        Self::with_listener(stage, size, hidden, None)
+    }
+
+    /// Like [`Progress::new`], also reporting the stage to a listener.
+    pub fn with_listener(
+        stage: &str,
+        size: u64,
+        hidden: bool,
+        listener: Option<SharedListener>,
+    ) -> Self {
+        let bar = ProgressBar::new(size)
+            .with_style(
+                ProgressStyle::with_template(
+                    "{msg}: {wide_bar} [{pos}/{len}] [{elapsed_precise}|{eta_precise}]",
+                )
+                .unwrap(),
+            )
+            .with_message(stage.to_string());
+        if hidden {
+            bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        let total = if size == u64::MAX { None } else { Some(size) };
+        let peak_rss = AtomicU64::new(0);
+        sample_memory(&peak_rss);
+        let progress = Self {
+            bar,
+            stage: stage.to_string(),
+            total,
+            done: AtomicU64::new(0),
+            start: Instant::now(),
+            peak_rss,
+            listener,
+        };
+        progress.emit(|| ProgressEvent::StageStarted {
+            stage: stage.to_string(),
+            total,
+        });
+        progress
+    }
+
+    fn emit(&self, event: impl FnOnce() -> ProgressEvent) {
+        if let Some(SharedListener(listener)) = &self.listener {
+            listener.on_event(&event());
+        }
+    }
+
+    pub fn inc(&self, n: u64) {
+        self.bar.inc(n);
+        let before = self.done.fetch_add(n, Ordering::Relaxed);
+        let done = before + n;
+        if before / PROGRESS_EVENT_INTERVAL != done / PROGRESS_EVENT_INTERVAL {
+            sample_memory(&self.peak_rss);
+            self.emit(|| ProgressEvent::StageProgress {
+                stage: self.stage.clone(),
+                done,
+                total: self.total,
+            });
+        }
+    }
+
+    pub fn wrap_iter<'a, I: Iterator + 'a>(
+        &'a self,
+        iter: I,
+    ) -> impl Iterator<Item = I::Item> + 'a {
+        iter.inspect(|_| self.inc(1))
+    }
+
+    pub fn finish_and_clear(&self) {
+        self.finish_with_counts(&[]);
+    }
+
+    /// Finishes the stage and reports its statistics to the listener.
+    pub fn finish_with_counts(&self, counts: &[(&str, usize)]) {
+        self.bar.finish_and_clear();
+        sample_memory(&self.peak_rss);
+        let peak_rss = match self.peak_rss.load(Ordering::Relaxed) {
+            0 => None,
+            rss => Some(rss),
+        };
+        if let Some(rss) = peak_rss {
+            STAGE_MEMORY.lock().unwrap().push((self.stage.clone(), rss));
+        }
+        self.emit(|| ProgressEvent::StageFinished {
+            stage: self.stage.clone(),
+            done: self.done.load(Ordering::Relaxed),
+            elapsed: self.start.elapsed(),
+            peak_rss,
+            counts: counts
+                .iter()
+                .map(|&(name, count)| (name.to_string(), count))
+                .collect(),
+        });
+    }
+}
@@ -0,0 +1,961 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::bail;
+use itertools::Itertools;
+use serde_json::json;
+
+use crate::cli::{BuildArgs, IoArgs};
+use crate::output::OutputDir;
+use crate::progress::enable_memory_sampling;
+use crate::stats::{StageTimer, Stats};
+use crate::{
+    alias_quality, check_dump_date, map_header_columns, normalize_label, progress_bar,
+    property_language_columns, record_iter, record_writer, report_header_problems, sample_records,
+    select_columns, symmetrize_inverses, wikidata_qualifier_groups, write_dump_date, FileFormat,
+    IdFormat, IdMap, KnowledgeGraph, KnowledgeGraphProcessor, Prop, PropInfo, PropertyId,
+    PropertyParseCounts, DEFAULT_DBPEDIA_ONTOLOGY_LABEL, DEFAULT_DBPEDIA_PROPERTY_LABEL,
+    DEFAULT_FREEBASE_LABEL, PROPERTY_COLUMNS,
+};
+
+/// Arguments of a property index build, see [`run`].
+#[derive(clap::Args, Debug, Clone)]
+#[clap(about = None, long_about = None)]
+pub struct PropertiesArgs {
+    #[clap(flatten)]
+    pub io: IoArgs,
+
+    #[clap(flatten)]
+    pub build: BuildArgs,
+
+    #[clap(short, long)]
+    inverse_output: Option<PathBuf>,
+
+    #[clap(long)]
+    inverse_jsonl: bool,
+
+    #[clap(long)]
+    symmetric_inverses: bool,
+
+    #[clap(short, long)]
+    no_aliases: bool,
+
+    /// Table of subproperty and parent property pairs (e.g. from P1647); the
+    /// labels and aliases of a parent are added to its direct subproperties
+    #[clap(long)]
+    subproperties: Option<PathBuf>,
+
+    /// Table of question templates per property id, with * as fallback for all
+    /// other properties; {label} is replaced by the property label
+    #[clap(long)]
+    question_templates: Option<PathBuf>,
+
+    /// Write one index per additional language (index.<lang>.tsv) instead of
+    /// merging all languages into the main index
+    #[clap(long)]
+    split_languages: bool,
+
+    #[clap(short, long)]
+    short_properties: bool,
+
+    #[clap(long)]
+    include_wikidata_qualifiers: bool,
+
+    /// Output ids as given by the knowledge graph (kg) or as consecutive
+    /// numbers (numeric), which are mapped back in id-map.tsv
+    #[clap(long, default_value = "kg")]
+    id_format: String,
+
+    /// label template of dbpedia ontology (dbo) properties, {label} is
+    /// replaced by the property label
+    #[clap(long, default_value = DEFAULT_DBPEDIA_ONTOLOGY_LABEL)]
+    dbpedia_ontology_label: String,
+
+    /// label template of dbpedia property (dbp) properties
+    #[clap(long, default_value = DEFAULT_DBPEDIA_PROPERTY_LABEL)]
+    dbpedia_property_label: String,
+
+    /// label template of freebase properties, with {label} and the {type},
+    /// {domain}, or {path} segments of the property path, e.g. {label} or
+    /// {label} ({path})
+    #[clap(long, default_value = DEFAULT_FREEBASE_LABEL)]
+    freebase_label: String,
+
+    /// also write an index of the domains and types in the paths of freebase
+    /// properties (types.tsv), e.g. people and people.person, labeled by
+    /// their path segments
+    #[clap(long)]
+    freebase_types: bool,
+
+    /// drop aliases with a quality below this value (between 0 and 1, based
+    /// on how distinct, different from the label, and specific they are) and
+    /// write the quality of all aliases to alias-quality.tsv
+    #[clap(long)]
+    min_alias_quality: Option<f64>,
+
+    /// id map of a previous build, whose numbers are kept for the same ids
+    /// (requires --id-format numeric)
+    #[clap(long)]
+    id_map: Option<PathBuf>,
+}
+
+impl PropertiesArgs {
+    /// The input files besides --file that must exist.
+    fn input_files(&self) -> impl Iterator<Item = &PathBuf> {
+        [&self.subproperties, &self.question_templates, &self.id_map]
+            .into_iter()
+            .flatten()
+    }
+}
+
+/// Builds the property index and returns the statistics of the build, or
+/// None if --skip-unchanged found the index up to date. Batches of several
+/// knowledge graphs are built with [`run_batch`](crate::batch::run_batch).
+pub fn run(args: PropertiesArgs) -> anyhow::Result<Option<Stats>> {
+    if args.build.memory_stats {
+        enable_memory_sampling();
+    }
+    let mut kg = args.build.processor()?;
+    kg.set_dbpedia_label_templates(&args.dbpedia_ontology_label, &args.dbpedia_property_label)?;
+    kg.set_freebase_label_template(&args.freebase_label)?;
+    if args.freebase_types && kg.kg != KnowledgeGraph::Freebase {
+        bail!("--freebase-types requires --knowledge-base freebase");
+    }
+    let input_format = args.io.input_format()?;
+    let output_format = args.io.output_format()?;
+    if let Some(date) = &args.build.dump_date {
+        check_dump_date(date)?;
+    }
+    let id_format = IdFormat::try_from(args.id_format.as_str())?;
+    if args.id_map.is_some() && id_format != IdFormat::Numeric {
+        bail!("--id-map requires --id-format numeric");
+    }
+    if args.build.dry_run {
+        return dry_run(&args, &kg, input_format).map(Some);
+    }
+    // load the previous id map before --force removes it
+    let mut ids = IdMap::new(id_format);
+    if let Some(file) = &args.id_map {
+        ids.load_previous(file)?;
+    }
+    let fingerprint_args = PropertiesArgs {
+        io: args.io.without_run_options(),
+        build: args.build.without_run_options(),
+        ..args.clone()
+    };
+    let inputs: Vec<_> = std::iter::once(&args.io.file)
+        .chain(args.input_files())
+        .map(PathBuf::as_path)
+        .collect();
+    let Some(out) = args
+        .build
+        .output_dir(&args.io.output, &fingerprint_args, &inputs)?
+    else {
+        return Ok(None);
+    };
+    let mut timer = StageTimer::new();
+    timer.start("loading");
+
+    let mut records = record_iter(&args.io.file, input_format)?;
+
+    let header = records.next().expect("file should have at least 1 line")?;
+    let header: Vec<_> = header.iter().collect();
+    let (columns, problems) = map_header_columns(&header, &PROPERTY_COLUMNS);
+    report_header_problems(&args.io.file, &problems, args.build.strict)?;
+    if columns[0].is_none() || columns[1].is_none() {
+        bail!("property file needs an id and a label column");
+    }
+    let language_columns = property_language_columns(&header);
+    // optional column of parent properties, separated by semicolons
+    let parents_column = header.iter().position(|h| {
+        h.trim()
+            .trim_start_matches('?')
+            .eq_ignore_ascii_case("p_parents")
+    });
+
+    let mut prop_infos = HashMap::new();
+    let mut num_invalid_properties = 0;
+    let mut parse_counts = PropertyParseCounts::default();
+
+    let records: Vec<_> = records.collect::<anyhow::Result<_>>()?;
+    timer.start("parsing");
+    let pbar = progress_bar(
+        "processing wikidata properties",
+        records.len() as u64,
+        !args.io.progress,
+    );
+    for record in &records {
+        pbar.inc(1);
+        let fields: Vec<_> = record.iter().collect();
+        let selected = select_columns(record, &columns);
+        let Ok((prop, mut info, counts)) = kg.parse_property_fields(&selected) else {
+            // lines with an empty or invalid property id
+            if selected
+                .first()
+                .and_then(|s| kg.parse_property_id(s))
+                .is_none()
+            {
+                num_invalid_properties += 1;
+            }
+            continue;
+        };
+        parse_counts += counts;
+        parse_counts +=
+            kg.parse_property_translations(prop.as_str(), &mut info, &language_columns, &fields)?;
+        if let Some(parents) = parents_column.and_then(|i| fields.get(i)) {
+            info.parents = parents
+                .split_terminator(';')
+                .filter_map(|s| kg.parse_property_id(s))
+                .collect();
+        }
+        prop_infos.insert(prop.as_str(), info);
+    }
+    pbar.finish_and_clear();
+    let num_parsed = prop_infos.len();
+    prop_infos.retain(|_, info| info.count >= args.build.min_count);
+    let num_below_min_count = num_parsed - prop_infos.len();
+
+    timer.start("label assignment");
+    let subproperty_records: Vec<_> = if let Some(path) = &args.subproperties {
+        record_iter(path, input_format)?.collect::<anyhow::Result<_>>()?
+    } else {
+        vec![]
+    };
+    for record in &subproperty_records {
+        // also skips the header
+        let (Some(sub), Some(parent)) = (
+            record.get(0).and_then(|s| kg.parse_property_id(s)),
+            record.get(1).and_then(|s| kg.parse_property_id(s)),
+        ) else {
+            continue;
+        };
+        if let Some(info) = prop_infos.get_mut(sub) {
+            if !info.parents.contains(&parent) {
+                info.parents.push(parent);
+            }
+        }
+    }
+    // parents below --min-count or missing from the property file are
+    // dropped, like parents of the subproperty file
+    let known: HashSet<_> = prop_infos.keys().copied().collect();
+    for (&prop, info) in prop_infos.iter_mut() {
+        info.parents
+            .retain(|&parent| parent != prop && known.contains(parent));
+    }
+    let has_parents = args.subproperties.is_some() || parents_column.is_some();
+
+    let (num_symmetrized, inverse_conflicts) = if args.symmetric_inverses {
+        symmetrize_inverses(&mut prop_infos)
+    } else {
+        (0, vec![])
+    };
+
+    let alias_qualities = match args.min_alias_quality {
+        Some(min_quality) => score_aliases(&mut prop_infos, min_quality),
+        None => vec![],
+    };
+
+    // differently encoded iris can have the same short form, those
+    // properties keep their full iris
+    let num_short_form_collisions = if args.short_properties {
+        let props: Vec<_> = prop_infos
+            .iter()
+            .flat_map(|(&prop, info)| std::iter::once(prop).chain(info.inverses.iter().copied()))
+            .collect();
+        kg.resolve_short_form_collisions(props)?
+    } else {
+        0
+    };
+
+    let languages: Vec<_> = language_columns
+        .iter()
+        .map(|(lang, ..)| Some(lang.as_str()))
+        .collect();
+    let merged: Vec<_> = std::iter::once(None)
+        .chain(if args.split_languages {
+            vec![]
+        } else {
+            languages.clone()
+        })
+        .collect();
+    let (label_to_prop, num_label_unique) = build_index(&kg, &prop_infos, &merged, args.no_aliases);
+
+    let mut stats = Stats::new(format!("{} properties", args.build.knowledge_base));
+    stats.count("records", records.len());
+    stats.count("properties", prop_infos.len());
+    if args.build.min_count > 0 {
+        stats.count("below_min_count", num_below_min_count);
+    }
+    stats.count("invalid_properties", num_invalid_properties);
+    stats.count("empty_aliases", parse_counts.empty_aliases);
+    stats.count("invalid_inverses", parse_counts.invalid_inverses);
+    if let Some(min_quality) = args.min_alias_quality {
+        stats.count(
+            "low_quality_aliases",
+            alias_qualities
+                .iter()
+                .filter(|&&(.., quality)| quality < min_quality)
+                .count(),
+        );
+    }
+    if args.short_properties {
+        stats.count("short_form_collisions", num_short_form_collisions);
+    }
+    stats.count("languages", languages.len() + 1);
+    stats.count("unique_by_label", num_label_unique);
+    stats.percent("label_coverage", num_label_unique, prop_infos.len());
+    stats.count(
+        "added_aliases",
+        label_to_prop.len().saturating_sub(num_label_unique),
+    );
+    stats.count("index_size", label_to_prop.len());
+    stats.percent(
+        "index_coverage",
+        label_to_prop.values().map(|p| p.as_str()).unique().count(),
+        prop_infos.len(),
+    );
+
+    timer.start("writing");
+    if let Some(date) = &args.build.dump_date {
+        write_dump_date(&out, date)?;
+    }
+
+    let mut templates: HashMap<PropertyId, String> = HashMap::new();
+    let mut default_template = None;
+    if let Some(path) = &args.question_templates {
+        for record in record_iter(path, input_format)? {
+            let record = record?;
+            let (Some(key), Some(template)) = (record.get(0), record.get(1)) else {
+                continue;
+            };
+            if key.trim() == "*" {
+                default_template = Some(template.to_string());
+            } else if let Ok(prop) = kg.property_id(key) {
+                templates.insert(prop, template.to_string());
+            }
+        }
+    }
+    let examples: HashMap<_, _> = prop_infos
+        .iter()
+        .filter_map(|(&prop, info)| {
+            let template = templates.get(prop).or(default_template.as_ref())?;
+            Some((prop, template.replace("{label}", &info.label)))
+        })
+        .collect();
+
+    let ctx = IndexContext {
+        args: &args,
+        kg: &kg,
+        prop_infos: &prop_infos,
+        examples: &examples,
+        out: &out,
+    };
+    // numbered by property id, e.g. P2 before P10
+    ids.number(
+        prop_infos
+            .keys()
+            .sorted_by_key(|&&prop| kg.property_order_key(prop))
+            .map(|prop| kg.format_property(prop, args.short_properties, None))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+    );
+    let output_dict = write_index(&ctx, &label_to_prop, None, &mut ids)?;
+    if has_parents {
+        stats.count(
+            "inherited_labels",
+            output_dict
+                .iter()
+                .flat_map(|(_, labels)| labels)
+                .filter(|l| matches!(l, Prop::Inherited(_)))
+                .count(),
+        );
+    }
+    if args.split_languages {
+        for lang in languages {
+            let (label_to_prop, _) = build_index(&kg, &prop_infos, &[lang], args.no_aliases);
+            let lang = lang.unwrap();
+            write_index(&ctx, &label_to_prop, Some(lang), &mut ids)?;
+            stats.count(&format!("index_size_{lang}"), label_to_prop.len());
+        }
+    }
+
+    // prefixes are always written as a table, also for jsonl outputs
+    let table_format = if output_format == FileFormat::Jsonl {
+        FileFormat::Tsv
+    } else {
+        output_format
+    };
+    let mut prefix_output =
+        record_writer(out.path(&table_format.file_name("prefixes")), table_format)?;
+    for (short, long) in kg.property_prefixes() {
+        prefix_output.write_record([short, long])?;
+    }
+    prefix_output.flush()?;
+
+    if args.min_alias_quality.is_some() {
+        let mut quality_output = record_writer(
+            out.path(&table_format.file_name("alias-quality")),
+            table_format,
+        )?;
+        for &(prop, alias, quality) in alias_qualities.iter().sorted_by(|a, b| {
+            kg.property_order_key(a.0)
+                .cmp(&kg.property_order_key(b.0))
+                .then(b.2.total_cmp(&a.2))
+                .then(a.1.cmp(b.1))
+        }) {
+            let id = ids.map(kg.format_property(prop, args.short_properties, None)?);
+            quality_output.write_record([id.as_str(), alias, &format!("{quality:.3}")])?;
+        }
+        quality_output.flush()?;
+    }
+
+    if has_parents {
+        let mut hierarchy_output =
+            record_writer(out.path(&table_format.file_name("hierarchy")), table_format)?;
+        let mut num_pairs = 0;
+        for (&prop, info) in prop_infos
+            .iter()
+            .sorted_by_key(|&(&prop, _)| kg.property_order_key(prop))
+        {
+            for &parent in info
+                .parents
+                .iter()
+                .sorted_by_key(|&&parent| kg.property_order_key(parent))
+            {
+                hierarchy_output.write_record([
+                    ids.map(kg.format_property(prop, args.short_properties, None)?)
+                        .as_str(),
+                    ids.map(kg.format_property(parent, args.short_properties, None)?)
+                        .as_str(),
+                    info.label.as_str(),
+                    prop_infos[parent].label.as_str(),
+                ])?;
+                num_pairs += 1;
+            }
+        }
+        hierarchy_output.flush()?;
+        stats.count("subproperty_pairs", num_pairs);
+    }
+
+    if args.freebase_types {
+        let types = freebase_type_index(&kg, &prop_infos);
+        let mut type_output =
+            record_writer(out.path(&table_format.file_name("types")), table_format)?;
+        for (id, labels) in &types {
+            let id = kg.format_entity(id, args.short_properties);
+            let labels = labels.iter().map(String::as_str);
+            type_output.write_record(std::iter::once(id.as_str()).chain(labels))?;
+        }
+        type_output.flush()?;
+        let num_domains = types.iter().filter(|(id, _)| !id.contains('.')).count();
+        stats.count("freebase_domains", num_domains);
+        stats.count("freebase_types", types.len() - num_domains);
+    }
+
+    if args.question_templates.is_some() {
+        let mut example_output =
+            record_writer(out.path(&table_format.file_name("examples")), table_format)?;
+        let mut num_examples = 0;
+        for (prop, _) in &output_dict {
+            let Some(example) = examples.get(prop) else {
+                continue;
+            };
+            num_examples += 1;
+            let id = ids.map(kg.format_property(prop, args.short_properties, None)?);
+            example_output.write_record([&id, &prop_infos[prop].label, example])?;
+        }
+        example_output.flush()?;
+        stats.count("examples", num_examples);
+    }
+
+    if let Some(inverse_output) = &args.inverse_output {
+        let label = |p: &str| {
+            prop_infos
+                .get(p)
+                .map(|info| info.label.clone())
+                .unwrap_or_default()
+        };
+        let mut inverses = vec![];
+        for (prop, _) in &output_dict {
+            let info = prop_infos.get(prop).unwrap();
+            for &inv in &info.inverses {
+                inverses.push([
+                    ids.map(kg.format_property(prop, args.short_properties, None)?),
+                    ids.map(kg.format_property(inv, args.short_properties, None)?),
+                    label(prop),
+                    label(inv),
+                ]);
+            }
+        }
+        let num_inverse = inverses.len();
+        if args.inverse_jsonl || output_format == FileFormat::Jsonl {
+            let mut inverse_output = BufWriter::new(File::create(inverse_output)?);
+            for [id, inverse, label, inverse_label] in inverses {
+                writeln!(
+                    inverse_output,
+                    "{}",
+                    json!({
+                        "id": id,
+                        "label": label,
+                        "inverse": inverse,
+                        "inverse_label": inverse_label,
+                    })
+                )?;
+            }
+            inverse_output.flush()?;
+        } else {
+            let mut inverse_output = record_writer(inverse_output, table_format)?;
+            for row in inverses {
+                inverse_output.write_record(row)?;
+            }
+            inverse_output.flush()?;
+        }
+        stats.count("inverses", num_inverse);
+        if args.symmetric_inverses {
+            stats.count("symmetrized_inverses", num_symmetrized);
+            stats.count("inverse_conflicts", inverse_conflicts.len());
+            for (prop, inv) in &inverse_conflicts {
+                let declared = &prop_infos[inv].inverses;
+                let mut others = inverse_conflicts
+                    .iter()
+                    .filter(|&(other, other_inv)| other_inv == inv && other != prop)
+                    .map(|(other, _)| other);
+                if declared.is_empty() {
+                    eprintln!(
+                        "inverse conflict: {prop} -> {inv} (also claimed by {})",
+                        others.join(", ")
+                    );
+                } else {
+                    eprintln!(
+                        "inverse conflict: {prop} -> {inv} (declares {})",
+                        declared.join(", ")
+                    );
+                }
+            }
+        }
+    }
+
+    if id_format == IdFormat::Numeric {
+        let mut id_output =
+            record_writer(out.path(&table_format.file_name("id-map")), table_format)?;
+        for (num, id) in ids.iter() {
+            id_output.write_record([num.to_string().as_str(), id])?;
+        }
+        id_output.flush()?;
+        if args.id_map.is_some() {
+            let (reused, retired, new) = ids.previous_counts();
+            stats.count("reused_ids", reused);
+            stats.count("retired_ids", retired);
+            stats.count("new_ids", new);
+        }
+    }
+
+    out.write_manifest()?;
+    timer.finish();
+    if args.build.memory_stats {
+        stats.memory();
+    }
+    if args.build.timing_stats {
+        stats.timing(&timer);
+    }
+
+    Ok(Some(stats))
+}
+
+/// Checks the inputs and the header of the property file, parses a sample of
+/// its records, and extrapolates the number of records, the memory needed
+/// to hold them, and the size of the index from it.
+fn dry_run(
+    args: &PropertiesArgs,
+    kg: &KnowledgeGraphProcessor,
+    input_format: FileFormat,
+) -> anyhow::Result<Stats> {
+    for path in args.input_files() {
+        if !path.is_file() {
+            bail!("input file {} does not exist", path.display());
+        }
+    }
+
+    let sample = sample_records(&args.io.file, input_format, args.build.dry_run_sample)?;
+    let Some((header, records)) = sample.records.split_first() else {
+        bail!("{} is empty", args.io.file.display());
+    };
+    let header: Vec<_> = header.iter().collect();
+    let (columns, problems) = map_header_columns(&header, &PROPERTY_COLUMNS);
+    report_header_problems(&args.io.file, &problems, args.build.strict)?;
+    if columns[0].is_none() || columns[1].is_none() {
+        bail!("property file needs an id and a label column");
+    }
+
+    let mut num_parsed = 0;
+    let mut memory_bytes = 0;
+    let mut output_bytes = 0;
+    for record in records {
+        // records are kept in memory for the whole build
+        memory_bytes += record.as_byte_record().as_slice().len()
+            + size_of::<usize>() * (record.len() + 1)
+            + size_of::<csv::StringRecord>();
+        let Ok((prop, info, _)) = kg.parse_property_fields(&select_columns(record, &columns))
+        else {
+            continue;
+        };
+        num_parsed += 1;
+        // the property info and its entries in the label map
+        memory_bytes += size_of::<PropInfo>()
+            + size_of::<&str>() * (info.aliases.len() + info.inverses.len())
+            + 2 * size_of::<(String, Prop)>();
+        output_bytes += kg
+            .format_property(prop.as_str(), args.short_properties, None)?
+            .len()
+            + info.label.len()
+            + info.aliases.iter().map(|s| s.len() + 1).sum::<usize>()
+            + 2;
+    }
+    if !records.is_empty() && num_parsed == 0 {
+        bail!(
+            "none of the {} sampled records could be parsed, check --knowledge-base and \
+            --input-format",
+            records.len()
+        );
+    }
+
+    let estimated_records = sample.estimated_records.saturating_sub(1) as usize;
+    let scale = |n: usize| {
+        (n as f64 * estimated_records as f64 / records.len().max(1) as f64).round() as usize
+    };
+    let mut stats = Stats::new(format!(
+        "{} properties (dry run)",
+        args.build.knowledge_base
+    ));
+    stats.count("input_mib", (sample.file_bytes >> 20) as usize);
+    stats.count("sampled_records", records.len());
+    stats.count("parsed_records", num_parsed);
+    stats.percent("parse_rate", num_parsed, records.len());
+    stats.count("estimated_records", estimated_records);
+    stats.count("estimated_properties", scale(num_parsed));
+    stats.count("estimated_memory_mib", scale(memory_bytes) >> 20);
+    stats.count("estimated_index_mib", scale(output_bytes) >> 20);
+    Ok(stats)
+}
+
+/// Builds the label to property index from the labels of the given languages,
+/// None being the primary language. Labels of earlier languages take precedence,
+/// aliases are added if they are unique across all given languages.
+fn build_index<'a>(
+    kg: &KnowledgeGraphProcessor,
+    prop_infos: &HashMap<&'a str, PropInfo<'a>>,
+    languages: &[Option<&str>],
+    no_aliases: bool,
+) -> (HashMap<String, Prop<'a>>, usize) {
+    // sorted by id, so ties in the count go to the smaller id
+    let mut props: Vec<_> = prop_infos.keys().copied().collect();
+    props.sort_by_key(|&p| kg.property_order_key(p));
+
+    let mut label_to_prop = HashMap::new();
+    for &lang in languages {
+        let mut lang_label_to_prop: HashMap<&str, &str> = HashMap::new();
+        for &prop in &props {
+            let info = &prop_infos[prop];
+            let Some((label, _)) = info.labels_in(lang) else {
+                continue;
+            };
+            match lang_label_to_prop.entry(label) {
+                Entry::Occupied(mut e) => {
+                    // the more popular property wins
+                    if info.count > prop_infos[e.get()].count {
+                        e.insert(prop);
+                    }
+                }
+                Entry::Vacant(e) => {
+                    e.insert(prop);
+                }
+            }
+        }
+        for (label, prop) in lang_label_to_prop {
+            label_to_prop
+                .entry(label.to_string())
+                .or_insert(Prop::Label(prop));
+        }
+    }
+    let num_label_unique = label_to_prop.len();
+    if no_aliases {
+        return (label_to_prop, num_label_unique);
+    }
+
+    let prop_aliases: Vec<_> = props
+        .iter()
+        .map(|&prop| {
+            let aliases: Vec<_> = languages
+                .iter()
+                .filter_map(|&lang| prop_infos[prop].labels_in(lang))
+                .flat_map(|(_, aliases)| aliases.iter().copied())
+                .unique()
+                .collect();
+            (prop, aliases)
+        })
+        .collect();
+    let alias_counts = prop_aliases.iter().flat_map(|(_, aliases)| aliases).fold(
+        HashMap::new(),
+        |mut map, &alias| {
+            *map.entry(alias).or_insert(0) += 1;
+            map
+        },
+    );
+    for (prop, aliases) in &prop_aliases {
+        for alias in aliases {
+            if alias_counts[alias] != 1 {
+                continue;
+            }
+            if let Entry::Vacant(entry) = label_to_prop.entry(alias.to_string()) {
+                entry.insert(Prop::Alias(prop));
+            }
+        }
+    }
+    (label_to_prop, num_label_unique)
+}
+
+/// Builds the index of the domains and types of Freebase properties, sorted
+/// by id. A type is labeled by its last path segment and its whole path
+/// (person and people person for people.person), a domain by its name and
+/// with "domain" appended. A label shared by several of them goes to a type
+/// before a domain, and then to the one with the most property uses.
+fn freebase_type_index<'a>(
+    kg: &KnowledgeGraphProcessor,
+    prop_infos: &HashMap<&'a str, PropInfo<'a>>,
+) -> Vec<(&'a str, Vec<String>)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (&prop, info) in prop_infos {
+        let Some((domain, ty)) = kg.freebase_property_type(prop) else {
+            continue;
+        };
+        *counts.entry(domain).or_default() += info.count;
+        *counts.entry(ty).or_default() += info.count;
+    }
+    let segment_label = |s: &str| s.replace('_', " ");
+    let mut candidates = vec![];
+    for (&id, &count) in &counts {
+        let labels = match id.rsplit_once('.') {
+            Some((_, last)) => {
+                // repeated segments like film.film are named once
+                let path = id.split('.').dedup().map(segment_label).join(" ");
+                std::iter::once(segment_label(last))
+                    .chain(Some(path))
+                    .unique()
+                    .collect()
+            }
+            None => vec![segment_label(id), format!("{} domain", segment_label(id))],
+        };
+        candidates.push((id.contains('.'), count, id, labels));
+    }
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(b.2)));
+    let mut taken = HashSet::new();
+    candidates
+        .into_iter()
+        .filter_map(|(.., id, labels)| {
+            let labels: Vec<_> = labels
+                .into_iter()
+                .filter(|label| taken.insert(label.clone()))
+                .collect();
+            (!labels.is_empty()).then_some((id, labels))
+        })
+        .sorted()
+        .collect()
+}
+
+/// Scores the aliases of all properties in all languages with alias_quality
+/// and removes those below the minimum quality. Returns the property, alias,
+/// and quality of every distinct alias.
+fn score_aliases<'a>(
+    prop_infos: &mut HashMap<&'a str, PropInfo<'a>>,
+    min_quality: f64,
+) -> Vec<(&'a str, &'a str, f64)> {
+    let form = |s: &str| normalize_label(s).to_lowercase();
+    let mut frequencies: HashMap<String, usize> = HashMap::new();
+    for info in prop_infos.values() {
+        let forms: HashSet<_> = std::iter::once((&info.label, &info.aliases))
+            .chain(
+                info.translations
+                    .values()
+                    .map(|(label, aliases)| (label, aliases)),
+            )
+            .flat_map(|(label, aliases)| {
+                std::iter::once(label.as_str()).chain(aliases.iter().copied())
+            })
+            .map(form)
+            .collect();
+        for f in forms {
+            *frequencies.entry(f).or_default() += 1;
+        }
+    }
+    let mut qualities = vec![];
+    for (&prop, info) in prop_infos.iter_mut() {
+        let PropInfo {
+            label,
+            aliases,
+            translations,
+            ..
+        } = info;
+        let languages = std::iter::once((&*label, aliases)).chain(
+            translations
+                .values_mut()
+                .map(|(label, aliases)| (&*label, aliases)),
+        );
+        for (label, aliases) in languages {
+            aliases.retain(|&alias| {
+                let quality = alias_quality(alias, label, frequencies[&form(alias)]);
+                qualities.push((prop, alias, quality));
+                quality >= min_quality
+            });
+        }
+    }
+    qualities.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    qualities.dedup_by(|a, b| (a.0, a.1) == (b.0, b.1));
+    qualities
+}
+
+/// Inputs shared by all indices written.
+struct IndexContext<'a> {
+    args: &'a PropertiesArgs,
+    kg: &'a KnowledgeGraphProcessor,
+    prop_infos: &'a HashMap<&'a str, PropInfo<'a>>,
+    /// example question per property, only for the primary language
+    examples: &'a HashMap<&'a str, String>,
+    out: &'a OutputDir,
+}
+
+/// Writes the index (with qualifiers if requested) sorted by property id and
+/// returns the labels per property.
+fn write_index<'a>(
+    ctx: &IndexContext<'a>,
+    label_to_prop: &'a HashMap<String, Prop<'a>>,
+    lang: Option<&str>,
+    ids: &mut IdMap,
+) -> anyhow::Result<Vec<(&'a str, Vec<Prop<'a>>)>> {
+    let IndexContext {
+        args,
+        kg,
+        prop_infos,
+        examples,
+        out,
+    } = ctx;
+    let output_format = args.io.output_format()?;
+    let index_file = match lang {
+        Some(lang) => out.path(&output_format.file_name(&format!("index.{lang}"))),
+        None => out.path(&output_format.file_name("index")),
+    };
+    let (mut output, mut jsonl_output) = if output_format == FileFormat::Jsonl {
+        (None, Some(BufWriter::new(File::create(index_file)?)))
+    } else {
+        (Some(record_writer(index_file, output_format)?), None)
+    };
+    let mut output_dict = HashMap::new();
+    for (label, prop) in label_to_prop {
+        output_dict
+            .entry(prop.as_str())
+            .or_insert_with(Vec::new)
+            .push(match prop {
+                Prop::Label(_) => Prop::Label(label),
+                Prop::Alias(_) | Prop::Inherited(_) => Prop::Alias(label),
+            });
+    }
+    let inherited: Vec<_> = prop_infos
+        .iter()
+        .filter(|(_, info)| !info.parents.is_empty())
+        .map(|(&sub, info)| {
+            let labels: Vec<_> = info
+                .parents
+                .iter()
+                .filter_map(|parent| output_dict.get(parent))
+                .flatten()
+                .map(|l| l.as_str())
+                .filter(|&l| {
+                    output_dict
+                        .get(sub)
+                        .is_none_or(|labels| labels.iter().all(|s| s.as_str() != l))
+                })
+                .unique()
+                .map(Prop::Inherited)
+                .collect();
+            (sub, labels)
+        })
+        .collect();
+    for (sub, labels) in inherited {
+        if !labels.is_empty() {
+            output_dict.entry(sub).or_default().extend(labels);
+        }
+    }
+    // output is sorted by property id to make it reproducible
+    let mut output_dict: Vec<_> = output_dict.into_iter().collect();
+    output_dict.sort_by_key(|&(prop, _)| kg.property_order_key(prop));
+    for (prop, labels) in output_dict.iter_mut() {
+        labels.sort();
+
+        let prop_id = ids.map(kg.format_property(prop, args.short_properties, None)?);
+        let qualifiers = if args.include_wikidata_qualifiers {
+            wikidata_qualifier_groups(labels.iter().map(|l| l.as_str()))
+                .into_iter()
+                .map(|(short, long, lbls)| {
+                    let pfx = if args.short_properties { &short } else { &long };
+                    let id = kg.format_property(prop, args.short_properties, Some(pfx))?;
+                    Ok((short, ids.map(id), lbls))
+                })
+                .collect::<anyhow::Result<_>>()?
+        } else {
+            vec![]
+        };
+
+        if let Some(output) = &mut output {
+            output.write_record(
+                std::iter::once(prop_id.as_str()).chain(labels.iter().map(|p| p.as_str())),
+            )?;
+            for (_, id, lbls) in qualifiers {
+                output.write_record(std::iter::once(id).chain(lbls))?;
+            }
+        } else if let Some(output) = &mut jsonl_output {
+            let info = &prop_infos[prop];
+            let label = info
+                .labels_in(lang)
+                .map(|(label, _)| label)
+                .unwrap_or_default();
+            let inverses = info
+                .inverses
+                .iter()
+                .map(|inv| Ok(ids.map(kg.format_property(inv, args.short_properties, None)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let qualifiers: serde_json::Map<_, _> = qualifiers
+                .into_iter()
+                .map(|(short, id, lbls)| (short, json!({ "id": id, "labels": lbls })))
+                .collect();
+            writeln!(
+                output,
+                "{}",
+                json!({
+                    "id": prop_id,
+                    "label": label,
+                    "aliases": labels
+                        .iter()
+                        .filter(|l| matches!(l, Prop::Alias(_)))
+                        .map(|l| l.as_str())
+                        .collect::<Vec<_>>(),
+                    "inherited": labels
+                        .iter()
+                        .filter(|l| matches!(l, Prop::Inherited(_)))
+                        .map(|l| l.as_str())
+                        .collect::<Vec<_>>(),
+                    "inverses": inverses,
+                    "qualifiers": qualifiers,
+                    "example": if lang.is_none() { examples.get(prop) } else { None },
+                })
+            )?;
+        }
+    }
+    if let Some(output) = &mut output {
+        output.flush()?;
+    } else if let Some(output) = &mut jsonl_output {
+        output.flush()?;
+    }
+    Ok(output_dict)
+}
@@ -0,0 +1,183 @@
+//! Python module exposing the parsers and the entity index builder, so
+//! pipelines can use them without calling the binaries. Built with maturin
+//! and the python feature, see pyproject.toml.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::intern::StringInterner;
+use crate::{
+    Disambiguation, EntityIndexBuilder, EntityIndexOptions, EntityInfo, KnowledgeGraph,
+    LabelAliasPrecedence, OwnedEntity, SurfaceFormEscaping,
+};
+
+/// Parses entity and property lines of a knowledge graph.
+#[pyclass(name = "KnowledgeGraphProcessor")]
+struct PyKnowledgeGraphProcessor {
+    kg: crate::KnowledgeGraphProcessor,
+}
+
+#[pymethods]
+impl PyKnowledgeGraphProcessor {
+    #[new]
+    #[pyo3(signature = (knowledge_base, dbpedia_host = None))]
+    fn new(knowledge_base: &str, dbpedia_host: Option<&str>) -> anyhow::Result<Self> {
+        let kg = match (KnowledgeGraph::try_from(knowledge_base)?, dbpedia_host) {
+            (KnowledgeGraph::DBPedia, Some(host)) => {
+                crate::KnowledgeGraphProcessor::with_dbpedia_host(host)?
+            }
+            (_, Some(_)) => bail!("dbpedia_host requires the knowledge base dbpedia"),
+            (kg, None) => crate::KnowledgeGraphProcessor::new(kg)?,
+        };
+        Ok(Self { kg })
+    }
+
+    /// Parses a line of an entity file into a dict with its id, label,
+    /// description, aliases, type ids, and count.
+    #[pyo3(signature = (line, ignore_types = false))]
+    fn parse_entity<'py>(
+        &self,
+        py: Python<'py>,
+        line: &str,
+        ignore_types: bool,
+    ) -> anyhow::Result<Bound<'py, PyDict>> {
+        let (ent, info) = self.kg.parse_entity(line, ignore_types)?;
+        let dict = PyDict::new_bound(py);
+        dict.set_item("id", ent.as_str())?;
+        dict.set_item("label", info.label)?;
+        dict.set_item("description", info.desc)?;
+        dict.set_item("aliases", &info.aliases)?;
+        dict.set_item("types", &*info.types.lock().unwrap())?;
+        dict.set_item("count", info.count)?;
+        Ok(dict)
+    }
+
+    /// Parses a line of a property file into a dict with its id, label,
+    /// aliases, inverse labels, and count.
+    fn parse_property<'py>(
+        &self,
+        py: Python<'py>,
+        line: &str,
+    ) -> anyhow::Result<Bound<'py, PyDict>> {
+        let (prop, info, _) = self.kg.parse_property(line)?;
+        let dict = PyDict::new_bound(py);
+        dict.set_item("id", prop.as_str())?;
+        dict.set_item("label", &info.label)?;
+        dict.set_item("aliases", &info.aliases)?;
+        dict.set_item("inverses", &info.inverses)?;
+        dict.set_item("count", info.count)?;
+        Ok(dict)
+    }
+
+    /// Formats an entity id, either prefixed (wd:Q42) or as iri.
+    #[pyo3(signature = (ent, short = false))]
+    fn format_entity(&self, ent: &str, short: bool) -> String {
+        self.kg.format_entity(ent, short)
+    }
+}
+
+/// Collects entities and assigns their surface forms like kg-entities.
+#[pyclass(name = "EntityIndexBuilder")]
+struct PyEntityIndexBuilder {
+    options: EntityIndexOptions,
+    entities: Vec<OwnedEntity>,
+    interner: StringInterner,
+}
+
+#[pymethods]
+impl PyEntityIndexBuilder {
+    #[new]
+    #[pyo3(signature = (
+        disambiguate = "info-suffix",
+        check_for_popular_aliases = false,
+        popular_alias_ratio = 1.0,
+        resolve_alias_conflicts = false,
+        alias_conflict_ratio = 1.0,
+        normalize_labels = false,
+        escape_surface_forms = "none",
+        label_alias_precedence = "label",
+        label_info_variants = false,
+    ))]
+    // keyword arguments of the Python constructor
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        disambiguate: &str,
+        check_for_popular_aliases: bool,
+        popular_alias_ratio: f64,
+        resolve_alias_conflicts: bool,
+        alias_conflict_ratio: f64,
+        normalize_labels: bool,
+        escape_surface_forms: &str,
+        label_alias_precedence: &str,
+        label_info_variants: bool,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            options: EntityIndexOptions {
+                check_for_popular_aliases,
+                popular_alias_ratio,
+                disambiguation: Disambiguation::try_from(disambiguate)?,
+                resolve_alias_conflicts,
+                alias_conflict_ratio,
+                normalize_labels,
+                escaping: SurfaceFormEscaping::try_from(escape_surface_forms)?,
+                label_alias_precedence: LabelAliasPrecedence::try_from(label_alias_precedence)?,
+                label_info_variants,
+                progress_listener: None,
+            },
+            entities: vec![],
+            interner: StringInterner::new(),
+        })
+    }
+
+    /// Adds an entity; its types are labels, ordered by popularity with the
+    /// most popular (which becomes the info) last.
+    #[pyo3(signature = (id, label, description = "", aliases = vec![], types = vec![], count = 0))]
+    fn add_entity(
+        &mut self,
+        id: &str,
+        label: &str,
+        description: &str,
+        aliases: Vec<String>,
+        types: Vec<String>,
+        count: usize,
+    ) {
+        let info = EntityInfo {
+            label,
+            desc: description,
+            aliases: aliases.iter().map(String::as_str).collect(),
+            types: Arc::new(Mutex::new(types.iter().map(String::as_str).collect())),
+            count,
+            redirects: None,
+        };
+        self.entities
+            .push(OwnedEntity::new(id, &info, &mut self.interner));
+    }
+
+    /// Assigns the surface forms and returns the rows of the index, every
+    /// entity with its surface forms, by descending count.
+    fn rows(&self) -> Vec<(String, Vec<String>)> {
+        let mut builder = EntityIndexBuilder::new(self.options.clone());
+        for ent in &self.entities {
+            builder.add_entity(&ent.id, ent.info());
+        }
+        builder.resolve();
+        builder
+            .rows()
+            .into_iter()
+            .map(|(ent, row)| {
+                let forms = row.into_iter().map(|(form, _)| form).collect();
+                (ent.to_string(), forms)
+            })
+            .collect()
+    }
+}
+
+#[pymodule]
+fn sparql_data_preparation(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyKnowledgeGraphProcessor>()?;
+    m.add_class::<PyEntityIndexBuilder>()?;
+    Ok(())
+}
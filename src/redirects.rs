@@ -0,0 +1,68 @@
+use crate::cli::IoArgs;
+use crate::entities::{load_redirects, resolve_redirect_chains};
+use crate::record_writer;
+use crate::stats::Stats;
+use crate::{KnowledgeGraph, KnowledgeGraphProcessor};
+
+/// Arguments of kg-index redirects, see [`run`].
+#[derive(clap::Args, Debug)]
+#[clap(about = None, long_about = None)]
+pub struct RedirectsArgs {
+    #[clap(flatten)]
+    pub io: IoArgs,
+
+    /// knowledge graph of the redirects (wikidata, freebase, or dbpedia)
+    #[clap(short, long)]
+    knowledge_base: String,
+
+    /// follow redirect chains (A to B to C) so that every redirect points to
+    /// the entity at the end of its chain; redirects in cycles are dropped
+    #[clap(long)]
+    resolve_chains: bool,
+}
+
+/// Parses a redirect file (an entity and the entities redirecting to it,
+/// separated by "; ", per line) and writes it with canonical iris, sorted by
+/// entity, so it can be passed to the --redirects of several builds. With
+/// --resolve-chains, redirects to redirected entities point to the end of
+/// their chain.
+pub fn run(args: RedirectsArgs) -> anyhow::Result<Stats> {
+    let kg = KnowledgeGraphProcessor::new(KnowledgeGraph::try_from(args.knowledge_base.as_str())?)?;
+    let input_format = args.io.input_format()?;
+    let output_format = args.io.output_format()?;
+
+    let mut redirects = load_redirects(&kg, &args.io.file, input_format, args.io.progress)?;
+    let (num_collapsed, num_cyclic) = if args.resolve_chains {
+        resolve_redirect_chains(&mut redirects)
+    } else {
+        (0, 0)
+    };
+    let mut redirects: Vec<_> = redirects.into_iter().collect();
+    redirects.sort();
+
+    let mut output = record_writer(&args.io.output, output_format)?;
+    let mut num_redirects = 0;
+    let mut max_redirects = 0;
+    for (ent, redirs) in &redirects {
+        num_redirects += redirs.len();
+        max_redirects = max_redirects.max(redirs.len());
+        let redirs = redirs
+            .iter()
+            .map(|redir| kg.format_entity(redir, false))
+            .collect::<Vec<_>>()
+            .join("; ");
+        output.write_record([kg.format_entity(ent, false), redirs])?;
+    }
+    output.flush()?;
+
+    let mut stats = Stats::new(format!("{} redirects", args.knowledge_base));
+    stats.count("redirected_entities", redirects.len());
+    stats.count("redirects", num_redirects);
+    stats.count("max_redirects", max_redirects);
+    if args.resolve_chains {
+        stats.count("collapsed_chains", num_collapsed);
+        stats.count("cyclic_redirects", num_cyclic);
+    }
+
+    Ok(stats)
+}
@@ -0,0 +1,185 @@
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use serde_json::{json, Map, Value};
+
+use crate::progress::{resident_set_size, stage_memory};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatsFormat {
+    Text,
+    Json,
+}
+
+impl TryFrom<&str> for StatsFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "text" => StatsFormat::Text,
+            "json" => StatsFormat::Json,
+            _ => return Err(anyhow!("invalid stats format {}", value)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatValue {
+    Count(usize),
+    Percent(f64),
+    Seconds(f64),
+}
+
+/// Wall-clock time of the consecutive stages of a build, e.g. loading,
+/// parsing, and writing.
+#[derive(Debug, Clone)]
+pub struct StageTimer {
+    start: Instant,
+    current: Option<(String, Instant)>,
+    stages: Vec<(String, Duration)>,
+}
+
+impl Default for StageTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StageTimer {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            current: None,
+            stages: vec![],
+        }
+    }
+
+    /// Finishes the current stage, if any, and starts the next one.
+    pub fn start(&mut self, stage: &str) {
+        self.finish();
+        self.current = Some((stage.to_string(), Instant::now()));
+    }
+
+    pub fn finish(&mut self) {
+        if let Some((stage, start)) = self.current.take() {
+            self.stages.push((stage, start.elapsed()));
+        }
+    }
+
+    /// Finished stages with their time, in the order they ran.
+    pub fn stages(&self) -> &[(String, Duration)] {
+        &self.stages
+    }
+
+    /// Time since the timer was created.
+    pub fn total(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Statistics of an index build. Metrics are named in snake_case and kept
+/// in insertion order; the text rendering replaces underscores with spaces.
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub title: String,
+    pub metrics: Vec<(String, StatValue)>,
+}
+
+impl Stats {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            metrics: vec![],
+        }
+    }
+
+    pub fn count(&mut self, name: &str, value: usize) {
+        self.metrics
+            .push((name.to_string(), StatValue::Count(value)));
+    }
+
+    /// Adds `part` as percentage of `total` (0 if `total` is 0).
+    pub fn percent(&mut self, name: &str, part: usize, total: usize) {
+        let value = if total == 0 {
+            0.0
+        } else {
+            100.0 * part as f64 / total as f64
+        };
+        self.metrics
+            .push((name.to_string(), StatValue::Percent(value)));
+    }
+
+    pub fn seconds(&mut self, name: &str, value: Duration) {
+        self.metrics
+            .push((name.to_string(), StatValue::Seconds(value.as_secs_f64())));
+    }
+
+    /// Adds the time of every finished stage of the timer and the total
+    /// time in seconds.
+    pub fn timing(&mut self, timer: &StageTimer) {
+        for (stage, elapsed) in timer.stages() {
+            self.seconds(&format!("seconds_{}", stage.replace(' ', "_")), *elapsed);
+        }
+        self.seconds("seconds_total", timer.total());
+    }
+
+    /// Adds the peak resident set size in MiB of every stage sampled so
+    /// far and of the whole process.
+    pub fn memory(&mut self) {
+        for (stage, rss) in stage_memory() {
+            let name = format!("peak_rss_mib_{}", stage.replace(' ', "_"));
+            self.count(&name, (rss >> 20) as usize);
+        }
+        if let Some(rss) = resident_set_size(true) {
+            self.count("peak_rss_mib", (rss >> 20) as usize);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StatValue> {
+        self.metrics
+            .iter()
+            .find_map(|(n, value)| if n == name { Some(value) } else { None })
+    }
+
+    pub fn to_text(&self) -> String {
+        let width = self
+            .metrics
+            .iter()
+            .map(|(name, _)| name.len() + 1)
+            .max()
+            .unwrap_or(0);
+        let mut text = format!("{}\n{}\n", self.title, "#".repeat(self.title.len()));
+        for (name, value) in &self.metrics {
+            let name = format!("{}:", name.replace('_', " "));
+            let _ = match value {
+                StatValue::Count(count) => writeln!(text, "{name:<width$} {count}"),
+                StatValue::Percent(pct) => writeln!(text, "{name:<width$} {pct:.2}%"),
+                StatValue::Seconds(secs) => writeln!(text, "{name:<width$} {secs:.3}s"),
+            };
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> Value {
+        let metrics: Map<_, _> = self
+            .metrics
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    StatValue::Count(count) => json!(count),
+                    StatValue::Percent(value) | StatValue::Seconds(value) => json!(value),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        json!({ "title": self.title, "metrics": metrics })
+    }
+
+    pub fn render(&self, format: StatsFormat) -> String {
+        match format {
+            StatsFormat::Text => self.to_text(),
+            StatsFormat::Json => format!("{}\n", self.to_json()),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::stats::{Stats, StatsFormat};
+use crate::{parse_surface_form, progress_bar, record_iter, FileFormat, SurfaceFormEscaping};
+
+/// Arguments of kg-index stats, see [`run`].
+#[derive(clap::Args, Debug)]
+#[clap(about = None, long_about = None)]
+pub struct SummaryArgs {
+    /// index file built by kg-index entities or properties
+    #[clap(short, long)]
+    file: PathBuf,
+
+    /// show progress bars
+    #[clap(short, long)]
+    progress: bool,
+
+    /// format of the index (tsv or csv)
+    #[clap(long, default_value = "tsv")]
+    input_format: String,
+
+    /// format of the statistics (text or json)
+    #[clap(long, default_value = "text")]
+    stats_format: String,
+
+    /// how the surface forms of the index are escaped (none, sanitize, or
+    /// escape)
+    #[clap(long, default_value = "none")]
+    escape_surface_forms: String,
+}
+
+impl SummaryArgs {
+    pub fn stats_format(&self) -> anyhow::Result<StatsFormat> {
+        StatsFormat::try_from(self.stats_format.as_str())
+    }
+}
+
+/// Statistics of an existing index: its rows, ids, and surface
+/// forms, and how many surface forms occur in several rows or carry a
+/// parenthesized info.
+pub fn run(args: SummaryArgs) -> anyhow::Result<Stats> {
+    let input_format = FileFormat::try_from(args.input_format.as_str())?;
+    let escaping = SurfaceFormEscaping::try_from(args.escape_surface_forms.as_str())?;
+
+    let mut num_rows = 0;
+    let mut num_rows_with_aliases = 0;
+    let mut num_forms = 0;
+    let mut num_forms_with_info = 0;
+    let mut max_forms = 0;
+    let mut ids = HashSet::new();
+    let mut form_rows: HashMap<String, usize> = HashMap::new();
+    let pbar = progress_bar("reading index", u64::MAX, !args.progress);
+    for record in pbar.wrap_iter(record_iter(&args.file, input_format)?) {
+        let record = record?;
+        let mut fields = record.iter();
+        let Some(id) = fields.next() else {
+            continue;
+        };
+        let forms: Vec<_> = fields.collect();
+        num_rows += 1;
+        if forms.len() > 1 {
+            num_rows_with_aliases += 1;
+        }
+        num_forms += forms.len();
+        max_forms = max_forms.max(forms.len());
+        ids.insert(id.to_string());
+        for form in forms {
+            if parse_surface_form(form, escaping).1.is_some() {
+                num_forms_with_info += 1;
+            }
+            *form_rows.entry(form.to_string()).or_default() += 1;
+        }
+    }
+    pbar.finish_and_clear();
+
+    let mut stats = Stats::new(format!("index {}", args.file.display()));
+    stats.count("rows", num_rows);
+    stats.count("ids", ids.len());
+    stats.count("surface_forms", num_forms);
+    stats.count("unique_surface_forms", form_rows.len());
+    stats.count(
+        "shared_surface_forms",
+        form_rows.values().filter(|&&rows| rows > 1).count(),
+    );
+    stats.count("surface_forms_with_info", num_forms_with_info);
+    stats.count("max_surface_forms_per_row", max_forms);
+    stats.percent("rows_with_aliases", num_rows_with_aliases, num_rows);
+
+    Ok(stats)
+}
@@ -0,0 +1,362 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use serde::de::{IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::intern::StringInterner;
+use crate::{line_iter, OwnedEntity};
+
+/// Number of dump lines parsed at once, in parallel.
+const CHUNK_SIZE: usize = 4096;
+
+/// Reads the official Wikidata JSON dump (`wikidata-YYYYMMDD-all.json.bz2`,
+/// a JSON array with one entity per line) and yields its items like a
+/// [`StreamingEntityReader`](crate::StreamingEntityReader): the English
+/// label, description, and aliases, the instance of (P31) types, and the
+/// number of sitelinks as count. Language independent (`mul`) labels and
+/// aliases are used as well. Properties are skipped, and so are items
+/// without label, which are counted.
+///
+/// Lines are read in chunks, which are deserialized in parallel into only
+/// the fields above, and yielded in dump order.
+pub struct WikidataDumpReader<'a> {
+    lines: Box<dyn Iterator<Item = anyhow::Result<String>> + 'a>,
+    ignore_types: bool,
+    counts: Option<&'a HashMap<String, usize>>,
+    filter: Box<dyn Fn(&str) -> bool + Sync + 'a>,
+    parsed: VecDeque<OwnedEntity>,
+    pub interner: StringInterner,
+    pub num_records: usize,
+    pub num_filtered: usize,
+    pub num_invalid: usize,
+    pub num_unlabeled: usize,
+}
+
+/// The fields of a dump entity the reader uses; the other languages and
+/// claims are skipped without being parsed.
+#[derive(Deserialize)]
+struct DumpEntity<'a> {
+    #[serde(borrow)]
+    id: Cow<'a, str>,
+    #[serde(rename = "type", borrow)]
+    kind: Cow<'a, str>,
+    #[serde(default, borrow)]
+    labels: LanguageValues<'a>,
+    #[serde(default, borrow)]
+    descriptions: LanguageValues<'a>,
+    #[serde(default, borrow)]
+    aliases: LanguageAliases<'a>,
+    #[serde(default, borrow)]
+    claims: Claims<'a>,
+    #[serde(default)]
+    sitelinks: NumEntries,
+}
+
+/// A language map (labels or descriptions) in English and language
+/// independent. Empty maps are written as empty arrays in the dump, which
+/// these structs accept as well.
+#[derive(Deserialize, Default)]
+struct LanguageValues<'a> {
+    #[serde(default, borrow)]
+    en: Option<LanguageValue<'a>>,
+    #[serde(default, borrow)]
+    mul: Option<LanguageValue<'a>>,
+}
+
+#[derive(Deserialize, Default)]
+struct LanguageAliases<'a> {
+    #[serde(default, borrow)]
+    en: Vec<LanguageValue<'a>>,
+    #[serde(default, borrow)]
+    mul: Vec<LanguageValue<'a>>,
+}
+
+#[derive(Deserialize)]
+struct LanguageValue<'a> {
+    #[serde(borrow)]
+    value: Cow<'a, str>,
+}
+
+#[derive(Deserialize, Default)]
+struct Claims<'a> {
+    #[serde(rename = "P31", default, borrow)]
+    instance_of: Vec<Claim<'a>>,
+}
+
+#[derive(Deserialize)]
+struct Claim<'a> {
+    #[serde(borrow)]
+    rank: Cow<'a, str>,
+    #[serde(borrow)]
+    mainsnak: Snak<'a>,
+}
+
+/// Only value snaks have a datavalue, not the unknown or no value ones.
+#[derive(Deserialize)]
+struct Snak<'a> {
+    #[serde(default, borrow)]
+    datavalue: Option<DataValue<'a>>,
+}
+
+#[derive(Deserialize)]
+struct DataValue<'a> {
+    #[serde(borrow)]
+    value: ItemValue<'a>,
+}
+
+#[derive(Deserialize)]
+struct ItemValue<'a> {
+    #[serde(default, borrow)]
+    id: Option<Cow<'a, str>>,
+}
+
+/// Number of entries of a map (or of an empty array) whose keys and values
+/// are skipped.
+#[derive(Default)]
+struct NumEntries(usize);
+
+impl<'de> Deserialize<'de> for NumEntries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NumEntriesVisitor;
+
+        impl<'de> Visitor<'de> for NumEntriesVisitor {
+            type Value = NumEntries;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map or an array")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<NumEntries, A::Error> {
+                let mut num = 0;
+                while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {
+                    num += 1;
+                }
+                Ok(NumEntries(num))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<NumEntries, A::Error> {
+                let mut num = 0;
+                while seq.next_element::<IgnoredAny>()?.is_some() {
+                    num += 1;
+                }
+                Ok(NumEntries(num))
+            }
+        }
+
+        deserializer.deserialize_any(NumEntriesVisitor)
+    }
+}
+
+impl<'a> LanguageValues<'a> {
+    /// The English value, or else the language independent one.
+    fn english(&self) -> Option<&str> {
+        self.en.as_ref().or(self.mul.as_ref()).map(|v| &*v.value)
+    }
+}
+
+/// A dump line after deserialization.
+enum DumpLine<'a> {
+    Invalid,
+    /// a property, or an item left out by the filter
+    Skipped {
+        filtered: bool,
+    },
+    Item(Box<DumpEntity<'a>>),
+}
+
+impl<'a> WikidataDumpReader<'a> {
+    pub fn new(file: impl AsRef<Path>, ignore_types: bool) -> anyhow::Result<Self> {
+        Ok(Self {
+            lines: Box::new(line_iter(file.as_ref().to_path_buf())?.fuse()),
+            ignore_types,
+            counts: None,
+            filter: Box::new(|_| true),
+            parsed: VecDeque::new(),
+            interner: StringInterner::new(),
+            num_records: 0,
+            num_filtered: 0,
+            num_invalid: 0,
+            num_unlabeled: 0,
+        })
+    }
+
+    /// Takes the counts of the given entities from the map instead of their
+    /// number of sitelinks.
+    pub fn with_counts(mut self, counts: &'a HashMap<String, usize>) -> Self {
+        self.counts = Some(counts);
+        self
+    }
+
+    /// Skips entities whose id does not satisfy the filter. The filter is
+    /// called from several threads.
+    pub fn with_filter(mut self, filter: impl Fn(&str) -> bool + Sync + 'a) -> Self {
+        self.filter = Box::new(filter);
+        self
+    }
+
+    /// Reads the next chunk of lines and parses its items. Returns false at
+    /// the end of the dump.
+    fn parse_chunk(&mut self) -> anyhow::Result<bool> {
+        let mut lines = Vec::with_capacity(CHUNK_SIZE);
+        for line in self.lines.by_ref() {
+            let line = line?;
+            // every entity is on its own line, followed by a comma except
+            // for the last one, between the lines "[" and "]"
+            let entity = line.trim().trim_end_matches(',');
+            if entity.is_empty() || entity == "[" || entity == "]" {
+                continue;
+            }
+            lines.push(line);
+            if lines.len() == CHUNK_SIZE {
+                break;
+            }
+        }
+        if lines.is_empty() {
+            return Ok(false);
+        }
+        self.num_records += lines.len();
+        let filter = &self.filter;
+        let parsed: Vec<_> = lines
+            .par_iter()
+            .map(|line| {
+                let line = line.trim().trim_end_matches(',');
+                let Ok(entity) = serde_json::from_str::<DumpEntity>(line) else {
+                    return DumpLine::Invalid;
+                };
+                if entity.kind != "item" {
+                    DumpLine::Skipped { filtered: false }
+                } else if !filter(&entity.id) {
+                    DumpLine::Skipped { filtered: true }
+                } else {
+                    DumpLine::Item(Box::new(entity))
+                }
+            })
+            .collect();
+        for line in parsed {
+            match line {
+                DumpLine::Invalid => self.num_invalid += 1,
+                DumpLine::Skipped { filtered } => self.num_filtered += filtered as usize,
+                DumpLine::Item(entity) => match self.intern(&entity) {
+                    Some(entity) => self.parsed.push_back(entity),
+                    None => self.num_unlabeled += 1,
+                },
+            }
+        }
+        Ok(true)
+    }
+
+    /// The entity of a deserialized item, None if it has no label.
+    fn intern(&mut self, entity: &DumpEntity) -> Option<OwnedEntity> {
+        let label = entity.labels.english()?;
+        let desc = entity.descriptions.english().unwrap_or("");
+        let mut aliases: Vec<Arc<str>> = vec![];
+        for alias in entity.aliases.en.iter().chain(&entity.aliases.mul) {
+            let alias = &*alias.value;
+            if alias != label && !aliases.iter().any(|a| &**a == alias) {
+                aliases.push(self.interner.intern(alias));
+            }
+        }
+        let types = if self.ignore_types {
+            vec![]
+        } else {
+            entity
+                .claims
+                .instance_of
+                .iter()
+                .filter(|claim| claim.rank != "deprecated")
+                .filter_map(|claim| claim.mainsnak.datavalue.as_ref()?.value.id.as_deref())
+                .map(|ty| self.interner.intern(ty))
+                .collect()
+        };
+        let count = match self.counts.and_then(|counts| counts.get(&*entity.id)) {
+            Some(&count) => count,
+            None => entity.sitelinks.0,
+        };
+        Some(OwnedEntity {
+            id: entity.id.to_string(),
+            label: self.interner.intern(label),
+            desc: self.interner.intern(desc),
+            aliases,
+            types,
+            count,
+        })
+    }
+}
+
+impl Iterator for WikidataDumpReader<'_> {
+    type Item = anyhow::Result<OwnedEntity>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entity) = self.parsed.pop_front() {
+                return Some(Ok(entity));
+            }
+            match self.parse_chunk() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_items_are_parsed_in_order() {
+        // empty maps are empty arrays in the dump, deprecated types and
+        // unknown values are left out
+        let lines = [
+            "[".to_string(),
+            r#"{"type": "property", "id": "P31", "labels": {"en": {"value": "instance of"}}},"#
+                .to_string(),
+            r#"{"type": "item", "id": "Q1", "labels": [], "claims": [], "sitelinks": []},"#
+                .to_string(),
+            concat!(
+                r#"{"type": "item", "id": "Q2", "labels": {"mul": {"value": "two"}}, "#,
+                r#""aliases": {"en": [{"value": "2"}, {"value": "two"}]}, "#,
+                r#""claims": {"P31": [{"rank": "deprecated", "mainsnak": {"datavalue": "#,
+                r#"{"value": {"id": "Q3"}}}}, {"rank": "normal", "mainsnak": {}}, "#,
+                r#"{"rank": "preferred", "mainsnak": {"datavalue": {"value": {"id": "Q4"}}}}]}, "#,
+                r#""sitelinks": {"enwiki": {"title": "2"}, "dewiki": {"title": "2"}}},"#
+            )
+            .to_string(),
+            "not json,".to_string(),
+        ]
+        .into_iter()
+        .chain((5..CHUNK_SIZE + 10).map(|i| {
+            format!(r#"{{"type": "item", "id": "Q{i}", "labels": {{"en": {{"value": "{i}"}}}}}},"#)
+        }))
+        .chain(["]".to_string()]);
+        let file = std::env::temp_dir().join(format!("dump-{}.json", std::process::id()));
+        std::fs::write(&file, lines.collect::<Vec<_>>().join("\n")).unwrap();
+        let mut reader = WikidataDumpReader::new(&file, false)
+            .unwrap()
+            .with_filter(|id| id != "Q7");
+        let entities: Vec<_> = reader.by_ref().map(Result::unwrap).collect();
+        std::fs::remove_file(&file).unwrap();
+
+        let two = &entities[0];
+        assert_eq!((two.id.as_str(), &*two.label), ("Q2", "two"));
+        assert_eq!(two.aliases, [Arc::from("2")]);
+        assert_eq!(two.types, [Arc::from("Q4")]);
+        assert_eq!(two.count, 2);
+        let ids: Vec<_> = entities[1..].iter().map(|ent| ent.id.clone()).collect();
+        let expected: Vec<_> = (5..CHUNK_SIZE + 10)
+            .filter(|&i| i != 7)
+            .map(|i| format!("Q{i}"))
+            .collect();
+        assert_eq!(ids, expected);
+        assert_eq!(reader.num_records, CHUNK_SIZE + 9);
+        assert_eq!(reader.num_invalid, 1);
+        assert_eq!(reader.num_filtered, 1);
+        assert_eq!(reader.num_unlabeled, 1);
+    }
+}
@@ -0,0 +1,2182 @@
+//! Builds indices from the miniature dumps in `tests/fixtures` and compares
+//! all outputs, including the printed statistics, with `tests/golden`.
+//! Run with `UPDATE_GOLDEN=1 cargo test` to rewrite the golden files after
+//! an intended change, and review their diff before committing.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use flate2::write::GzEncoder;
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use sparql_data_preparation::cache::read_entity_cache;
+use sparql_data_preparation::candidates::{CandidateGenerator, MatchKind, NearMisses};
+use sparql_data_preparation::intern::read_dictionary_index;
+use sparql_data_preparation::{FileFormat, SurfaceFormEscaping};
+
+fn fixture(path: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(path)
+        .display()
+        .to_string()
+}
+
+fn golden_file(path: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(path)
+        .display()
+        .to_string()
+}
+
+/// Runs the binary with its output in a fresh directory, or in `file`
+/// within that directory for binaries writing a single file.
+fn run(bin: &str, name: &str, args: &[&str], file: Option<&str>) -> PathBuf {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("end-to-end")
+        .join(name);
+    if output.exists() {
+        fs::remove_dir_all(&output).unwrap();
+    }
+    let target = match file {
+        Some(file) => {
+            fs::create_dir_all(&output).unwrap();
+            output.join(file)
+        }
+        None => output.clone(),
+    };
+    let result = Command::new(bin)
+        .args(args)
+        .arg("--output")
+        .arg(target)
+        .output()
+        .unwrap();
+    assert!(
+        result.status.success(),
+        "{name} failed:\n{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    fs::write(output.join("stats.txt"), result.stdout).unwrap();
+    output
+}
+
+/// Runs the binary expecting it to fail and returns its error output.
+fn run_error(bin: &str, args: &[&str]) -> String {
+    let result = Command::new(bin).args(args).output().unwrap();
+    assert!(!result.status.success(), "{bin} should have failed");
+    String::from_utf8_lossy(&result.stderr).to_string()
+}
+
+fn files(dir: &Path) -> Vec<String> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    files
+}
+
+fn check_golden(name: &str, output: &Path) {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        if golden.exists() {
+            fs::remove_dir_all(&golden).unwrap();
+        }
+        fs::create_dir_all(&golden).unwrap();
+        for file in files(output) {
+            fs::copy(output.join(&file), golden.join(&file)).unwrap();
+        }
+        return;
+    }
+    assert_eq!(
+        files(output),
+        files(&golden),
+        "{name}: output files differ from the golden files"
+    );
+    for file in files(&golden) {
+        let expected = fs::read_to_string(golden.join(&file)).unwrap();
+        let actual = fs::read_to_string(output.join(&file)).unwrap();
+        assert!(
+            expected == actual,
+            "{name}/{file} differs from the golden file:\n{actual}"
+        );
+    }
+}
+
+fn entities(name: &str, args: &[&str]) {
+    let output = run(env!("CARGO_BIN_EXE_kg-entities"), name, args, None);
+    check_golden(name, &output);
+}
+
+fn properties(name: &str, args: &[&str]) {
+    let output = run(env!("CARGO_BIN_EXE_kg-properties"), name, args, None);
+    check_golden(name, &output);
+}
+
+#[test]
+fn wikidata_entities() {
+    entities(
+        "wikidata-entities",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--redirects",
+            &fixture("wikidata/redirects.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+            "--keep-most-common-non-unique",
+            "--ignore-types",
+        ],
+    );
+}
+
+#[test]
+fn wikidata_entities_streaming() {
+    // streaming only changes how the file is read, not the outputs
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-streaming",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--redirects",
+            &fixture("wikidata/redirects.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+            "--keep-most-common-non-unique",
+            "--ignore-types",
+            "--streaming",
+        ],
+        None,
+    );
+    check_golden("wikidata-entities", &output);
+}
+
+#[test]
+fn wikidata_entities_interning() {
+    // the entities of the default, parallel path are interned as well
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-interning",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--memory-stats",
+        ],
+        None,
+    );
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    let count = |name: &str| -> usize {
+        let line = stats
+            .lines()
+            .find(|line| line.starts_with(&format!("{name}:")))
+            .unwrap_or_else(|| panic!("no {name} in the stats"));
+        line.split_whitespace().last().unwrap().parse().unwrap()
+    };
+    assert!(count("distinct strings") > 0);
+    // e.g. the type city (Q515) of several entities
+    assert!(count("interning saved bytes") > 0);
+}
+
+#[test]
+fn wikidata_entities_json_dump() {
+    entities(
+        "wikidata-entities-json-dump",
+        &[
+            "--file",
+            &fixture("wikidata/dump.json"),
+            "--knowledge-base",
+            "wikidata",
+            "--wikidata-json",
+            "--disambiguate",
+            "all",
+        ],
+    );
+}
+
+#[test]
+fn wikidata_entities_kg_index() {
+    // the entities subcommand is the same build as kg-entities
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-index"),
+        "wikidata-entities-kg-index",
+        &[
+            "entities",
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--redirects",
+            &fixture("wikidata/redirects.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+            "--disambiguate",
+            "all",
+            "--ignore-types",
+        ],
+        None,
+    );
+    check_golden("wikidata-entities", &output);
+}
+
+#[test]
+fn batch_entities_kg_index() {
+    // one invocation builds both knowledge graphs like their own builds,
+    // with the short options attached to their values
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("batch");
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("batch.tsv");
+    let lines = [
+        "# knowledge graph, option, and value".to_string(),
+        format!("wikidata\tfile\t{}", fixture("wikidata/entities.tsv")),
+        format!("wikidata\tredirects\t{}", fixture("wikidata/redirects.tsv")),
+        format!("dbpedia\tfile\t{}", fixture("dbpedia/entities.tsv")),
+        format!("dbpedia\tredirects\t{}", fixture("dbpedia/redirects.tsv")),
+    ];
+    fs::write(&config, lines.join("\n")).unwrap();
+    let config = format!("-f{}", config.display());
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-index"),
+        "batch-entities",
+        &[
+            "entities",
+            &config,
+            "-kwikidata,dbpedia",
+            "--check-for-popular-aliases",
+            "--disambiguate",
+            "all",
+            "--ignore-types",
+        ],
+        None,
+    );
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut stats = String::new();
+    for (kg, name) in [
+        ("wikidata", "wikidata-entities"),
+        ("dbpedia", "dbpedia-entities"),
+    ] {
+        for file in files(&golden.join(name)) {
+            let expected = fs::read_to_string(golden.join(name).join(&file)).unwrap();
+            if file == "stats.txt" {
+                stats.push_str(&expected);
+                continue;
+            }
+            let actual = fs::read_to_string(output.join(kg).join(&file)).unwrap();
+            assert!(expected == actual, "{kg}/{file} differs from {name}");
+        }
+    }
+    // the statistics of the builds in the order of the knowledge graphs
+    assert_eq!(fs::read_to_string(output.join("stats.txt")).unwrap(), stats);
+    let manifest = fs::read_to_string(output.join("manifest.tsv")).unwrap();
+    assert!(manifest.starts_with("dbpedia/index.tsv\t"));
+    assert!(manifest.contains("\nwikidata/index.tsv\t"));
+}
+
+#[test]
+fn wikidata_redirect_chains() {
+    // Q1 redirects to Q2, which redirects to Q3, and Q4 and Q5 to each other
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("redirect-chains");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("redirects.tsv");
+    let iri = |id: &str| format!("<http://www.wikidata.org/entity/{id}>");
+    let lines = [
+        "?ent\t?redirs".to_string(),
+        format!("{}\t{}", iri("Q2"), iri("Q1")),
+        format!("{}\t{}", iri("Q3"), iri("Q2")),
+        format!("{}\t{}", iri("Q4"), iri("Q5")),
+        format!("{}\t{}", iri("Q5"), iri("Q4")),
+    ];
+    fs::write(&file, lines.join("\n") + "\n").unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-index"),
+        "wikidata-redirect-chains",
+        &[
+            "redirects",
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--resolve-chains",
+        ],
+        Some("redirects.tsv"),
+    );
+    assert_eq!(
+        fs::read_to_string(output.join("redirects.tsv")).unwrap(),
+        "http://www.wikidata.org/entity/Q3\t\
+         http://www.wikidata.org/entity/Q1; http://www.wikidata.org/entity/Q2\n"
+    );
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    for (name, count) in [("collapsed chains", 1), ("cyclic redirects", 2)] {
+        let prefix = format!("{name}:");
+        assert!(stats
+            .lines()
+            .any(|line| line.starts_with(&prefix) && line.ends_with(&format!(" {count}"))));
+    }
+}
+
+#[test]
+fn wikidata_entities_fst() {
+    // every surface form of the index maps to the first row it occurs in
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-fst",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--redirects",
+            &fixture("wikidata/redirects.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--ignore-types",
+            "--fst",
+        ],
+        None,
+    );
+    let map = fst::Map::new(fs::read(output.join("index.fst")).unwrap()).unwrap();
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    let mut num_forms = 0;
+    for (row, line) in index.lines().enumerate() {
+        for form in line.split('\t').skip(1) {
+            let first = map.get(form).unwrap() as usize;
+            assert!(
+                first <= row,
+                "{form} maps to row {first}, expected at most {row}"
+            );
+            num_forms += 1;
+        }
+    }
+    assert!(num_forms >= map.len());
+}
+
+#[test]
+fn wikidata_entities_dictionary_index() {
+    // the dictionary-coded index decodes to the rows of the tsv index
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-dictionary-index",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--streaming",
+            "--dictionary-index",
+        ],
+        None,
+    );
+    let rows = read_dictionary_index(output.join("index.dict"), SurfaceFormEscaping::None).unwrap();
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    let expected: Vec<_> = index
+        .lines()
+        .map(|line| {
+            let mut fields = line.split('\t').map(str::to_string);
+            (fields.next().unwrap(), fields.collect::<Vec<_>>())
+        })
+        .collect();
+    assert_eq!(rows, expected);
+}
+
+#[test]
+fn wikidata_entities_type_filters() {
+    // humans are left out, but their type label is still used for the others
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("type-filters");
+    fs::create_dir_all(&dir).unwrap();
+    let types = dir.join("types.txt");
+    fs::write(&types, "Q5\n").unwrap();
+    let build = |name: &str, flag: &str| {
+        let output = run(
+            env!("CARGO_BIN_EXE_kg-entities"),
+            name,
+            &[
+                "--file",
+                &fixture("wikidata/entities.tsv"),
+                "--knowledge-base",
+                "wikidata",
+                flag,
+                types.to_str().unwrap(),
+            ],
+            None,
+        );
+        let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+        let ids: Vec<_> = index
+            .lines()
+            .map(|line| {
+                line.split('\t')
+                    .next()
+                    .unwrap()
+                    .rsplit('/')
+                    .next()
+                    .unwrap()
+                    .to_string()
+            })
+            .sorted()
+            .collect();
+        let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+        assert!(stats.contains("type filtered entities:"));
+        ids
+    };
+    assert_eq!(
+        build("wikidata-entities-include-types", "--include-types"),
+        ["Q167646", "Q42"]
+    );
+    let excluded = build("wikidata-entities-exclude-types", "--exclude-types");
+    assert!(excluded.contains(&"Q90".to_string()) && excluded.contains(&"Q5".to_string()));
+    assert!(!excluded.contains(&"Q42".to_string()));
+}
+
+#[test]
+fn wikidata_entities_type_filters_while_reading() {
+    // human (Q5) is left out while reading, in both ways of reading, but
+    // still labels the type of Douglas Adams
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("type-filters-reading");
+    fs::create_dir_all(&dir).unwrap();
+    let types = dir.join("types.txt");
+    fs::write(&types, "Q5\n").unwrap();
+    let file = fixture("wikidata/entities.tsv");
+    for streaming in [false, true] {
+        let merged = dir.join(format!("merged-{streaming}.bin.zst"));
+        let mut args = vec![
+            "--file",
+            &file,
+            "--knowledge-base",
+            "wikidata",
+            "--include-types",
+            types.to_str().unwrap(),
+            "--save-merged",
+            merged.to_str().unwrap(),
+        ];
+        if streaming {
+            args.push("--streaming");
+        }
+        run(
+            env!("CARGO_BIN_EXE_kg-entities"),
+            &format!("wikidata-entities-type-filters-reading-{streaming}"),
+            &args,
+            None,
+        );
+        let records = read_entity_cache(&merged).unwrap();
+        assert!(records.iter().all(|value| value.id != "Q5"));
+        let adams = records.iter().find(|value| value.id == "Q42").unwrap();
+        assert_eq!(adams.types, ["human"]);
+    }
+}
+
+#[test]
+fn wikidata_entities_no_scholarly_profile() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("no-scholarly");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    let mut content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    content.push_str(concat!(
+        "<http://www.wikidata.org/entity/Q56>\t\"Paris in the 19th century\"@en\t",
+        "\"scholarly article\"@en\t1\t<http://www.wikidata.org/entity/Q13442814>\t\n",
+    ));
+    fs::write(&file, content).unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-no-scholarly",
+        &[
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--profile",
+            "no-scholarly",
+        ],
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(!index.contains("/Q56\t"));
+    assert!(index.contains("/Q42\t"));
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    assert!(stats
+        .lines()
+        .any(|line| line.starts_with("type filtered entities:") && line.ends_with(" 1")));
+}
+
+#[test]
+fn wikidata_entities_merged_shards() {
+    // the merged entities of two halves of the dump give the same index as
+    // the whole dump
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("shards");
+    fs::create_dir_all(&dir).unwrap();
+    let content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    let (header, lines) = content.split_once('\n').unwrap();
+    let lines: Vec<_> = lines.lines().collect();
+    let mut shards = vec![];
+    for (i, half) in lines.chunks(lines.len().div_ceil(2)).enumerate() {
+        let file = dir.join(format!("shard-{i}.tsv"));
+        fs::write(&file, format!("{header}\n{}\n", half.join("\n"))).unwrap();
+        let merged = dir.join(format!("shard-{i}.bin.zst"));
+        run(
+            env!("CARGO_BIN_EXE_kg-entities"),
+            &format!("wikidata-entities-shard-{i}"),
+            &[
+                "--file",
+                file.to_str().unwrap(),
+                "--redirects",
+                &fixture("wikidata/redirects.tsv"),
+                "--knowledge-base",
+                "wikidata",
+                "--ignore-types",
+                "--save-merged",
+                merged.to_str().unwrap(),
+            ],
+            None,
+        );
+        shards.push(merged.display().to_string());
+    }
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-merge-shards"),
+        "wikidata-entities-merged-shards",
+        &[
+            "--file",
+            &shards[0],
+            "--shard",
+            &shards[1],
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+            "--disambiguate",
+            "all",
+            "--ignore-types",
+        ],
+        None,
+    );
+    check_golden("wikidata-entities", &output);
+}
+
+#[test]
+fn wikidata_entities_update() {
+    // an older dump with a changed capital, without Douglas Adams, and with
+    // a since deleted Paris, updated to the current one
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("update");
+    fs::create_dir_all(&dir).unwrap();
+    let content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    let (header, lines) = content.split_once('\n').unwrap();
+    let line = |ent: &str| {
+        lines
+            .lines()
+            .find(|line| line.starts_with(&format!("<http://www.wikidata.org/entity/{ent}>")))
+            .unwrap()
+    };
+    let deleted = "<http://www.wikidata.org/entity/Q999>\t\"Paris\"@en\t\"nightclub\"@en\t1000\t\t";
+    let old: Vec<_> = lines
+        .lines()
+        .filter(|&l| l != line("Q42"))
+        .map(|l| l.replace("\"capital of France\"", "\"city in France\""))
+        .chain([deleted.to_string()])
+        .collect();
+    let old_file = dir.join("old.tsv");
+    fs::write(&old_file, format!("{header}\n{}\n", old.join("\n"))).unwrap();
+    let delta = dir.join("delta.tsv");
+    let changes = [
+        format!("changed\t{}", line("Q90")),
+        format!("added\t{}", line("Q42")),
+        "deleted\t<http://www.wikidata.org/entity/Q999>".to_string(),
+    ];
+    fs::write(
+        &delta,
+        format!("?change\t{header}\n{}\n", changes.join("\n")),
+    )
+    .unwrap();
+    let merged = dir.join("old.bin.zst");
+    let redirects = fixture("wikidata/redirects.tsv");
+    let build = |name: &str, file: &str, extra: &[&str]| {
+        let mut args = vec![
+            "--file",
+            file,
+            "--redirects",
+            &redirects,
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+        ];
+        args.extend(extra);
+        run(env!("CARGO_BIN_EXE_kg-entities"), name, &args, None)
+    };
+    let previous = build(
+        "wikidata-entities-old",
+        old_file.to_str().unwrap(),
+        &["--save-merged", merged.to_str().unwrap()],
+    );
+    let expected = build(
+        "wikidata-entities-current",
+        &fixture("wikidata/entities.tsv"),
+        &[],
+    );
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-update"),
+        "wikidata-entities-updated",
+        &[
+            "--file",
+            merged.to_str().unwrap(),
+            "--delta",
+            delta.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+        ],
+        None,
+    );
+    for file in ["index.tsv", "redirects.tsv", "popularity.tsv"] {
+        assert_eq!(
+            fs::read_to_string(output.join(file)).unwrap(),
+            fs::read_to_string(expected.join(file)).unwrap(),
+            "{file} differs"
+        );
+    }
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    for (name, count) in [("added", 1), ("changed", 1), ("deleted", 1)] {
+        let prefix = format!("{name} entities:");
+        assert!(stats
+            .lines()
+            .any(|line| line.starts_with(&prefix) && line.ends_with(&format!(" {count}"))));
+    }
+
+    // only the entities sharing a surface form with the delta are resolved
+    // again, the rows of the others are reused from the previous index
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-update"),
+        "wikidata-entities-updated-previous-index",
+        &[
+            "--file",
+            merged.to_str().unwrap(),
+            "--delta",
+            delta.to_str().unwrap(),
+            "--previous-index",
+            previous.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+        ],
+        None,
+    );
+    for file in ["index.tsv", "redirects.tsv", "popularity.tsv"] {
+        assert_eq!(
+            fs::read_to_string(output.join(file)).unwrap(),
+            fs::read_to_string(expected.join(file)).unwrap(),
+            "{file} differs with --previous-index"
+        );
+    }
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    let count = |name: &str| -> usize {
+        let line = stats
+            .lines()
+            .find(|line| line.starts_with(&format!("{name}:")))
+            .unwrap();
+        line.split_whitespace().last().unwrap().parse().unwrap()
+    };
+    assert!(count("resolved entities") < count("records"));
+    assert!(count("reused entities") > 0);
+}
+
+#[test]
+fn wikidata_entities_update_type_labels() {
+    // the type of the added entity has no record, its label comes from
+    // --type-labels
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("update-type-labels");
+    fs::create_dir_all(&dir).unwrap();
+    let content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    let (header, lines) = content.split_once('\n').unwrap();
+    let (added, old): (Vec<_>, Vec<_>) = lines
+        .lines()
+        .partition(|line| line.starts_with("<http://www.wikidata.org/entity/Q90>"));
+    let old_file = dir.join("old.tsv");
+    fs::write(&old_file, format!("{header}\n{}\n", old.join("\n"))).unwrap();
+    let delta = dir.join("delta.tsv");
+    fs::write(&delta, format!("?change\t{header}\nadded\t{}\n", added[0])).unwrap();
+    let type_labels = dir.join("type-labels.tsv");
+    fs::write(
+        &type_labels,
+        "?type\t?label\n<http://www.wikidata.org/entity/Q515>\t\"city\"@en\n",
+    )
+    .unwrap();
+    let merged = dir.join("old.bin.zst");
+    run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-update-type-labels-old",
+        &[
+            "--file",
+            old_file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--save-merged",
+            merged.to_str().unwrap(),
+        ],
+        None,
+    );
+    let updated = dir.join("updated.bin.zst");
+    run(
+        env!("CARGO_BIN_EXE_kg-update"),
+        "wikidata-entities-update-type-labels",
+        &[
+            "--file",
+            merged.to_str().unwrap(),
+            "--delta",
+            delta.to_str().unwrap(),
+            "--type-labels",
+            type_labels.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--save-merged",
+            updated.to_str().unwrap(),
+        ],
+        None,
+    );
+    let records = read_entity_cache(&updated).unwrap();
+    let paris = records.iter().find(|value| value.id == "Q90").unwrap();
+    assert_eq!(paris.types, ["city"]);
+}
+
+#[test]
+fn wikidata_entities_id_map() {
+    // a rebuild with Douglas Adams keeps the numbers of the other entities
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("id-map");
+    fs::create_dir_all(&dir).unwrap();
+    let content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    let old_file = dir.join("old.tsv");
+    fs::write(
+        &old_file,
+        content
+            .lines()
+            .filter(|line| !line.starts_with("<http://www.wikidata.org/entity/Q42>"))
+            .map(|line| format!("{line}\n"))
+            .collect::<String>(),
+    )
+    .unwrap();
+    let build = |name: &str, file: &str, extra: &[&str]| {
+        let mut args = vec![
+            "--file",
+            file,
+            "--knowledge-base",
+            "wikidata",
+            "--id-format",
+            "numeric",
+        ];
+        args.extend(extra);
+        run(env!("CARGO_BIN_EXE_kg-entities"), name, &args, None)
+    };
+    let id_map = |dir: &Path| -> Vec<(usize, String)> {
+        fs::read_to_string(dir.join("id-map.tsv"))
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let (num, id) = line.split_once('\t').unwrap();
+                (num.parse().unwrap(), id.to_string())
+            })
+            .collect()
+    };
+    let old = build(
+        "wikidata-entities-id-map-old",
+        old_file.to_str().unwrap(),
+        &[],
+    );
+    let previous = old.join("id-map.tsv");
+    let new = build(
+        "wikidata-entities-id-map-new",
+        &fixture("wikidata/entities.tsv"),
+        &["--id-map", previous.to_str().unwrap()],
+    );
+    let (old_ids, new_ids) = (id_map(&old), id_map(&new));
+    assert!(new_ids.starts_with(&old_ids));
+    assert_eq!(
+        new_ids[old_ids.len()..],
+        [(
+            old_ids.len(),
+            "http://www.wikidata.org/entity/Q42".to_string()
+        )]
+    );
+    let index = fs::read_to_string(new.join("index.tsv")).unwrap();
+    assert!(index
+        .lines()
+        .all(|line| line.split('\t').next().unwrap().parse::<usize>().is_ok()));
+    let stats = fs::read_to_string(new.join("stats.txt")).unwrap();
+    assert!(stats
+        .lines()
+        .any(|line| line.starts_with("new ids:") && line.ends_with(" 1")));
+}
+
+#[test]
+fn wikidata_entities_augment_redirect_labels() {
+    // new surface forms of an entity go before the redirect labels of its
+    // existing row, which stay listed as its redirect labels
+    let redirects = fixture("wikidata/redirects.tsv");
+    let args = |file: &str| {
+        vec![
+            "--file".to_string(),
+            file.to_string(),
+            "--redirects".to_string(),
+            redirects.clone(),
+            "--knowledge-base".to_string(),
+            "wikidata".to_string(),
+            "--redirect-labels".to_string(),
+        ]
+    };
+    let existing = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-augment-existing",
+        &args(&fixture("wikidata/entities.tsv"))
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>(),
+        None,
+    );
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("augment");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    fs::write(
+        &file,
+        "?ent\t?ent_name\t?ent_description\t?links\t?types\t?aliases\n\
+         <http://www.wikidata.org/entity/Q60>\t\"New York City\"@en\t\"city\"@en\t300\t\t\"Gotham\"\n",
+    )
+    .unwrap();
+    let mut augment_args = args(file.to_str().unwrap());
+    augment_args.extend(["--augment".to_string(), existing.display().to_string()]);
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-augment",
+        &augment_args.iter().map(String::as_str).collect::<Vec<_>>(),
+        None,
+    );
+    let row = |dir: &Path, file: &str| {
+        fs::read_to_string(dir.join(file))
+            .unwrap()
+            .lines()
+            .find(|line| line.starts_with("http://www.wikidata.org/entity/Q60\t"))
+            .map(str::to_string)
+    };
+    assert_eq!(
+        row(&output, "redirect-labels.tsv"),
+        row(&existing, "redirect-labels.tsv")
+    );
+    let forms = row(&output, "index.tsv").unwrap();
+    assert!(forms.ends_with("\tGotham\tNYC (redirect)"), "{forms}");
+}
+
+#[test]
+fn wikidata_entities_literal_labels() {
+    // the year is written to literals.tsv instead of the index
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("literal-labels");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    let mut content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    content.push_str(concat!(
+        "<http://www.wikidata.org/entity/Q2432>\t\"1984\"@en\t\"year\"@en\t80\t\t\n",
+        "<http://www.wikidata.org/entity/Q208460>\t\"Nineteen Eighty-Four\"@en\t\"novel\"@en\t90\t\t\"1984\"\n",
+    ));
+    fs::write(&file, content).unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-literal-labels",
+        &[
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--literal-labels",
+            "separate",
+        ],
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(!index.contains("/Q2432\t"));
+    assert!(index.contains("/Q208460\tNineteen Eighty-Four\n"));
+    let literals = fs::read_to_string(output.join("literals.tsv")).unwrap();
+    assert_eq!(
+        literals,
+        "http://www.wikidata.org/entity/Q208460\t1984\tyear\n\
+         http://www.wikidata.org/entity/Q2432\t1984\tyear\n"
+    );
+}
+
+#[test]
+fn wikidata_entities_clean_labels() {
+    // a combining accent and a zero-width space do not make new labels
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("clean-labels");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    let mut content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    content.push_str(concat!(
+        "<http://www.wikidata.org/entity/Q72>\t\"Zu\u{308}rich\u{200b}\"@en\t\"city\"@en\t70\t\t\"Zürich\"\n",
+        "<http://www.wikidata.org/entity/Q73>\t\"\u{200b}\"@en\t\"\"@en\t10\t\t\n",
+    ));
+    fs::write(&file, content).unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-clean-labels",
+        &[
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--unicode-form",
+            "nfc",
+            "--clean-labels",
+        ],
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(index.contains("/Q72\tZürich\n"));
+    assert!(!index.contains("/Q73\t"));
+    assert!(index.contains("/Q42\tDouglas Adams\t"));
+}
+
+#[test]
+fn wikidata_entities_max_label_tokens() {
+    let file = fixture("wikidata/entities.tsv");
+    let build = |name: &str, extra: &[&str]| {
+        let mut args = vec![
+            "--file",
+            &file,
+            "--knowledge-base",
+            "wikidata",
+            "--max-label-tokens",
+            "1",
+        ];
+        args.extend(extra);
+        run(env!("CARGO_BIN_EXE_kg-entities"), name, &args, None)
+    };
+    // New York City, New York, U.S. state, Douglas Adams, and the redirect
+    let output = build("wikidata-entities-truncated-labels", &[]);
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(index.contains("/Q42\tDouglas\t"));
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    assert!(stats
+        .lines()
+        .any(|line| line.starts_with("truncated labels:") && line.ends_with(" 5")));
+    let output = build(
+        "wikidata-entities-dropped-long-labels",
+        &["--drop-long-labels"],
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(!index.contains("/Q42\t"));
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    assert!(stats
+        .lines()
+        .any(|line| line.starts_with("dropped long labels:") && line.ends_with(" 5")));
+}
+
+#[test]
+fn wikidata_entities_from_merged() {
+    // an index built from the merged entities is the same as one built from
+    // the dump
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("from-merged");
+    fs::create_dir_all(&dir).unwrap();
+    let merged = dir.join("entities.bin.zst");
+    run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-save-merged",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--redirects",
+            &fixture("wikidata/redirects.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--ignore-types",
+            "--save-merged",
+            merged.to_str().unwrap(),
+        ],
+        None,
+    );
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-from-merged",
+        &[
+            "--file",
+            merged.to_str().unwrap(),
+            "--from-merged",
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+            "--disambiguate",
+            "all",
+            "--ignore-types",
+        ],
+        None,
+    );
+    check_golden("wikidata-entities", &output);
+}
+
+#[test]
+fn wikidata_entities_alias_priors() {
+    // NYC is an alias of both New York City and the state
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-alias-priors",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--alias-priors",
+        ],
+        None,
+    );
+    let priors = fs::read_to_string(output.join("alias-priors.tsv")).unwrap();
+    assert_eq!(
+        priors,
+        "NYC\thttp://www.wikidata.org/entity/Q60\t300\t0.5455\n\
+         NYC\thttp://www.wikidata.org/entity/Q1384\t250\t0.4545\n"
+    );
+    // the index is unchanged
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(index.contains("\tNYC\t") && index.contains("\tNYC (U.S. state)\n"));
+}
+
+#[test]
+fn wikidata_entities_alias_conflicts() {
+    // NYC is an alias of New York City (300) and of the state (250), and the
+    // label of a band (10), which keeps it while the alias is contested
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("alias-conflicts");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    let mut content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    content
+        .push_str("<http://www.wikidata.org/entity/Q3342860>\t\"NYC\"@en\t\"band\"@en\t10\t\t\n");
+    fs::write(&file, content).unwrap();
+    let args = |ratio| {
+        [
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+            "--resolve-alias-conflicts",
+            "--alias-conflict-ratio",
+            ratio,
+        ]
+    };
+    // 300 is at least 1.1 times 250, so the alias goes to the city and the
+    // band is only indexed with info
+    entities("wikidata-entities-alias-conflicts", &args("1.1"));
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-alias-conflicts-contested",
+        &args("1.5"),
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(index.contains("/Q3342860\tNYC\n"));
+    assert!(index.contains("/Q60\tNew York City\tBig Apple\tNew York\tNYC (city)\n"));
+}
+
+#[test]
+fn wikidata_entities_exclude_ids() {
+    // without the other Springfield, Q151 gets the label alone
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("exclude-ids");
+    fs::create_dir_all(&dir).unwrap();
+    let ids = dir.join("ids.txt");
+    fs::write(&ids, "http://www.wikidata.org/entity/Q152\nQ1384\n").unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-exclude-ids",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--exclude-ids",
+            ids.to_str().unwrap(),
+        ],
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(index.contains("/Q151\tSpringfield\n"));
+    assert!(!index.contains("/Q152\t") && !index.contains("/Q1384\t"));
+    // the label of the excluded state goes to the city, which has it as alias
+    assert!(index.contains("/Q60\tNew York City\tBig Apple\tNYC\tNew York\n"));
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    assert!(stats.contains("excluded entities:        2\n"));
+}
+
+#[test]
+fn wikidata_entities_include_ids() {
+    // only the capital is built, so it gets Paris alone, and its type is
+    // still labeled since the class is included too
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("include-ids");
+    fs::create_dir_all(&dir).unwrap();
+    let ids = dir.join("ids.txt");
+    fs::write(&ids, "Q90\nQ515\n").unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-include-ids",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--include-ids",
+            ids.to_str().unwrap(),
+        ],
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert_eq!(
+        index,
+        "http://www.wikidata.org/entity/Q90\tParis\tCity of Light\n\
+         http://www.wikidata.org/entity/Q515\tcity\tcities\ttown\n"
+    );
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    assert!(stats.contains("not included entities:    12\n"));
+    assert!(stats.contains("entities city:            1\n"));
+}
+
+#[test]
+fn wikidata_entities_label_alias_precedence() {
+    // Paris is both the label and an alias of the son of Priam
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("label-alias-precedence");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    let content = fs::read_to_string(fixture("wikidata/entities.tsv"))
+        .unwrap()
+        .replace("\"City of Light\"", "\"City of Light; Paris\"")
+        .replace("\"Alexander\"", "\"Alexander; Paris\"");
+    fs::write(&file, content).unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-label-alias-precedence",
+        &[
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--label-alias-precedence",
+            "merge",
+        ],
+        None,
+    );
+    let sources = fs::read_to_string(output.join("surface-form-sources.tsv")).unwrap();
+    assert_eq!(
+        sources,
+        "http://www.wikidata.org/entity/Q167646\tParis\tlabel+alias\n"
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(index.contains("/Q167646\tParis\tAlexander\tParis (human)\n"));
+}
+
+#[test]
+fn wikidata_entities_dropped_aliases() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dropped-aliases");
+    fs::create_dir_all(&dir).unwrap();
+    let stoplist = dir.join("stoplist.txt");
+    fs::write(&stoplist, "People\n").unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-dropped-aliases",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--dropped-aliases",
+            "--alias-stoplist",
+            stoplist.to_str().unwrap(),
+            "--min-alias-length",
+            "3",
+        ],
+        None,
+    );
+    let dropped = fs::read_to_string(output.join("dropped-aliases.tsv")).unwrap();
+    // the shared aliases all get info, so only filtered aliases are dropped
+    assert_eq!(
+        dropped.lines().collect::<Vec<_>>(),
+        [
+            "http://www.wikidata.org/entity/Q1384\tNY\ttoo short\t",
+            "http://www.wikidata.org/entity/Q183\tDE\ttoo short\t",
+            "http://www.wikidata.org/entity/Q5\tpeople\tstoplist\t",
+        ]
+    );
+}
+
+#[test]
+fn wikidata_entities_label_info_variants() {
+    // Gotham has no info, so it gets no variant, and its alias only looks
+    // like a label with info
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("label-info-variants");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    let mut content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    content.push_str(
+        "<http://www.wikidata.org/entity/Q903>\t\"Gotham\"@en\t\"\"@en\t5\t\t\"Gotham (comics)\"\n",
+    );
+    fs::write(&file, content).unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-label-info-variants",
+        &[
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--label-info-variants",
+            "--output-format",
+            "jsonl",
+        ],
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.jsonl")).unwrap();
+    let ent = |id: &str| -> serde_json::Value {
+        index
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .find(|value: &serde_json::Value| {
+                value["id"] == format!("http://www.wikidata.org/entity/{id}")
+            })
+            .unwrap()
+    };
+    let germany = ent("Q183");
+    assert_eq!(germany["label"], "Germany");
+    assert_eq!(germany["label_info"], "Germany (country in Central Europe)");
+    assert_eq!(germany["aliases"], serde_json::json!(["DE", "Deutschland"]));
+    let gotham = ent("Q903");
+    assert_eq!(gotham["label"], "Gotham");
+    assert_eq!(gotham["label_info"], serde_json::Value::Null);
+    assert_eq!(gotham["aliases"], serde_json::json!(["Gotham (comics)"]));
+}
+
+#[test]
+fn wikidata_entities_merge_duplicate_chains() {
+    // Q830149 is a duplicate of Q152, which is a duplicate of Q151, but
+    // the lines come in the other order
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("duplicate-chains");
+    fs::create_dir_all(&dir).unwrap();
+    let duplicates = dir.join("duplicates.tsv");
+    fs::write(
+        &duplicates,
+        concat!(
+            "<http://www.wikidata.org/entity/Q152>\t<http://www.wikidata.org/entity/Q151>\n",
+            "<http://www.wikidata.org/entity/Q830149>\t<http://www.wikidata.org/entity/Q152>\n",
+        ),
+    )
+    .unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-merge-duplicate-chains",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--merge-duplicates",
+            duplicates.to_str().unwrap(),
+            "--output-format",
+            "jsonl",
+        ],
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.jsonl")).unwrap();
+    let ents: Vec<_> = index
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect();
+    let ent = |id: &str| {
+        ents.iter()
+            .find(|value| value["id"] == format!("http://www.wikidata.org/entity/{id}"))
+    };
+    assert!(ent("Q152").is_none());
+    assert!(ent("Q830149").is_none());
+    // the label of Q830149 reached Q151 through Q152
+    assert_eq!(
+        ent("Q151").unwrap()["aliases"],
+        serde_json::json!(["Paris (city)"])
+    );
+}
+
+#[test]
+fn wikidata_entities_specific_types() {
+    // Metropolis is a city and a human settlement, Smallville a big city,
+    // which has no label
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("specific-types");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    let mut content = fs::read_to_string(fixture("wikidata/entities.tsv")).unwrap();
+    content.push_str(concat!(
+        "<http://www.wikidata.org/entity/Q486972>\t\"human settlement\"@en\t\"\"@en\t190\t\t\n",
+        "<http://www.wikidata.org/entity/Q901>\t\"Metropolis\"@en\t\"\"@en\t30\t",
+        "<http://www.wikidata.org/entity/Q515>; <http://www.wikidata.org/entity/Q486972>\t\n",
+        "<http://www.wikidata.org/entity/Q902>\t\"Smallville\"@en\t\"\"@en\t20\t",
+        "<http://www.wikidata.org/entity/Q1549591>\t\n",
+    ));
+    fs::write(&file, content).unwrap();
+    let subclasses = dir.join("subclasses.tsv");
+    fs::write(
+        &subclasses,
+        concat!(
+            "<http://www.wikidata.org/entity/Q515>\t<http://www.wikidata.org/entity/Q486972>\n",
+            "<http://www.wikidata.org/entity/Q1549591>\t<http://www.wikidata.org/entity/Q515>\n",
+        ),
+    )
+    .unwrap();
+    let infos = |name: &str, extra: &[&str]| {
+        let mut args = vec![
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--output-format",
+            "jsonl",
+        ];
+        args.extend(extra);
+        let output = run(env!("CARGO_BIN_EXE_kg-entities"), name, &args, None);
+        let index = fs::read_to_string(output.join("index.jsonl")).unwrap();
+        let info = |ent: &str| {
+            index
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                .find(|value| value["id"] == format!("http://www.wikidata.org/entity/{ent}"))
+                .unwrap()["info"]
+                .clone()
+        };
+        (info("Q901"), info("Q902"))
+    };
+    let (metropolis, smallville) = infos("wikidata-entities-without-specific-types", &[]);
+    assert_eq!(metropolis, "human settlement");
+    assert_eq!(smallville, "");
+    let (metropolis, smallville) = infos(
+        "wikidata-entities-specific-types",
+        &[
+            "--subclass-of",
+            subclasses.to_str().unwrap(),
+            "--specific-types",
+        ],
+    );
+    assert_eq!(metropolis, "city");
+    assert_eq!(smallville, "city");
+}
+
+#[test]
+fn wikidata_entities_min_count() {
+    // without the less popular Parises, the capital gets its label alone
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-min-count",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--min-count",
+            "100",
+        ],
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert_eq!(index.lines().count(), 9);
+    assert!(index.contains("/Q90\tParis\t"));
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    assert!(stats
+        .lines()
+        .any(|line| line.starts_with("below min count:") && line.ends_with(" 5")));
+}
+
+#[test]
+fn wikidata_entities_diff() {
+    // a newer dump renames Germany, drops DNA, and adds Berlin
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("diff");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    let mut content = fs::read_to_string(fixture("wikidata/entities.tsv"))
+        .unwrap()
+        .replace("\"Germany\"@en", "\"Federal Republic of Germany\"@en")
+        .lines()
+        .filter(|line| !line.starts_with("<http://www.wikidata.org/entity/Q7430>"))
+        .join("\n");
+    content.push_str(
+        "\n<http://www.wikidata.org/entity/Q64>\t\"Berlin\"@en\t\"capital of Germany\"@en\t\
+         350\t<http://www.wikidata.org/entity/Q515>\t\n",
+    );
+    fs::write(&file, content).unwrap();
+    let build = |name, file: &str, date| {
+        let args = [
+            "--file",
+            file,
+            "--knowledge-base",
+            "wikidata",
+            "--dump-date",
+            date,
+        ];
+        run(env!("CARGO_BIN_EXE_kg-entities"), name, &args, None)
+    };
+    let old = build(
+        "wikidata-entities-diff-old",
+        &fixture("wikidata/entities.tsv"),
+        "2024-01-01",
+    );
+    let new = build(
+        "wikidata-entities-diff-new",
+        file.to_str().unwrap(),
+        "2024-06-01",
+    );
+    // the directories are given the wrong way round and compared in the
+    // order of their dump dates
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-diff"),
+        "wikidata-entities-diff",
+        &[
+            "--old",
+            new.to_str().unwrap(),
+            "--new",
+            old.to_str().unwrap(),
+            "--temporal",
+        ],
+        Some("changes.tsv"),
+    );
+    check_golden("wikidata-entities-diff", &output);
+
+    // without dump dates there is no order to compare in
+    let undated = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-diff-undated",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+        ],
+        None,
+    );
+    let error = run_error(
+        env!("CARGO_BIN_EXE_kg-diff"),
+        &[
+            "--old",
+            undated.to_str().unwrap(),
+            "--new",
+            new.to_str().unwrap(),
+            "--temporal",
+        ],
+    );
+    assert!(error.contains("--temporal needs dump dates for both directories"));
+}
+
+#[test]
+fn wikidata_dry_run_wrong_knowledge_base() {
+    // the Wikidata iris are no Freebase ids, which a dry run reports
+    // without building anything
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dry-run");
+    for (bin, file) in [
+        (env!("CARGO_BIN_EXE_kg-entities"), "wikidata/entities.tsv"),
+        (
+            env!("CARGO_BIN_EXE_kg-properties"),
+            "wikidata/properties.tsv",
+        ),
+    ] {
+        let error = run_error(
+            bin,
+            &[
+                "--file",
+                &fixture(file),
+                "--knowledge-base",
+                "freebase",
+                "--dry-run",
+                "--output",
+                output.to_str().unwrap(),
+            ],
+        );
+        assert!(
+            error.contains("none of the") && error.contains("sampled records could be parsed"),
+            "{error}"
+        );
+        assert!(!output.exists());
+    }
+}
+
+#[test]
+fn wikidata_entities_manifest_and_force() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("manifest-force");
+    if output.exists() {
+        fs::remove_dir_all(&output).unwrap();
+    }
+    let build = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_kg-entities"))
+            .args(["--file", &fixture("wikidata/entities.tsv")])
+            .args(["--knowledge-base", "wikidata", "--output"])
+            .arg(&output)
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+    assert!(build(&["--alias-priors"]).status.success());
+
+    // every other file is listed with its number of lines and checksum
+    let manifest = fs::read_to_string(output.join("manifest.tsv")).unwrap();
+    let mut listed = vec![];
+    for line in manifest.lines() {
+        let (name, rows, checksum) = line.split('\t').collect_tuple().unwrap();
+        let content = fs::read(output.join(name)).unwrap();
+        let num_lines = content.iter().filter(|&&b| b == b'\n').count();
+        assert_eq!(rows, num_lines.to_string(), "{name}");
+        let digest = Sha256::digest(&content);
+        assert_eq!(checksum, format!("{digest:x}"), "{name}");
+        listed.push(name.to_string());
+    }
+    let mut unlisted = files(&output);
+    unlisted.retain(|file| file != "manifest.tsv");
+    assert_eq!(listed, unlisted);
+    assert!(listed.contains(&"alias-priors.tsv".to_string()));
+
+    let result = build(&[]);
+    assert!(!result.status.success());
+    let error = String::from_utf8_lossy(&result.stderr);
+    assert!(error.contains("already contains a build, pass --force to overwrite it"));
+    assert_eq!(
+        fs::read_to_string(output.join("manifest.tsv")).unwrap(),
+        manifest
+    );
+
+    // files of the previous build that are not written again are removed
+    assert!(build(&["--force"]).status.success());
+    assert!(!output.join("alias-priors.tsv").exists());
+    assert!(!fs::read_to_string(output.join("manifest.tsv"))
+        .unwrap()
+        .contains("alias-priors.tsv"));
+}
+
+#[test]
+fn wikidata_properties_skip_unchanged() {
+    // a second build with the same arguments and inputs is skipped, a
+    // changed input file is rebuilt
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("skip-unchanged");
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("properties.tsv");
+    fs::copy(fixture("wikidata/properties.tsv"), &input).unwrap();
+    let output = dir.join("output");
+    let build_with = |args: &[&str]| {
+        let result = Command::new(env!("CARGO_BIN_EXE_kg-properties"))
+            .args(args)
+            .arg("--file")
+            .arg(&input)
+            .arg("--output")
+            .arg(&output)
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        !result.stdout.is_empty()
+    };
+    let build = || build_with(&["--knowledge-base", "wikidata", "--skip-unchanged"]);
+    assert!(build());
+    assert!(output.join("fingerprint.txt").exists());
+    assert!(!build());
+    // the same arguments in another order and with options that do not
+    // change the outputs
+    assert!(!build_with(&[
+        "--skip-unchanged",
+        "--progress",
+        "--knowledge-base=wikidata",
+    ]));
+    assert!(build_with(&[
+        "--skip-unchanged",
+        "--knowledge-base",
+        "wikidata",
+        "--min-count",
+        "1",
+    ]));
+    assert!(build());
+    let mut content = fs::read_to_string(&input).unwrap();
+    content.push('\n');
+    fs::write(&input, content).unwrap();
+    assert!(build());
+}
+
+#[test]
+fn wikidata_candidates() {
+    let index = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden/wikidata-entities")
+        .join("index.tsv");
+    let generator = CandidateGenerator::from_index(index, FileFormat::Tsv).unwrap();
+    let candidates = |mention: &str| -> Vec<(String, MatchKind)> {
+        generator
+            .candidates(mention)
+            .into_iter()
+            .map(|c| (c.id.rsplit('/').next().unwrap().to_string(), c.kind))
+            .collect()
+    };
+    // the most popular Paris comes first, the others match without their
+    // parenthesized suffix
+    assert_eq!(
+        candidates("Paris"),
+        [
+            ("Q90".to_string(), MatchKind::Exact),
+            ("Q167646".to_string(), MatchKind::CaseFolded),
+            ("Q830149".to_string(), MatchKind::CaseFolded),
+        ]
+    );
+    assert_eq!(
+        candidates("Dóuglas ADAMS"),
+        [("Q42".to_string(), MatchKind::AsciiFolded)]
+    );
+    assert_eq!(
+        candidates("Germani"),
+        [("Q183".to_string(), MatchKind::Fuzzy(1))]
+    );
+    assert!(candidates("Gremlins").is_empty());
+    // two edits away, so only a near miss
+    assert!(candidates("Gremany").is_empty());
+    assert_eq!(
+        generator.near_misses("Gremany"),
+        NearMisses {
+            folded: "gremany".to_string(),
+            fuzzy_skipped: false,
+            prefix: vec!["Germany".to_string()],
+            closest: vec![("Germany".to_string(), 2)],
+        }
+    );
+    // short mentions are never looked up fuzzily
+    let near_misses = generator.near_misses("Nx");
+    assert!(near_misses.fuzzy_skipped);
+    assert!(near_misses.prefix.contains(&"NY".to_string()));
+}
+
+/// Serves the responses in order, one per connection, and returns the
+/// endpoint url and the handle yielding the received request bodies.
+#[cfg(feature = "fetch")]
+fn serve(responses: Vec<String>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    use std::io::{BufRead, BufReader, Read};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut bodies = vec![];
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            bodies.push(String::from_utf8(body).unwrap());
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+        bodies
+    });
+    (url, handle)
+}
+
+#[test]
+#[cfg(feature = "fetch")]
+fn wikidata_fetch() {
+    // the first request fails and is retried, the short second page is the
+    // last one
+    let ok = |body: &str| {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    };
+    let header = "?ent\t?same\n";
+    let (url, handle) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n".to_string(),
+        ok(&format!("{header}wd:Q1\twd:Q2\nwd:Q3\twd:Q4\n")),
+        ok(&format!("{header}wd:Q5\twd:Q6\n")),
+    ]);
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-fetch"),
+        "wikidata-fetch",
+        &[
+            "--knowledge-base",
+            "wikidata",
+            "--query",
+            "same-as",
+            "--endpoint",
+            &url,
+            "--page-size",
+            "2",
+            "--retries",
+            "1",
+        ],
+        Some("same-as.tsv"),
+    );
+    check_golden("wikidata-fetch", &output);
+    let bodies = handle.join().unwrap();
+    assert!(bodies[0].contains("wdt%3AP460") && bodies[0].ends_with("LIMIT+2+OFFSET+0"));
+    assert!(bodies[2].ends_with("LIMIT+2+OFFSET+2"));
+}
+
+#[test]
+#[cfg(feature = "fetch")]
+fn wikidata_entities_fetched_type_labels() {
+    // human settlement is a superclass of city, but not in the entity file
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fetched-type-labels");
+    fs::create_dir_all(&dir).unwrap();
+    let subclass_of = dir.join("subclass-of.tsv");
+    let iri = |id: &str| format!("<http://www.wikidata.org/entity/{id}>");
+    fs::write(
+        &subclass_of,
+        format!("?ent\t?superclass\n{}\t{}\n", iri("Q515"), iri("Q486972")),
+    )
+    .unwrap();
+    let cache = dir.join("type-label-cache.tsv");
+    if cache.exists() {
+        fs::remove_file(&cache).unwrap();
+    }
+    let body = format!(
+        "?ent\t?label\n{}\t\"human settlement\"@en\n",
+        iri("Q486972")
+    );
+    let (url, handle) = serve(vec![format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )]);
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-fetched-type-labels",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--subclass-of",
+            subclass_of.to_str().unwrap(),
+            "--class-types",
+            "--fetch-type-labels",
+            "--type-label-endpoint",
+            &url,
+            "--type-label-cache",
+            cache.to_str().unwrap(),
+        ],
+        None,
+    );
+    let bodies = handle.join().unwrap();
+    assert!(bodies[0].contains("Q486972"));
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    assert!(stats.contains("fetched type labels:") && stats.contains("missing type labels:"));
+    assert_eq!(
+        fs::read_to_string(&cache).unwrap(),
+        "http://www.wikidata.org/entity/Q486972\thuman settlement\n"
+    );
+}
+
+#[test]
+fn wikidata_entities_compressed() {
+    // compressed inputs are decompressed on the fly, the outputs stay the same
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("compressed");
+    fs::create_dir_all(&dir).unwrap();
+    let compress = |name: &str, ext: &str| {
+        let content = fs::read(fixture(&format!("wikidata/{name}.tsv"))).unwrap();
+        let file = dir.join(format!("{name}.tsv.{ext}"));
+        let compressed = match ext {
+            "gz" => {
+                let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(&content).unwrap();
+                encoder.finish().unwrap()
+            }
+            _ => zstd::encode_all(&content[..], 0).unwrap(),
+        };
+        fs::write(&file, compressed).unwrap();
+        file.display().to_string()
+    };
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-entities-compressed",
+        &[
+            "--file",
+            &compress("entities", "gz"),
+            "--redirects",
+            &compress("redirects", "zst"),
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+            "--disambiguate",
+            "all",
+            "--ignore-types",
+        ],
+        None,
+    );
+    check_golden("wikidata-entities", &output);
+}
+
+#[test]
+fn wikidata_entities_with_types() {
+    entities(
+        "wikidata-entities-types",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+        ],
+    );
+}
+
+#[test]
+fn wikidata_entities_jsonl() {
+    entities(
+        "wikidata-entities-jsonl",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--redirects",
+            &fixture("wikidata/redirects.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--check-for-popular-aliases",
+            "--redirect-labels",
+            "--output-format",
+            "jsonl",
+        ],
+    );
+}
+
+#[test]
+fn wikidata_embeddings() {
+    // vectors are matched by iri and by short id, the rows without one get
+    // zeros and the vector of an entity that is not indexed is unused
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-embeddings"),
+        "wikidata-embeddings",
+        &[
+            "--file",
+            &golden_file("wikidata-entities/index.tsv"),
+            "--embeddings",
+            &fixture("wikidata/embeddings.txt"),
+            "--knowledge-base",
+            "wikidata",
+        ],
+        Some("embeddings.tsv"),
+    );
+    check_golden("wikidata-embeddings", &output);
+}
+
+#[test]
+fn wikidata_frequency() {
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-frequency"),
+        "wikidata-frequency",
+        &[
+            "--file",
+            &golden_file("wikidata-entities/index.tsv"),
+            "--corpus",
+            &fixture("wikidata/corpus.txt"),
+        ],
+        Some("frequency.tsv"),
+    );
+    check_golden("wikidata-frequency", &output);
+}
+
+#[test]
+fn wikidata_eval_linking() {
+    // the capital is the first Paris and the son of Priam the second, like
+    // the state is the second NYC after the more popular city
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-eval-linking"),
+        "wikidata-eval-linking",
+        &[
+            "--file",
+            &fixture("wikidata/mentions.tsv"),
+            "--index",
+            &golden_file("wikidata-entities/index.tsv"),
+            "--knowledge-base",
+            "wikidata",
+        ],
+        Some("linking.tsv"),
+    );
+    check_golden("wikidata-eval-linking", &output);
+}
+
+#[test]
+fn wikidata_properties() {
+    properties(
+        "wikidata-properties",
+        &[
+            "--file",
+            &fixture("wikidata/properties.tsv"),
+            "--knowledge-base",
+            "wikidata",
+            "--include-wikidata-qualifiers",
+        ],
+    );
+}
+
+#[test]
+fn wikidata_property_hierarchy() {
+    // location in the administrative entity is a location by the parents
+    // column, father a relative by the subproperty file
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("property-hierarchy");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("properties.tsv");
+    let content: String = fs::read_to_string(fixture("wikidata/properties.tsv"))
+        .unwrap()
+        .lines()
+        .map(|line| match line {
+            _ if line.starts_with('?') => format!("{line}\t?p_parents\n"),
+            _ if line.contains("/P131>") => {
+                format!("{line}\t<http://www.wikidata.org/entity/P276>\n")
+            }
+            _ => format!("{line}\t\n"),
+        })
+        .collect();
+    fs::write(&file, content).unwrap();
+    let subproperties = dir.join("subproperties.tsv");
+    fs::write(
+        &subproperties,
+        "?p\t?parent\n<http://www.wikidata.org/entity/P22>\t<http://www.wikidata.org/entity/P1038>\n",
+    )
+    .unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-properties"),
+        "wikidata-property-hierarchy",
+        &[
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "wikidata",
+            "--subproperties",
+            subproperties.to_str().unwrap(),
+        ],
+        None,
+    );
+    let hierarchy = fs::read_to_string(output.join("hierarchy.tsv")).unwrap();
+    assert_eq!(
+        hierarchy,
+        "http://www.wikidata.org/prop/direct/P22\thttp://www.wikidata.org/prop/direct/P1038\t\
+         father\trelative\n\
+         http://www.wikidata.org/prop/direct/P131\thttp://www.wikidata.org/prop/direct/P276\t\
+         located in the administrative territorial entity\tlocation\n"
+    );
+    // subproperties inherit the labels of their parents
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(index.contains(
+        "/P131\tlocated in the administrative territorial entity\tin\tlocation\tvenue\n"
+    ));
+}
+
+#[test]
+fn wikidata_qualifiers() {
+    let index = run(
+        env!("CARGO_BIN_EXE_kg-properties"),
+        "wikidata-qualifier-properties",
+        &[
+            "--file",
+            &fixture("wikidata/properties.tsv"),
+            "--knowledge-base",
+            "wikidata",
+        ],
+        None,
+    );
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-qualifiers"),
+        "wikidata-qualifiers",
+        &["--file", &index.join("index.tsv").display().to_string()],
+        Some("qualifiers.tsv"),
+    );
+    check_golden("wikidata-qualifiers", &output);
+}
+
+#[test]
+fn freebase_entities() {
+    entities(
+        "freebase-entities",
+        &[
+            "--file",
+            &fixture("freebase/entities.tsv"),
+            "--knowledge-base",
+            "freebase",
+            "--check-for-popular-aliases",
+            "--disambiguate",
+            "all",
+            "--ignore-types",
+        ],
+    );
+}
+
+#[test]
+fn freebase_wikidata_mapping() {
+    let freebase = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "freebase-mapping-index",
+        &[
+            "--file",
+            &fixture("freebase/entities.tsv"),
+            "--knowledge-base",
+            "freebase",
+        ],
+        None,
+    );
+    let wikidata = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "wikidata-mapping-index",
+        &[
+            "--file",
+            &fixture("wikidata/entities.tsv"),
+            "--knowledge-base",
+            "wikidata",
+        ],
+        None,
+    );
+    // sameAs triples of fb2w.nt and a P646 row, Barack Obama (Q76) is not
+    // in the Wikidata index
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("freebase-wikidata-mapping");
+    fs::create_dir_all(&dir).unwrap();
+    let mapping = dir.join("fb2w.nt");
+    fs::write(
+        &mapping,
+        concat!(
+            "<http://rdf.freebase.com/ns/m.02mjmr>\t<http://www.w3.org/2002/07/owl#sameAs>\t",
+            "<http://www.wikidata.org/entity/Q76> .\n",
+            "<http://rdf.freebase.com/ns/m.05kkh>\t<http://www.w3.org/2002/07/owl#sameAs>\t",
+            "<http://www.wikidata.org/entity/Q1384> .\n",
+            "Q1384\t/m/05kkh\n",
+            "invalid\n",
+        ),
+    )
+    .unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-index"),
+        "freebase-wikidata-mapping",
+        &[
+            "mapping",
+            "--freebase",
+            freebase.to_str().unwrap(),
+            "--wikidata",
+            wikidata.to_str().unwrap(),
+            "--mapping",
+            mapping.to_str().unwrap(),
+        ],
+        Some("mapping.tsv"),
+    );
+    let mapped = fs::read_to_string(output.join("mapping.tsv")).unwrap();
+    assert_eq!(
+        mapped,
+        "http://rdf.freebase.com/ns/m.05kkh\thttp://www.wikidata.org/entity/Q1384\tOhio\tNew York\n"
+    );
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    for (name, value) in [("invalid lines:", " 1"), ("not in wikidata index:", " 1")] {
+        assert!(stats
+            .lines()
+            .any(|line| line.starts_with(name) && line.ends_with(value)));
+    }
+}
+
+#[test]
+fn freebase_properties() {
+    properties(
+        "freebase-properties",
+        &[
+            "--file",
+            &fixture("freebase/properties.tsv"),
+            "--knowledge-base",
+            "freebase",
+        ],
+    );
+}
+
+#[test]
+fn freebase_property_types() {
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-properties"),
+        "freebase-property-types",
+        &[
+            "--file",
+            &fixture("freebase/properties.tsv"),
+            "--knowledge-base",
+            "freebase",
+            "--freebase-types",
+        ],
+        None,
+    );
+    let types = fs::read_to_string(output.join("types.tsv")).unwrap();
+    // the location type takes the label from the location domain
+    assert_eq!(
+        types,
+        concat!(
+            "http://rdf.freebase.com/ns/film\tfilm domain\n",
+            "http://rdf.freebase.com/ns/film.film\tfilm\n",
+            "http://rdf.freebase.com/ns/location\tlocation domain\n",
+            "http://rdf.freebase.com/ns/location.location\tlocation\n",
+            "http://rdf.freebase.com/ns/people\tpeople\tpeople domain\n",
+            "http://rdf.freebase.com/ns/people.person\tperson\tpeople person\n",
+        )
+    );
+}
+
+#[test]
+fn dbpedia_entities() {
+    entities(
+        "dbpedia-entities",
+        &[
+            "--file",
+            &fixture("dbpedia/entities.tsv"),
+            "--redirects",
+            &fixture("dbpedia/redirects.tsv"),
+            "--knowledge-base",
+            "dbpedia",
+            "--check-for-popular-aliases",
+            "--disambiguate",
+            "all",
+            "--ignore-types",
+        ],
+    );
+}
+
+#[test]
+fn dbpedia_entities_title_aliases() {
+    // the name of the Trojan prince belongs to the city, so his title
+    // becomes an alias with the qualifier as info
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("title-aliases");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("entities.tsv");
+    let mut content = fs::read_to_string(fixture("dbpedia/entities.tsv")).unwrap();
+    content.push_str(concat!(
+        "<http://dbpedia.org/resource/Paris>\t\"Paris\"@en\t\"\"\t900\t\t\n",
+        "<http://dbpedia.org/resource/Paris_(mythology)>\t\"Alexandros\"@en\t\"\"\t50\t\t\n",
+    ));
+    fs::write(&file, content).unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_kg-entities"),
+        "dbpedia-entities-title-aliases",
+        &[
+            "--file",
+            file.to_str().unwrap(),
+            "--knowledge-base",
+            "dbpedia",
+            "--ignore-types",
+            "--title-aliases",
+        ],
+        None,
+    );
+    let index = fs::read_to_string(output.join("index.tsv")).unwrap();
+    assert!(index.contains("/Paris_(mythology)\tAlexandros\tParis (mythology)\n"));
+    // the name of Berlin, New Hampshire has no qualifier
+    assert!(index.contains("/Berlin,_New_Hampshire\tBerlin (city in New Hampshire)\n"));
+    let stats = fs::read_to_string(output.join("stats.txt")).unwrap();
+    assert!(stats
+        .lines()
+        .any(|line| line.starts_with("title aliases:") && line.ends_with(" 1")));
+}
+
+#[test]
+fn dbpedia_properties() {
+    properties(
+        "dbpedia-properties",
+        &[
+            "--file",
+            &fixture("dbpedia/properties.tsv"),
+            "--knowledge-base",
+            "dbpedia",
+        ],
+    );
+}